@@ -22,6 +22,12 @@ pub struct Director {
     director_tx: mpsc::Sender<DirectorCommand>,
 }
 
+impl Default for Director {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Director {
     pub fn new() -> Self {
         let routing_table = Arc::new(RwLock::new(HashMap::new()));
@@ -63,7 +63,7 @@ impl Worker {
                     })) => {
                         // Write data to the physical agent
                         let payload = format!("{}\n", input);
-                        if let Err(_) = stdin.write_all(payload.as_bytes()).await {
+                        if stdin.write_all(payload.as_bytes()).await.is_err() {
                             let _ = reply_channel.send(Err("Failed to write to agent".to_string()));
                             continue;
                         }
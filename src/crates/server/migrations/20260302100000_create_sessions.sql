@@ -0,0 +1,32 @@
+-- Conversation sessions shared by multi-turn agent and flow executions
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    name TEXT,
+    max_tokens INTEGER, -- Token budget of the stored history, NULL means unlimited
+    strategy TEXT NOT NULL DEFAULT 'truncate', -- truncate, summarize
+    summarizer_task_id TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_summarizer_task
+        FOREIGN KEY (summarizer_task_id)
+        REFERENCES agent_tasks(id)
+        ON DELETE SET NULL
+);
+
+-- Ordered message history of each session
+CREATE TABLE IF NOT EXISTS session_messages (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    role TEXT NOT NULL, -- system, user, assistant
+    content TEXT NOT NULL,
+    token_count INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_session
+        FOREIGN KEY (session_id)
+        REFERENCES sessions(id)
+        ON DELETE CASCADE
+);
+
+-- A position holds a single message of the session
+CREATE UNIQUE INDEX IF NOT EXISTS idx_session_messages_position ON session_messages (session_id, position);
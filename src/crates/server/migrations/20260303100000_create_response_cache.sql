@@ -0,0 +1,18 @@
+-- Optional persistent backing of the response cache
+CREATE TABLE IF NOT EXISTS response_cache (
+    key TEXT PRIMARY KEY, -- SHA-256 of the task id and the normalized payload
+    task_id TEXT NOT NULL,
+    response JSONB NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_task
+        FOREIGN KEY (task_id)
+        REFERENCES agent_tasks(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_response_cache_expires ON response_cache (expires_at);
+
+-- Trace which task produced a log entry and whether it was served from the cache
+ALTER TABLE agent_logs ADD COLUMN IF NOT EXISTS task_id TEXT;
+ALTER TABLE agent_logs ADD COLUMN IF NOT EXISTS cache_hit BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Fallback target (index, task, agent, model, provider) that handled a logged call
+ALTER TABLE agent_logs ADD COLUMN IF NOT EXISTS target JSONB;
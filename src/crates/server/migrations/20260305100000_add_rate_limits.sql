@@ -0,0 +1,12 @@
+-- Per agent limits, e.g. {"requests_per_second": 2, "max_concurrent": 4, "mode": "queue"}
+ALTER TABLE agents ADD COLUMN IF NOT EXISTS rate_limit JSONB;
+
+-- Limits of provider credentials, identified by the environment variable holding the key
+CREATE TABLE IF NOT EXISTS provider_limits (
+    credential TEXT PRIMARY KEY,
+    requests_per_minute INTEGER,
+    tokens_per_minute INTEGER,
+    mode TEXT NOT NULL DEFAULT 'queue', -- queue, reject
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
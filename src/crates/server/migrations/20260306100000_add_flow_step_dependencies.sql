@@ -0,0 +1,5 @@
+-- Flows become DAGs: steps may be named and declare the steps they depend on.
+-- A NULL depends_on keeps the linear behaviour (depends on the previous step by step_order),
+-- an empty array makes the step a root receiving the flow input.
+ALTER TABLE flow_steps ADD COLUMN IF NOT EXISTS name TEXT;
+ALTER TABLE flow_steps ADD COLUMN IF NOT EXISTS depends_on JSONB;
@@ -0,0 +1,6 @@
+-- Steps are no longer always agent tasks: routers only choose the downstream branch
+ALTER TABLE flow_steps ADD COLUMN IF NOT EXISTS step_type TEXT NOT NULL DEFAULT 'task'; -- task, router
+ALTER TABLE flow_steps ALTER COLUMN task_id DROP NOT NULL;
+
+-- Branches chosen by the router steps of an execution
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS branches JSONB;
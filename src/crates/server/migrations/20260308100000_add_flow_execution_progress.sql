@@ -0,0 +1,2 @@
+-- Per-step progress of an execution, polled while the flow runs in the background
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS steps JSONB;
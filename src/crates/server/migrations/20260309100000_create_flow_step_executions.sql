@@ -0,0 +1,22 @@
+-- One record per step of a flow execution: resolved payload, response and timing
+CREATE TABLE IF NOT EXISTS flow_step_executions (
+    id TEXT PRIMARY KEY,
+    execution_id TEXT NOT NULL,
+    step_id TEXT NOT NULL,
+    step_name TEXT NOT NULL,
+    status TEXT NOT NULL, -- Running, Completed, Failed, Skipped
+    payload JSONB,
+    response JSONB,
+    error TEXT,
+    retries INTEGER NOT NULL DEFAULT 0,
+    agent_log_id TEXT, -- Last agent log written by the step
+    started_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    completed_at TIMESTAMP WITH TIME ZONE,
+    duration_ms BIGINT,
+    CONSTRAINT fk_step_execution
+        FOREIGN KEY (execution_id)
+        REFERENCES flow_executions(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_flow_step_executions_execution ON flow_step_executions (execution_id, started_at);
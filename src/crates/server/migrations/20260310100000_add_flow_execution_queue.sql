@@ -0,0 +1,9 @@
+-- Flow executions double as a durable job queue: Pending rows are claimed by the executor
+-- workers of any gateway instance, Running rows with a stale heartbeat are requeued
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS session_id TEXT;
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS claimed_by TEXT; -- Worker running the execution
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS heartbeat_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_flow_executions_pending ON flow_executions (started_at) WHERE status = 'Pending';
+CREATE INDEX IF NOT EXISTS idx_flow_executions_running ON flow_executions (heartbeat_at) WHERE status = 'Running';
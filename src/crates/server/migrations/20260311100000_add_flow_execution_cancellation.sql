@@ -0,0 +1,2 @@
+-- Cancellation requested through the API, picked up by the worker running the execution
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS cancel_requested BOOLEAN NOT NULL DEFAULT FALSE;
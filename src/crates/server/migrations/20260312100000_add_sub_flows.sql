@@ -0,0 +1,6 @@
+-- Sub-flow steps run another flow in an execution of its own, linked to the parent execution
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS parent_execution_id TEXT REFERENCES flow_executions(id) ON DELETE CASCADE;
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS parent_step_id TEXT; -- Step of the parent flow running the sub-flow
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS depth INTEGER NOT NULL DEFAULT 0; -- 0 for top-level executions
+
+CREATE INDEX IF NOT EXISTS idx_flow_executions_parent ON flow_executions (parent_execution_id);
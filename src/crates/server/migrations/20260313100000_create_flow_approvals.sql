@@ -0,0 +1,26 @@
+-- Approval steps pause their execution until a reviewer approves (possibly editing) or rejects the data
+CREATE TABLE IF NOT EXISTS flow_approvals (
+    id TEXT PRIMARY KEY,
+    execution_id TEXT NOT NULL,
+    flow_id TEXT NOT NULL,
+    step_id TEXT NOT NULL,
+    step_name TEXT NOT NULL,
+    step_execution_id TEXT, -- Record of the step in flow_step_executions
+    status TEXT NOT NULL, -- Pending, Approved, Rejected, TimedOut, Cancelled
+    message TEXT, -- Rendered instructions for the reviewer
+    data JSONB, -- Data submitted for review
+    response JSONB, -- Approved data, edited by the reviewer or unchanged
+    reviewer TEXT,
+    comment TEXT,
+    on_timeout TEXT NOT NULL DEFAULT 'reject',
+    expires_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    decided_at TIMESTAMP WITH TIME ZONE,
+    CONSTRAINT fk_approval_execution
+        FOREIGN KEY (execution_id)
+        REFERENCES flow_executions(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_flow_approvals_pending ON flow_approvals (created_at) WHERE status = 'Pending';
+CREATE INDEX IF NOT EXISTS idx_flow_approvals_execution ON flow_approvals (execution_id);
@@ -0,0 +1,38 @@
+-- Cron triggers of flows, evaluated by the scheduler of the gateway
+CREATE TABLE IF NOT EXISTS flow_schedules (
+    id TEXT PRIMARY KEY,
+    flow_id TEXT NOT NULL,
+    name TEXT,
+    cron TEXT NOT NULL, -- 5 fields (minute precision) or 6 fields starting with seconds
+    timezone TEXT NOT NULL DEFAULT 'UTC', -- IANA name the cron expression is evaluated in
+    input JSONB, -- Fixed input of every execution
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    overlap_policy TEXT NOT NULL DEFAULT 'skip', -- skip, allow, cancel_previous
+    next_run_at TIMESTAMP WITH TIME ZONE,
+    last_run_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_schedule_flow
+        FOREIGN KEY (flow_id)
+        REFERENCES flows(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_flow_schedules_due ON flow_schedules (next_run_at) WHERE enabled;
+
+-- One row per occurrence of a schedule: the execution it started, or why it did not
+CREATE TABLE IF NOT EXISTS flow_schedule_runs (
+    id TEXT PRIMARY KEY,
+    schedule_id TEXT NOT NULL,
+    execution_id TEXT REFERENCES flow_executions(id) ON DELETE SET NULL,
+    status TEXT NOT NULL, -- Triggered, Skipped, Failed
+    scheduled_for TIMESTAMP WITH TIME ZONE,
+    triggered_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    error TEXT,
+    CONSTRAINT fk_schedule_run
+        FOREIGN KEY (schedule_id)
+        REFERENCES flow_schedules(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_flow_schedule_runs_schedule ON flow_schedule_runs (schedule_id, triggered_at);
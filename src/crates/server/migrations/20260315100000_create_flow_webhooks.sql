@@ -0,0 +1,32 @@
+-- Inbound webhook of a flow: posting to /hooks/{token} starts an execution
+CREATE TABLE IF NOT EXISTS flow_webhooks (
+    id TEXT PRIMARY KEY,
+    flow_id TEXT NOT NULL UNIQUE,
+    token TEXT NOT NULL UNIQUE, -- Unguessable path segment of the webhook URL
+    secret TEXT NOT NULL, -- HMAC-SHA256 key signing the posted bodies
+    input_template TEXT, -- Maps the request to the flow input, the body as is when null
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_webhook_flow
+        FOREIGN KEY (flow_id)
+        REFERENCES flows(id)
+        ON DELETE CASCADE
+);
+
+-- Idempotency keys already received by a webhook, retried deliveries start no execution
+CREATE TABLE IF NOT EXISTS flow_webhook_deliveries (
+    id TEXT PRIMARY KEY,
+    webhook_id TEXT NOT NULL,
+    idempotency_key TEXT NOT NULL,
+    execution_id TEXT REFERENCES flow_executions(id) ON DELETE SET NULL,
+    received_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_delivery_webhook
+        FOREIGN KEY (webhook_id)
+        REFERENCES flow_webhooks(id)
+        ON DELETE CASCADE,
+    CONSTRAINT uq_delivery_key UNIQUE (webhook_id, idempotency_key)
+);
+
+-- Deliveries past their retention are purged by the flow workers
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_received ON flow_webhook_deliveries (received_at);
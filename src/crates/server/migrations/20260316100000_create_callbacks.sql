@@ -0,0 +1,45 @@
+-- Outbound callbacks: execution events are posted to the callback URL of the execution
+-- and to the event subscriptions of its flow, agent call results to their callback URL
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS callback_url TEXT;
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS notified_at TIMESTAMP WITH TIME ZONE; -- Events of the last settlement queued
+
+-- Executions settled before callbacks existed have nothing to notify
+UPDATE flow_executions SET notified_at = CURRENT_TIMESTAMP
+WHERE status IN ('Completed', 'Failed', 'Cancelled');
+
+CREATE INDEX IF NOT EXISTS idx_flow_executions_unnotified ON flow_executions (completed_at)
+WHERE status IN ('Completed', 'Failed', 'Cancelled') AND parent_execution_id IS NULL;
+
+CREATE TABLE IF NOT EXISTS flow_event_subscriptions (
+    id TEXT PRIMARY KEY,
+    flow_id TEXT NOT NULL,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL, -- HMAC-SHA256 key signing the posted events
+    events JSONB NOT NULL, -- Names of the events posted, e.g. ["execution.failed"]
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_subscription_flow
+        FOREIGN KEY (flow_id)
+        REFERENCES flows(id)
+        ON DELETE CASCADE
+);
+
+-- Outbox of the events to post, retried with backoff until delivered or dead-lettered
+CREATE TABLE IF NOT EXISTS callback_deliveries (
+    id TEXT PRIMARY KEY,
+    event TEXT NOT NULL,
+    url TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    execution_id TEXT REFERENCES flow_executions(id) ON DELETE SET NULL,
+    subscription_id TEXT REFERENCES flow_event_subscriptions(id) ON DELETE CASCADE, -- Null for callback URLs
+    status TEXT NOT NULL DEFAULT 'Pending', -- Pending, Delivered, DeadLetter
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    response_status INTEGER, -- HTTP status of the last attempt
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    delivered_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_callback_deliveries_due ON callback_deliveries (next_attempt_at) WHERE status = 'Pending';
+CREATE INDEX IF NOT EXISTS idx_callback_deliveries_dead ON callback_deliveries (created_at) WHERE status = 'DeadLetter';
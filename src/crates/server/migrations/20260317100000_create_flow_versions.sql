@@ -0,0 +1,18 @@
+-- Published revisions of a flow: immutable snapshots of its steps. The steps in
+-- flow_steps are the draft, edited freely and published as the next version
+CREATE TABLE IF NOT EXISTS flow_versions (
+    id TEXT PRIMARY KEY,
+    flow_id TEXT NOT NULL,
+    version INTEGER NOT NULL, -- 1, 2, ... per flow
+    steps JSONB NOT NULL, -- flow_steps rows of the draft when it was published
+    notes TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_version_flow
+        FOREIGN KEY (flow_id)
+        REFERENCES flows(id)
+        ON DELETE CASCADE,
+    CONSTRAINT uq_flow_version UNIQUE (flow_id, version)
+);
+
+-- Version an execution runs, null for executions of the draft
+ALTER TABLE flow_executions ADD COLUMN IF NOT EXISTS version_id TEXT REFERENCES flow_versions(id);
@@ -0,0 +1,2 @@
+-- How the error policy of a step dealt with its failures (retries, fallback task, default output, error handler)
+ALTER TABLE flow_step_executions ADD COLUMN IF NOT EXISTS recovery JSONB;
@@ -1,5 +1,13 @@
 pub mod agent;
 pub mod agent_task;
+pub mod approval;
+pub mod callback;
+pub mod document;
 pub mod flow;
 pub mod gateway;
+pub mod provider_limit;
+pub mod schedule;
+pub mod session;
+pub mod version;
+pub mod webhook;
 pub mod ws;
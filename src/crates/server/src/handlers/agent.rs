@@ -1,5 +1,6 @@
 use crate::models::agent::Model as Agent;
 use crate::services::agent::Service as AgentService;
+use crate::services::rate_limiter::AgentRateLimit;
 use crate::state::AppState;
 use axum::{
     extract::{Json, Path, State},
@@ -77,3 +78,29 @@ pub async fn get_agent(State(state): State<AppState>, Path(id): Path<String>) ->
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
+
+#[utoipa::path(
+    put,
+    path = "/{id}/rate-limit",
+    params(
+        ("id" = String, Path, description = "Agent database id")
+    ),
+    request_body = Option<AgentRateLimit>,
+    responses(
+        (status = 200, description = "Rate limit updated", body = Agent),
+        (status = 400, description = "Invalid rate limit"),
+        (status = 404, description = "Agent not found")
+    )
+)]
+// Sets the requests per second and concurrency limits of an Agent, a null body removes them.
+pub async fn update_rate_limit(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<Option<AgentRateLimit>>,
+) -> impl IntoResponse {
+    match AgentService::update_rate_limit(&state.db, id, payload).await {
+        Ok(Some(agent)) => (StatusCode::OK, Json(agent)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Agent not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
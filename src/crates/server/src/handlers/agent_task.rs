@@ -1,5 +1,9 @@
 use crate::models::agent_task::Model as AgentTask;
 use crate::services::agent_task::Service as AgentTaskService;
+use crate::services::agentic::{self, Service as AgenticService};
+use crate::services::contract;
+use crate::services::fallback::FallbackPolicy;
+use crate::services::response_cache::CachePolicy;
 use crate::state::AppState;
 use axum::{
     extract::{Json, Path, State},
@@ -14,7 +18,7 @@ pub struct CreateAgentTaskPayload {
     pub agent_id: String,
     pub name: String,
     pub description: Option<String>,
-    /// Task type: "endpoint", "function", "script", etc.
+    /// Task type: "endpoint", "function", "script", "agentic", etc.
     pub task_type: String,
     /// HTTP path for endpoint tasks (e.g. "/api/generate")
     pub path: Option<String>,
@@ -37,6 +41,7 @@ pub struct CreateAgentTaskPayload {
     request_body = CreateAgentTaskPayload,
     responses(
         (status = 201, description = "Task created successfully", body = AgentTask),
+        (status = 400, description = "Invalid agentic, fallback or cache settings, or invalid contracts"),
         (status = 500, description = "Internal server error")
     )
 )]
@@ -44,6 +49,27 @@ pub async fn create_task(
     State(state): State<AppState>,
     Json(payload): Json<CreateAgentTaskPayload>,
 ) -> impl IntoResponse {
+    // Agentic tasks must reference existing tools before they can be stored
+    if payload.task_type == agentic::TASK_TYPE {
+        if let Err(e) = AgenticService::validate(&state.db, payload.settings.as_ref()).await {
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    }
+    if let Err(e) = FallbackPolicy::validate(&state.db, payload.settings.as_ref()).await {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+    if let Err(e) = CachePolicy::from_settings(payload.settings.as_ref()) {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+    for schema in [&payload.input_contract, &payload.output_contract]
+        .into_iter()
+        .flatten()
+    {
+        if let Err(e) = contract::validate_schema(schema) {
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    }
+
     match AgentTaskService::create_task(
         &state.db,
         payload.agent_id,
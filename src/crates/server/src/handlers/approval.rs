@@ -0,0 +1,96 @@
+use crate::models::flow_approval::{DecideApprovalPayload, Model as FlowApprovalModel};
+use crate::services::flow_approval::Service as FlowApprovalService;
+use crate::state::AppState;
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+#[utoipa::path(
+    get,
+    path = "/",
+    responses(
+        (status = 200, description = "Approvals waiting for a reviewer, oldest first", body = [FlowApprovalModel]),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_pending_approvals(State(state): State<AppState>) -> impl IntoResponse {
+    match FlowApprovalService::list_pending(&state.db).await {
+        Ok(approvals) => (StatusCode::OK, Json(approvals)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    params(
+        ("id" = String, Path, description = "Approval id")
+    ),
+    responses(
+        (status = 200, description = "Approval with the data submitted for review", body = FlowApprovalModel),
+        (status = 404, description = "Approval not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_approval(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowApprovalService::get_approval(&state.db, id).await {
+        Ok(Some(approval)) => (StatusCode::OK, Json(approval)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Approval not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/approve",
+    params(
+        ("id" = String, Path, description = "Approval id")
+    ),
+    request_body = DecideApprovalPayload,
+    responses(
+        (status = 200, description = "Approved, the execution resumes with the approved (or edited) data", body = FlowApprovalModel),
+        (status = 404, description = "Approval not found"),
+        (status = 409, description = "Approval already decided")
+    )
+)]
+pub async fn approve(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<DecideApprovalPayload>,
+) -> impl IntoResponse {
+    decided(FlowApprovalService::approve(&state.db, id, payload).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/reject",
+    params(
+        ("id" = String, Path, description = "Approval id")
+    ),
+    request_body = DecideApprovalPayload,
+    responses(
+        (status = 200, description = "Rejected, the execution fails", body = FlowApprovalModel),
+        (status = 404, description = "Approval not found"),
+        (status = 409, description = "Approval already decided")
+    )
+)]
+pub async fn reject(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<DecideApprovalPayload>,
+) -> impl IntoResponse {
+    decided(FlowApprovalService::reject(&state.db, id, payload).await)
+}
+
+fn decided(result: Result<Option<FlowApprovalModel>, String>) -> Response {
+    match result {
+        Ok(Some(approval)) => (StatusCode::OK, Json(approval)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Approval not found").into_response(),
+        Err(e) => (StatusCode::CONFLICT, e).into_response(),
+    }
+}
@@ -0,0 +1,141 @@
+use crate::models::callback_delivery::Model as CallbackDeliveryModel;
+use crate::models::flow_event_subscription::{
+    CreateSubscriptionPayload, Model as FlowEventSubscriptionModel,
+};
+use crate::services::callback::Service as CallbackService;
+use crate::state::AppState;
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+#[utoipa::path(
+    get,
+    path = "/{id}/subscriptions",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    responses(
+        (status = 200, description = "URLs subscribed to the execution events of the flow", body = [FlowEventSubscriptionModel]),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_subscriptions(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match CallbackService::list_subscriptions(&state.db, id).await {
+        Ok(subscriptions) => (StatusCode::OK, Json(subscriptions)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/subscriptions",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    request_body = CreateSubscriptionPayload,
+    responses(
+        (status = 201, description = "URL subscribed, events are signed with the returned secret", body = FlowEventSubscriptionModel),
+        (status = 400, description = "Invalid URL, event or secret"),
+        (status = 404, description = "Flow not found")
+    )
+)]
+/// Subscribes a URL to the completed, failed and cancelled events of the flow executions.
+pub async fn create_subscription(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<CreateSubscriptionPayload>,
+) -> impl IntoResponse {
+    match CallbackService::subscribe(&state.db, id, payload).await {
+        Ok(Some(subscription)) => (StatusCode::CREATED, Json(subscription)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Flow not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}/subscriptions/{subscription_id}",
+    params(
+        ("id" = String, Path, description = "Flow database id"),
+        ("subscription_id" = String, Path, description = "Subscription id")
+    ),
+    responses(
+        (status = 204, description = "Subscription removed with its pending deliveries"),
+        (status = 404, description = "Subscription not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn delete_subscription(
+    State(state): State<AppState>,
+    Path((id, subscription_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match CallbackService::unsubscribe(&state.db, id, subscription_id).await {
+        Ok(0) => (StatusCode::NOT_FOUND, "Subscription not found").into_response(),
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/executions/{id}/callbacks",
+    params(
+        ("id" = String, Path, description = "Execution id")
+    ),
+    responses(
+        (status = 200, description = "Events of the execution posted (or being posted) to callbacks", body = [CallbackDeliveryModel]),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_execution_callbacks(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match CallbackService::get_execution_deliveries(&state.db, id).await {
+        Ok(deliveries) => (StatusCode::OK, Json(deliveries)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/dead-letters",
+    responses(
+        (status = 200, description = "Deliveries whose attempts all failed, newest first", body = [CallbackDeliveryModel]),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_dead_letters(State(state): State<AppState>) -> impl IntoResponse {
+    match CallbackService::list_dead_letters(&state.db).await {
+        Ok(deliveries) => (StatusCode::OK, Json(deliveries)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/retry",
+    params(
+        ("id" = String, Path, description = "Delivery id")
+    ),
+    responses(
+        (status = 202, description = "Delivery queued again with a fresh set of attempts", body = CallbackDeliveryModel),
+        (status = 404, description = "Delivery not found"),
+        (status = 409, description = "The delivery is not dead-lettered")
+    )
+)]
+pub async fn retry_delivery(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match CallbackService::retry_delivery(&state.db, id).await {
+        Ok(Some(delivery)) => (StatusCode::ACCEPTED, Json(delivery)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Delivery not found").into_response(),
+        Err(e) => (StatusCode::CONFLICT, e).into_response(),
+    }
+}
@@ -0,0 +1,101 @@
+use crate::models::flow_document::{FlowDocument, FlowImportReport};
+use crate::services::flow_document::{self, Service as FlowDocumentService};
+use crate::state::AppState;
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+pub struct ExportQuery {
+    /// "yaml" (default) or "json"
+    pub format: Option<String>,
+    /// Published version to export, the draft steps when omitted
+    pub version: Option<i32>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ImportQuery {
+    /// Report the changes without applying them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Flow updated by the document, otherwise the flow with the same name (created when none)
+    pub flow_id: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/export",
+    params(
+        ("id" = String, Path, description = "Flow database id"),
+        ExportQuery
+    ),
+    responses(
+        (status = 200, description = "Portable document of the flow, in YAML or JSON", body = FlowDocument,
+            content_type = "application/yaml"),
+        (status = 400, description = "Unknown format"),
+        (status = 404, description = "Flow or version not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+/// Exports a flow with its steps, tasks and agents, referenced by slug and name instead of
+/// database ids, to keep it in version control or import it in another environment.
+pub async fn export_flow(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> impl IntoResponse {
+    let format = query.format.unwrap_or_else(|| "yaml".to_string());
+    if format != "yaml" && format != "json" {
+        return (StatusCode::BAD_REQUEST, "format must be yaml or json").into_response();
+    }
+    let document = match FlowDocumentService::export(&state.db, id, query.version).await {
+        Ok(Some(document)) => document,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Flow or version not found").into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    if format == "json" {
+        return (StatusCode::OK, Json(document)).into_response();
+    }
+    match serde_yaml::to_string(&document) {
+        Ok(yaml) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/yaml")],
+            yaml,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/import",
+    params(ImportQuery),
+    request_body(content = FlowDocument, description = "Flow document, YAML or JSON",
+        content_type = "application/yaml"),
+    responses(
+        (status = 200, description = "Document imported, or the changes it would make on a dry run", body = FlowImportReport),
+        (status = 400, description = "Invalid document, unknown task, agent or sub-flow, or invalid steps"),
+        (status = 404, description = "Flow not found")
+    )
+)]
+/// Creates or updates a flow from a document, registering its missing agents and tasks.
+pub async fn import_flow(
+    State(state): State<AppState>,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> impl IntoResponse {
+    let document = match flow_document::parse_document(&body) {
+        Ok(document) => document,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match FlowDocumentService::import(&state.db, document, query.flow_id, query.dry_run).await {
+        Ok(Some(report)) => (StatusCode::OK, Json(report)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Flow not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
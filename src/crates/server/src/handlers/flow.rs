@@ -1,15 +1,24 @@
 use crate::models::flow::{
-    CreateFlowPayload, ExecuteFlowPayload, ExecuteFlowResponse, FlowWithSteps,
+    CreateFlowPayload, ExecuteFlowPayload, ExecuteFlowResponse, FlowExecutionAccepted,
+    FlowWithSteps, UpdateFlowPayload,
 };
-use crate::models::flow_step::CreateFlowStepPayload;
+use crate::models::flow_execution::Model as FlowExecutionModel;
+use crate::models::flow_step::{
+    CreateFlowStepPayload, ReorderFlowStepsPayload, UpdateFlowStepPayload,
+};
+use crate::models::flow_step_execution::Model as FlowStepExecutionModel;
 use crate::models::{flow::Model as FlowModel, flow_step::Model as FlowStepModel};
+use crate::services::callback;
+use crate::services::contract::{ContractViolation, FlowCheck};
 use crate::services::flow::Service as FlowService;
 use crate::services::flow_executor::Service as FlowExecutorService;
+use crate::services::flow_simulation::Service as FlowSimulationService;
+use crate::services::flow_version::Revision;
 use crate::state::AppState;
 use axum::{
     extract::{Json, Path, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 
 #[utoipa::path(
@@ -20,7 +29,11 @@ use axum::{
     ),
     request_body = ExecuteFlowPayload,
     responses(
-        (status = 200, description = "Flow executed successfully", body = ExecuteFlowResponse),
+        (status = 200, description = "Flow executed successfully, or a `FlowSimulation` for `dry_run: true`", body = ExecuteFlowResponse),
+        (status = 202, description = "Flow started in the background (`async: true`), or paused on an approval step", body = FlowExecutionAccepted),
+        (status = 400, description = "Invalid callback URL, callback URL without `async: true`, both version and draft, or mocks without `dry_run: true`"),
+        (status = 404, description = "Flow not found (dry run)"),
+        (status = 422, description = "A step payload or response broke its task contracts", body = ContractViolation),
         (status = 500, description = "Internal server error")
     )
 )]
@@ -30,17 +43,127 @@ pub async fn execute_flow_task(
     Path(id): Path<String>,
     Json(payload): Json<ExecuteFlowPayload>,
 ) -> impl IntoResponse {
+    let revision = match Revision::from_request(payload.version, payload.draft) {
+        Ok(revision) => revision,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    if payload.dry_run {
+        if payload.run_async || payload.session_id.is_some() {
+            return (
+                StatusCode::BAD_REQUEST,
+                "dry_run cannot be combined with async or session_id",
+            )
+                .into_response();
+        }
+        return match FlowSimulationService::simulate(
+            &state.db,
+            id,
+            revision,
+            payload.payload,
+            payload.mocks.unwrap_or_default(),
+            payload.mock_execution_id,
+        )
+        .await
+        {
+            Ok(Some(simulation)) => (StatusCode::OK, Json(simulation)).into_response(),
+            Ok(None) => (StatusCode::NOT_FOUND, "Flow not found").into_response(),
+            Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+        };
+    }
+    if payload.mocks.is_some() || payload.mock_execution_id.is_some() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "mocks and mock_execution_id require dry_run: true",
+        )
+            .into_response();
+    }
+    if let Some(url) = &payload.callback_url {
+        if !payload.run_async {
+            return (StatusCode::BAD_REQUEST, "callback_url requires async: true").into_response();
+        }
+        if let Err(e) = callback::check_callback_url(url) {
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    }
+
+    if payload.run_async {
+        return match FlowExecutorService::start_flow(
+            &state,
+            id,
+            payload.payload,
+            payload.session_id,
+            payload.callback_url,
+            revision,
+        )
+        .await
+        {
+            Ok(execution) => (
+                StatusCode::ACCEPTED,
+                Json(FlowExecutionAccepted {
+                    status_url: format!("/flows/executions/{}", execution.id),
+                    execution_id: execution.id,
+                    status: execution.status,
+                }),
+            )
+                .into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.message).into_response(),
+        };
+    }
+
     let result = FlowExecutorService::execute_flow(
-        &state.db,
-        &state.http_client,
+        &state,
         id.clone(),
         payload.payload,
+        payload.session_id,
+        revision,
     )
     .await;
 
     match result {
         Ok(response) => (StatusCode::OK, Json(ExecuteFlowResponse { response })).into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        Err(e) => match (e.contract, e.paused) {
+            (Some(violation), _) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, Json(*violation)).into_response()
+            }
+            // The execution goes on in the background once its approvals are decided
+            (None, Some(execution_id)) => (
+                StatusCode::ACCEPTED,
+                Json(FlowExecutionAccepted {
+                    status_url: format!("/flows/executions/{}", execution_id),
+                    execution_id,
+                    status: "WaitingForApproval".to_string(),
+                }),
+            )
+                .into_response(),
+            (None, None) => (StatusCode::INTERNAL_SERVER_ERROR, e.message).into_response(),
+        },
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/check",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    responses(
+        (status = 200, description = "Contract compatibility of the flow steps", body = FlowCheck),
+        (status = 400, description = "Invalid flow graph"),
+        (status = 404, description = "Flow not found")
+    )
+)]
+/// Validates the flow without running it: the output contract of every step must be
+/// accepted by the input contract of the steps it feeds. Steps with a config are checked
+/// on the payload they build: rendered for static templates, on its structure for
+/// templates reading runtime data.
+pub async fn check_flow(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowService::check_flow(&state.db, id).await {
+        Ok(Some(report)) => (StatusCode::OK, Json(report)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Flow not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
     }
 }
 
@@ -98,6 +221,54 @@ pub async fn get_flow(State(state): State<AppState>, Path(id): Path<String>) ->
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/{id}",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    request_body = UpdateFlowPayload,
+    responses(
+        (status = 200, description = "Flow updated", body = FlowModel),
+        (status = 400, description = "Empty name"),
+        (status = 404, description = "Flow not found")
+    )
+)]
+pub async fn update_flow(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateFlowPayload>,
+) -> impl IntoResponse {
+    match FlowService::update_flow(&state.db, id, payload).await {
+        Ok(Some(flow)) => (StatusCode::OK, Json(flow)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Flow not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    responses(
+        (status = 204, description = "Flow deleted with its steps, versions and executions"),
+        (status = 404, description = "Flow not found"),
+        (status = 409, description = "The flow is run as a sub-flow, or has executions in progress")
+    )
+)]
+pub async fn delete_flow(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowService::delete_flow(&state.db, id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Flow not found").into_response(),
+        Err(e) => (StatusCode::CONFLICT, e).into_response(),
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/{id}/steps",
@@ -107,7 +278,7 @@ pub async fn get_flow(State(state): State<AppState>, Path(id): Path<String>) ->
     request_body = CreateFlowStepPayload,
     responses(
         (status = 201, description = "Flow step created successfully", body = FlowStepModel),
-        (status = 500, description = "Internal server error")
+        (status = 400, description = "Unknown task, unknown dependency or dependency cycle")
     )
 )]
 pub async fn add_flow_step(
@@ -115,17 +286,109 @@ pub async fn add_flow_step(
     Path(id): Path<String>,
     Json(payload): Json<CreateFlowStepPayload>,
 ) -> impl IntoResponse {
-    match FlowService::add_flow_step(
-        &state.db,
-        id,
-        payload.task_id,
-        payload.step_order,
-        payload.config,
-    )
-    .await
-    {
+    match FlowService::add_flow_step(&state.db, id, payload).await {
         Ok(step) => (StatusCode::CREATED, Json(step)).into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/{id}/steps/{step_id}",
+    params(
+        ("id" = String, Path, description = "Flow database id"),
+        ("step_id" = String, Path, description = "Step database id")
+    ),
+    request_body = UpdateFlowStepPayload,
+    responses(
+        (status = 200, description = "Step updated", body = FlowStepModel),
+        (status = 400, description = "Unknown task, unknown dependency, dependency cycle or invalid config"),
+        (status = 404, description = "Step not found")
+    )
+)]
+pub async fn update_flow_step(
+    State(state): State<AppState>,
+    Path((id, step_id)): Path<(String, String)>,
+    Json(payload): Json<UpdateFlowStepPayload>,
+) -> impl IntoResponse {
+    match FlowService::update_flow_step(&state.db, id, step_id, payload).await {
+        Ok(Some(step)) => (StatusCode::OK, Json(step)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Step not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}/steps/{step_id}",
+    params(
+        ("id" = String, Path, description = "Flow database id"),
+        ("step_id" = String, Path, description = "Step database id")
+    ),
+    responses(
+        (status = 204, description = "Step deleted"),
+        (status = 400, description = "Other steps depend on the step"),
+        (status = 404, description = "Step not found")
+    )
+)]
+pub async fn delete_flow_step(
+    State(state): State<AppState>,
+    Path((id, step_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match FlowService::delete_flow_step(&state.db, id, step_id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Step not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/{id}/steps/order",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    request_body = ReorderFlowStepsPayload,
+    responses(
+        (status = 200, description = "Steps in their new order", body = [FlowStepModel]),
+        (status = 400, description = "Missing, unknown or repeated step, or dependency cycle"),
+        (status = 404, description = "Flow not found")
+    )
+)]
+/// Reorders all the steps of a flow in a single transaction.
+pub async fn reorder_flow_steps(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<ReorderFlowStepsPayload>,
+) -> impl IntoResponse {
+    match FlowService::reorder_flow_steps(&state.db, id, payload).await {
+        Ok(Some(steps)) => (StatusCode::OK, Json(steps)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Flow not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/steps/{step_id}/duplicate",
+    params(
+        ("id" = String, Path, description = "Flow database id"),
+        ("step_id" = String, Path, description = "Step database id")
+    ),
+    responses(
+        (status = 201, description = "Copy of the step, added at the end of the flow", body = FlowStepModel),
+        (status = 404, description = "Step not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn duplicate_flow_step(
+    State(state): State<AppState>,
+    Path((id, step_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match FlowService::duplicate_flow_step(&state.db, id, step_id).await {
+        Ok(Some(step)) => (StatusCode::CREATED, Json(step)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Step not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
     }
 }
 
@@ -149,3 +412,153 @@ pub async fn get_flow_steps(
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
+
+#[utoipa::path(
+    get,
+    path = "/executions/{id}",
+    params(
+        ("id" = String, Path, description = "Flow execution id")
+    ),
+    responses(
+        (status = 200, description = "Status, step progress and output of the execution", body = FlowExecutionModel),
+        (status = 404, description = "Execution not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_execution(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowService::get_execution(&state.db, id).await {
+        Ok(Some(execution)) => (StatusCode::OK, Json(execution)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Execution not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/executions/{id}/steps",
+    params(
+        ("id" = String, Path, description = "Flow execution id")
+    ),
+    responses(
+        (status = 200, description = "Records of the steps run by the execution", body = [FlowStepExecutionModel]),
+        (status = 404, description = "Execution not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_step_executions(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowService::get_step_executions(&state.db, id).await {
+        Ok(Some(records)) => (StatusCode::OK, Json(records)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Execution not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/executions/{id}/children",
+    params(
+        ("id" = String, Path, description = "Flow execution id")
+    ),
+    responses(
+        (status = 200, description = "Sub-flow executions started by the execution", body = [FlowExecutionModel]),
+        (status = 404, description = "Execution not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_child_executions(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowService::get_child_executions(&state.db, id).await {
+        Ok(Some(children)) => (StatusCode::OK, Json(children)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Execution not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/executions/{id}/resume",
+    params(
+        ("id" = String, Path, description = "Flow execution id")
+    ),
+    responses(
+        (status = 202, description = "Execution resumed in the background from its failed steps", body = FlowExecutionAccepted),
+        (status = 404, description = "Execution not found"),
+        (status = 409, description = "Execution is not Failed or Cancelled")
+    )
+)]
+/// Continues a failed execution, reusing the outputs of the steps that already completed.
+pub async fn resume_execution(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    resumed(FlowExecutorService::resume_execution(&state, id, None).await)
+}
+
+#[utoipa::path(
+    post,
+    path = "/executions/{id}/retry-step/{step}",
+    params(
+        ("id" = String, Path, description = "Flow execution id"),
+        ("step" = String, Path, description = "Id or name of the step to rerun")
+    ),
+    responses(
+        (status = 202, description = "Step and downstream steps rerun in the background", body = FlowExecutionAccepted),
+        (status = 404, description = "Execution not found"),
+        (status = 409, description = "Execution still running or unknown step")
+    )
+)]
+/// Reruns a step of a settled execution and every step downstream of it.
+pub async fn retry_step(
+    State(state): State<AppState>,
+    Path((id, step)): Path<(String, String)>,
+) -> impl IntoResponse {
+    resumed(FlowExecutorService::resume_execution(&state, id, Some(step)).await)
+}
+
+fn resumed(result: Result<Option<FlowExecutionModel>, String>) -> Response {
+    match result {
+        Ok(Some(execution)) => (
+            StatusCode::ACCEPTED,
+            Json(FlowExecutionAccepted {
+                status_url: format!("/flows/executions/{}", execution.id),
+                execution_id: execution.id,
+                status: execution.status,
+            }),
+        )
+            .into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Execution not found").into_response(),
+        Err(e) => (StatusCode::CONFLICT, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/executions/{id}/cancel",
+    params(
+        ("id" = String, Path, description = "Flow execution id")
+    ),
+    responses(
+        (status = 202, description = "Cancellation requested, the execution turns Cancelled once its steps in flight are stopped", body = FlowExecutionModel),
+        (status = 404, description = "Execution not found"),
+        (status = 409, description = "Execution already settled")
+    )
+)]
+/// Stops a Pending or Running execution, aborting the agent calls in flight.
+pub async fn cancel_execution(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowExecutorService::cancel_execution(&state, id).await {
+        Ok(Some(execution)) => (StatusCode::ACCEPTED, Json(execution)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Execution not found").into_response(),
+        Err(e) => (StatusCode::CONFLICT, e).into_response(),
+    }
+}
@@ -1,12 +1,22 @@
+use crate::models::agent;
+use crate::models::callback_delivery::{EVENT_AGENT_CALL_COMPLETED, EVENT_AGENT_CALL_FAILED};
 use crate::services::agent::Service as AgentService;
-use crate::services::agent_client::Service as AgentClient;
+use crate::services::agent_client::{AgentCallError, ErrorClass, Service as AgentClient};
+use crate::services::agent_task::Service as AgentTaskService;
+use crate::services::callback::{self, Service as CallbackService};
+use crate::services::contract::ContractViolation;
+use crate::services::response_cache::{CachePolicy, CacheScope};
+use crate::services::session::Service as SessionService;
+use crate::services::task_runner::Service as TaskRunner;
 use crate::state::AppState;
 use axum::{
     extract::{Json, Path, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::future::Future;
 use utoipa::ToSchema;
 
 #[derive(Deserialize, ToSchema)]
@@ -14,6 +24,20 @@ pub struct ExecuteAgentPayload {
     /// Payload to send to the agent's target endpoint
     #[schema(value_type = Object)]
     pub payload: serde_json::Value,
+    /// Optional session whose history is prepended to the payload and extended with this exchange
+    pub session_id: Option<String>,
+    /// Run the call in the background and post its agent_call.completed or
+    /// agent_call.failed event to this URL, signed with the CALLBACK_SECRET key of the server
+    pub callback_url: Option<String>,
+    /// Response cache of direct agent calls (in memory only), tasks use the `cache`
+    /// section of their settings instead
+    pub cache: Option<CachePolicy>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AgentCallAccepted {
+    /// Id of the call, repeated in its callback event
+    pub call_id: String,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -32,7 +56,10 @@ pub struct ExecuteAgentResponse {
     request_body = ExecuteAgentPayload,
     responses(
         (status = 200, description = "Task executed successfully", body = ExecuteAgentResponse),
-        (status = 404, description = "Agent not found"),
+        (status = 202, description = "Call running in the background, its event is posted to the callback URL", body = AgentCallAccepted),
+        (status = 400, description = "Invalid callback URL"),
+        (status = 404, description = "Agent or session not found"),
+        (status = 429, description = "Rate limit of the agent exhausted"),
         (status = 500, description = "Internal server error")
     )
 )]
@@ -46,40 +73,14 @@ pub async fn execute_agent_task(
 
     match agent_result {
         Ok(Some(agent)) => {
-            // Perform resilient execution call to target agent node
-            let result =
-                AgentClient::execute_task(&state.http_client, &agent.endpoint, &payload.payload)
-                    .await;
-
-            // Extract valid JSON from the final response parsing
-            let (response_json, retries_used) = match &result {
-                Ok((res, retries)) => (res.clone(), *retries),
-                Err((err_msg, retries)) => (serde_json::json!({ "error": err_msg }), *retries),
+            let request = match with_session_history(&state, &payload).await {
+                Ok(request) => request,
+                Err(response) => return response,
             };
-
-            // Log the task asynchronously in PostgreSQL so it never blocks the HTTP response to the client
-            // We clone the necessary variables (DB pool, keys, values) to let Tokio spawn it separately
-            let ping_db = state.db.clone();
-            let agent_id_log = agent.id.clone();
-            let payload_log = payload.payload.clone();
-            tokio::spawn(async move {
-                let _ = crate::services::agent_log::Service::create(
-                    &ping_db,
-                    agent_id_log,
-                    payload_log,
-                    response_json,
-                    retries_used,
-                )
-                .await;
-            });
-
-            // 4. Return the outcome gracefully resolving the current request
-            match result {
-                Ok((response, _)) => {
-                    (StatusCode::OK, Json(ExecuteAgentResponse { response })).into_response()
-                }
-                Err((e, _)) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-            }
+            let call_state = state.clone();
+            let cache = payload.cache.clone().filter(|policy| policy.enabled);
+            let call = async move { call_agent(&call_state, &agent, request, cache).await };
+            respond(state, payload, json!({ "agent_id": id }), call).await
         }
         Ok(None) => (StatusCode::NOT_FOUND, format!("Agent {} not found", id)).into_response(),
         Err(e) => (
@@ -89,3 +90,218 @@ pub async fn execute_agent_task(
             .into_response(),
     }
 }
+
+#[utoipa::path(
+    post,
+    path = "/{id}/execute",
+    params(
+        ("id" = String, Path, description = "Task database id")
+    ),
+    request_body = ExecuteAgentPayload,
+    responses(
+        (status = 200, description = "Task executed successfully", body = ExecuteAgentResponse),
+        (status = 202, description = "Call running in the background, its event is posted to the callback URL", body = AgentCallAccepted),
+        (status = 400, description = "Invalid callback URL"),
+        (status = 404, description = "Task or session not found"),
+        (status = 422, description = "Payload or response breaking the task contracts", body = ContractViolation),
+        (status = 429, description = "Rate limit of the agent or provider exhausted"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+// Executes a single task against its agent endpoint (or runs the tool loop for agentic tasks).
+pub async fn execute_task(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<ExecuteAgentPayload>,
+) -> impl IntoResponse {
+    let task = match AgentTaskService::get_task_by_id(&state.db, id.clone()).await {
+        Ok(Some(task)) => task,
+        Ok(None) => {
+            return (StatusCode::NOT_FOUND, format!("Task {} not found", id)).into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response()
+        }
+    };
+
+    let request = match with_session_history(&state, &payload).await {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+
+    let call_state = state.clone();
+    let call = async move {
+        TaskRunner::execute(&call_state, &task, &request)
+            .await
+            .map(|(response, _)| response)
+    };
+    respond(state, payload, json!({ "task_id": id }), call).await
+}
+
+// Calls an agent endpoint, or answers from the response cache, logging the exchange in
+// the background so the log never delays the response.
+async fn call_agent(
+    state: &AppState,
+    agent: &agent::Model,
+    request: Value,
+    cache: Option<CachePolicy>,
+) -> Result<Value, AgentCallError> {
+    let call = async {
+        let result = AgentClient::execute_task(
+            &state.http_client,
+            &state.limiter,
+            agent,
+            &agent.endpoint,
+            &request,
+            None,
+        )
+        .await;
+        log_agent_call(state, agent, request.clone(), &result);
+        result
+    };
+    TaskRunner::cached(state, CacheScope::Agent(agent), cache, &request, call)
+        .await
+        .map(|(response, _)| response)
+}
+
+// Logs a direct agent call in the background.
+fn log_agent_call(
+    state: &AppState,
+    agent: &agent::Model,
+    request: Value,
+    result: &Result<(Value, i32), AgentCallError>,
+) {
+    // Extract valid JSON from the final response parsing
+    let (response_json, retries_used) = match result {
+        Ok((res, retries)) => (res.clone(), *retries),
+        Err(e) => (json!({ "error": e.message, "class": e.class }), e.retries),
+    };
+
+    // Log the task asynchronously in PostgreSQL so it never blocks the HTTP response to the client
+    // We clone the necessary variables (DB pool, keys, values) to let Tokio spawn it separately
+    let ping_db = state.db.clone();
+    let agent_id_log = agent.id.clone();
+    tokio::spawn(async move {
+        let _ = crate::services::agent_log::Service::create(
+            &ping_db,
+            uuid::Uuid::new_v4().to_string(),
+            agent_id_log,
+            request,
+            response_json,
+            retries_used,
+            None,
+            false,
+            None,
+        )
+        .await;
+    });
+}
+
+// Answers with the outcome of an agent call, or right away with 202 when the caller
+// gave a callback URL: the call then goes on in the background and its event is posted
+// once it settles.
+async fn respond(
+    state: AppState,
+    payload: ExecuteAgentPayload,
+    target: Value,
+    call: impl Future<Output = Result<Value, AgentCallError>> + Send + 'static,
+) -> Response {
+    let url = match payload.callback_url.clone() {
+        Some(url) => url,
+        None => {
+            return match call.await {
+                Ok(response) => {
+                    record_session_exchange(&state, &payload, &response).await;
+                    (StatusCode::OK, Json(ExecuteAgentResponse { response })).into_response()
+                }
+                Err(e) => error_response(e),
+            }
+        }
+    };
+    if let Err(e) = callback::check_callback_url(&url) {
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+
+    let call_id = uuid::Uuid::new_v4().to_string();
+    let accepted = AgentCallAccepted {
+        call_id: call_id.clone(),
+    };
+    tokio::spawn(async move {
+        let mut data = target;
+        data["call_id"] = json!(call_id);
+        let event = match call.await {
+            Ok(response) => {
+                record_session_exchange(&state, &payload, &response).await;
+                data["response"] = response;
+                EVENT_AGENT_CALL_COMPLETED
+            }
+            Err(e) => {
+                data["error"] = json!(e.message);
+                data["class"] = json!(e.class);
+                if let Some(violation) = e.contract {
+                    data["contract"] = json!(*violation);
+                }
+                EVENT_AGENT_CALL_FAILED
+            }
+        };
+        if let Err(e) = CallbackService::enqueue(&state.db, event, url, None, None, data).await {
+            tracing::warn!("Failed to queue the callback of agent call: {}", e);
+        }
+    });
+    (StatusCode::ACCEPTED, Json(accepted)).into_response()
+}
+
+// Rejections of the rate limiter surface as 429, broken task contracts as a structured
+// 422, every other failure as 500.
+fn error_response(error: AgentCallError) -> Response {
+    match (error.class, error.contract) {
+        (ErrorClass::RateLimited, _) => {
+            (StatusCode::TOO_MANY_REQUESTS, error.message).into_response()
+        }
+        (_, Some(violation)) => {
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(*violation)).into_response()
+        }
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, error.message).into_response(),
+    }
+}
+
+// Prepends the session history to the payload when the caller references a session.
+async fn with_session_history(
+    state: &AppState,
+    payload: &ExecuteAgentPayload,
+) -> Result<serde_json::Value, Response> {
+    let session_id = match &payload.session_id {
+        Some(session_id) => session_id.clone(),
+        None => return Ok(payload.payload.clone()),
+    };
+
+    match SessionService::find_history(&state.db, session_id.clone()).await {
+        Ok(Some(history)) => Ok(SessionService::apply_history(&history, &payload.payload)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            format!("Session {} not found", session_id),
+        )
+            .into_response()),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e).into_response()),
+    }
+}
+
+// Stores the exchange in the session. Failures are only logged, the agent already answered.
+async fn record_session_exchange(
+    state: &AppState,
+    payload: &ExecuteAgentPayload,
+    response: &serde_json::Value,
+) {
+    if let Some(session_id) = &payload.session_id {
+        if let Err(e) =
+            SessionService::record_exchange(state, session_id.clone(), &payload.payload, response)
+                .await
+        {
+            tracing::warn!("Failed to record session {}: {}", session_id, e);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::models::provider_limit::{Model as ProviderLimit, UpsertProviderLimitPayload};
+use crate::services::provider_limit::Service as ProviderLimitService;
+use crate::state::AppState;
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+#[utoipa::path(
+    get,
+    path = "/",
+    responses(
+        (status = 200, description = "List all provider credential limits", body = [ProviderLimit]),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_limits(State(state): State<AppState>) -> impl IntoResponse {
+    match ProviderLimitService::get_all_limits(&state.db).await {
+        Ok(limits) => (StatusCode::OK, Json(limits)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/{credential}",
+    params(
+        ("credential" = String, Path, description = "Environment variable holding the provider API key")
+    ),
+    request_body = UpsertProviderLimitPayload,
+    responses(
+        (status = 200, description = "Limits stored", body = ProviderLimit),
+        (status = 400, description = "Invalid limits")
+    )
+)]
+// Stores the requests and tokens per minute allowed to a provider credential and applies them right away.
+pub async fn upsert_limit(
+    State(state): State<AppState>,
+    Path(credential): Path<String>,
+    Json(payload): Json<UpsertProviderLimitPayload>,
+) -> impl IntoResponse {
+    match ProviderLimitService::upsert_limit(
+        &state.db,
+        credential,
+        payload.requests_per_minute,
+        payload.tokens_per_minute,
+        payload.mode,
+    )
+    .await
+    {
+        Ok(limit) => {
+            state.limiter.set_provider_limit(limit.clone());
+            (StatusCode::OK, Json(limit)).into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{credential}",
+    params(
+        ("credential" = String, Path, description = "Environment variable holding the provider API key")
+    ),
+    responses(
+        (status = 204, description = "Limits removed"),
+        (status = 404, description = "No limits for this credential"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn delete_limit(
+    State(state): State<AppState>,
+    Path(credential): Path<String>,
+) -> impl IntoResponse {
+    match ProviderLimitService::delete_limit(&state.db, credential.clone()).await {
+        Ok(0) => (StatusCode::NOT_FOUND, "No limits for this credential").into_response(),
+        Ok(_) => {
+            state.limiter.remove_provider_limit(&credential);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,139 @@
+use crate::models::flow_schedule::{
+    CreateFlowSchedulePayload, Model as FlowScheduleModel, UpdateFlowSchedulePayload,
+};
+use crate::models::flow_schedule_run::Model as FlowScheduleRunModel;
+use crate::services::flow_schedule::Service as FlowScheduleService;
+use crate::state::AppState;
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+#[utoipa::path(
+    post,
+    path = "/",
+    request_body = CreateFlowSchedulePayload,
+    responses(
+        (status = 201, description = "Schedule created, with its next occurrence", body = FlowScheduleModel),
+        (status = 400, description = "Unknown flow, invalid cron expression, timezone or overlap policy")
+    )
+)]
+pub async fn create_schedule(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateFlowSchedulePayload>,
+) -> impl IntoResponse {
+    match FlowScheduleService::create_schedule(&state.db, payload).await {
+        Ok(schedule) => (StatusCode::CREATED, Json(schedule)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    responses(
+        (status = 200, description = "List all schedules", body = [FlowScheduleModel]),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_schedules(State(state): State<AppState>) -> impl IntoResponse {
+    match FlowScheduleService::get_all_schedules(&state.db).await {
+        Ok(schedules) => (StatusCode::OK, Json(schedules)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    params(
+        ("id" = String, Path, description = "Schedule id")
+    ),
+    responses(
+        (status = 200, description = "Schedule with its next occurrence", body = FlowScheduleModel),
+        (status = 404, description = "Schedule not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_schedule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowScheduleService::get_schedule(&state.db, id).await {
+        Ok(Some(schedule)) => (StatusCode::OK, Json(schedule)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Schedule not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/{id}",
+    params(
+        ("id" = String, Path, description = "Schedule id")
+    ),
+    request_body = UpdateFlowSchedulePayload,
+    responses(
+        (status = 200, description = "Schedule updated, e.g. paused with enabled = false", body = FlowScheduleModel),
+        (status = 400, description = "Invalid cron expression, timezone or overlap policy"),
+        (status = 404, description = "Schedule not found")
+    )
+)]
+pub async fn update_schedule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateFlowSchedulePayload>,
+) -> impl IntoResponse {
+    match FlowScheduleService::update_schedule(&state.db, id, payload).await {
+        Ok(Some(schedule)) => (StatusCode::OK, Json(schedule)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Schedule not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    params(
+        ("id" = String, Path, description = "Schedule id")
+    ),
+    responses(
+        (status = 204, description = "Schedule deleted with its run history"),
+        (status = 404, description = "Schedule not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn delete_schedule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowScheduleService::delete_schedule(&state.db, id).await {
+        Ok(0) => (StatusCode::NOT_FOUND, "Schedule not found").into_response(),
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/runs",
+    params(
+        ("id" = String, Path, description = "Schedule id")
+    ),
+    responses(
+        (status = 200, description = "Latest runs of the schedule, newest first", body = [FlowScheduleRunModel]),
+        (status = 404, description = "Schedule not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_schedule_runs(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowScheduleService::get_runs(&state.db, id).await {
+        Ok(Some(runs)) => (StatusCode::OK, Json(runs)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Schedule not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
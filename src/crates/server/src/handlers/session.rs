@@ -0,0 +1,97 @@
+use crate::models::session::{CreateSessionPayload, Model as Session, SessionWithMessages};
+use crate::services::session::Service as SessionService;
+use crate::state::AppState;
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+#[utoipa::path(
+    post,
+    path = "/",
+    request_body = CreateSessionPayload,
+    responses(
+        (status = 201, description = "Session created successfully", body = Session),
+        (status = 400, description = "Invalid session policy")
+    )
+)]
+// Opens a new conversation session with an optional token budget policy.
+pub async fn create_session(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateSessionPayload>,
+) -> impl IntoResponse {
+    match SessionService::create_session(
+        &state.db,
+        payload.name,
+        payload.max_tokens,
+        payload.strategy,
+        payload.summarizer_task_id,
+    )
+    .await
+    {
+        Ok(session) => (StatusCode::CREATED, Json(session)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    responses(
+        (status = 200, description = "List all sessions", body = [Session]),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_sessions(State(state): State<AppState>) -> impl IntoResponse {
+    match SessionService::get_all_sessions(&state.db).await {
+        Ok(sessions) => (StatusCode::OK, Json(sessions)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    params(
+        ("id" = String, Path, description = "Session id")
+    ),
+    responses(
+        (status = 200, description = "Session with its message history", body = SessionWithMessages),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match SessionService::get_session_with_messages(&state.db, id).await {
+        Ok(Some(session)) => (StatusCode::OK, Json(session)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    params(
+        ("id" = String, Path, description = "Session id")
+    ),
+    responses(
+        (status = 200, description = "Session deleted"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn delete_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match SessionService::delete_session(&state.db, id).await {
+        Ok(rows) if rows > 0 => (StatusCode::OK, "Session deleted").into_response(),
+        Ok(_) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
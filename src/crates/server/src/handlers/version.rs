@@ -0,0 +1,114 @@
+use crate::models::flow_version::{FlowVersionDiff, Model as FlowVersionModel, PublishFlowPayload};
+use crate::services::flow_version::Service as FlowVersionService;
+use crate::state::AppState;
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+pub struct DiffQuery {
+    /// Newer version to compare with, the draft when omitted
+    pub to: Option<i32>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/versions",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    request_body = PublishFlowPayload,
+    responses(
+        (status = 201, description = "Draft steps published as the next version", body = FlowVersionModel),
+        (status = 400, description = "Draft without steps, with an invalid graph, or unchanged since the latest version"),
+        (status = 404, description = "Flow not found")
+    )
+)]
+/// Publishes the draft steps of a flow as an immutable version, run by default by the
+/// following executions.
+pub async fn publish_flow(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<PublishFlowPayload>,
+) -> impl IntoResponse {
+    match FlowVersionService::publish(&state.db, id, payload).await {
+        Ok(Some(version)) => (StatusCode::CREATED, Json(version)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Flow not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/versions",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    responses(
+        (status = 200, description = "Published versions of the flow, latest first", body = [FlowVersionModel]),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_flow_versions(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowVersionService::list_versions(&state.db, id).await {
+        Ok(versions) => (StatusCode::OK, Json(versions)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/versions/{version}",
+    params(
+        ("id" = String, Path, description = "Flow database id"),
+        ("version" = i32, Path, description = "Version number")
+    ),
+    responses(
+        (status = 200, description = "Version with the steps it runs", body = FlowVersionModel),
+        (status = 404, description = "Version not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_flow_version(
+    State(state): State<AppState>,
+    Path((id, version)): Path<(String, i32)>,
+) -> impl IntoResponse {
+    match FlowVersionService::get_version(&state.db, id, version).await {
+        Ok(Some(version)) => (StatusCode::OK, Json(version)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Version not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/versions/{version}/diff",
+    params(
+        ("id" = String, Path, description = "Flow database id"),
+        ("version" = i32, Path, description = "Older version number"),
+        DiffQuery
+    ),
+    responses(
+        (status = 200, description = "Steps added, removed and changed since the version", body = FlowVersionDiff),
+        (status = 404, description = "Version not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn diff_flow_versions(
+    State(state): State<AppState>,
+    Path((id, version)): Path<(String, i32)>,
+    Query(query): Query<DiffQuery>,
+) -> impl IntoResponse {
+    match FlowVersionService::diff(&state.db, id, version, query.to).await {
+        Ok(Some(diff)) => (StatusCode::OK, Json(diff)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Version not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
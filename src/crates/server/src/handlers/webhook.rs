@@ -0,0 +1,154 @@
+use crate::models::flow::FlowExecutionAccepted;
+use crate::models::flow_execution;
+use crate::models::flow_webhook::{ConfigureWebhookPayload, Model as FlowWebhookModel};
+use crate::services::flow_webhook::{
+    Delivery, Service as FlowWebhookService, WebhookError, WebhookRequest,
+};
+use crate::state::AppState;
+use axum::{
+    body::Bytes,
+    extract::{Json, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use std::collections::HashMap;
+
+#[utoipa::path(
+    get,
+    path = "/{id}/webhook",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    responses(
+        (status = 200, description = "Webhook of the flow, posted to at /hooks/{token}", body = FlowWebhookModel),
+        (status = 404, description = "The flow has no webhook"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowWebhookService::get_webhook(&state.db, id).await {
+        Ok(Some(webhook)) => (StatusCode::OK, Json(webhook)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "The flow has no webhook").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/{id}/webhook",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    request_body = ConfigureWebhookPayload,
+    responses(
+        (status = 200, description = "Webhook created or updated", body = FlowWebhookModel),
+        (status = 400, description = "Invalid secret or input template"),
+        (status = 404, description = "Flow not found")
+    )
+)]
+/// Exposes a flow through a webhook URL, or changes the settings of its webhook.
+pub async fn configure_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<ConfigureWebhookPayload>,
+) -> impl IntoResponse {
+    match FlowWebhookService::configure_webhook(&state.db, id, payload).await {
+        Ok(Some(webhook)) => (StatusCode::OK, Json(webhook)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Flow not found").into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}/webhook",
+    params(
+        ("id" = String, Path, description = "Flow database id")
+    ),
+    responses(
+        (status = 204, description = "Webhook removed, its URL no longer starts executions"),
+        (status = 404, description = "The flow has no webhook"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match FlowWebhookService::delete_webhook(&state.db, id).await {
+        Ok(0) => (StatusCode::NOT_FOUND, "The flow has no webhook").into_response(),
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/{token}",
+    params(
+        ("token" = String, Path, description = "Token of the flow webhook"),
+        ("X-AetherFlow-Signature" = String, Header, description = "sha256=<hex HMAC-SHA256 of the raw body, keyed with the webhook secret>"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Deliveries repeating a key start no new execution")
+    ),
+    request_body(content = Object, description = "Any body, JSON bodies are passed on as data"),
+    responses(
+        (status = 202, description = "Execution started", body = FlowExecutionAccepted),
+        (status = 200, description = "Idempotency key already received, execution of the first delivery", body = FlowExecutionAccepted),
+        (status = 400, description = "The input template failed on the request"),
+        (status = 401, description = "Missing or invalid signature"),
+        (status = 404, description = "Unknown or disabled webhook"),
+        (status = 409, description = "The first delivery of the idempotency key is still starting"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+/// Starts an execution of the flow behind a webhook with the posted body as input.
+pub async fn receive_webhook(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let request = WebhookRequest {
+        body: body.to_vec(),
+        headers: headers
+            .iter()
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|value| (name.as_str().to_string(), value.to_string()))
+            })
+            .collect(),
+        query,
+    };
+
+    match FlowWebhookService::receive(&state, token, request).await {
+        Ok(Delivery::Started(execution)) => {
+            (StatusCode::ACCEPTED, Json(accepted(execution))).into_response()
+        }
+        Ok(Delivery::Duplicate(execution)) => {
+            (StatusCode::OK, Json(accepted(execution))).into_response()
+        }
+        Err(WebhookError::NotFound) => (StatusCode::NOT_FOUND, "Webhook not found").into_response(),
+        Err(WebhookError::Unauthorized(e)) => (StatusCode::UNAUTHORIZED, e).into_response(),
+        Err(WebhookError::Invalid(e)) => (StatusCode::BAD_REQUEST, e).into_response(),
+        Err(WebhookError::InProgress) => (
+            StatusCode::CONFLICT,
+            "The first delivery of this idempotency key is still being processed",
+        )
+            .into_response(),
+        Err(WebhookError::Internal(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+fn accepted(execution: flow_execution::Model) -> FlowExecutionAccepted {
+    FlowExecutionAccepted {
+        status_url: format!("/flows/executions/{}", execution.id),
+        execution_id: execution.id,
+        status: execution.status,
+    }
+}
@@ -1,4 +1,5 @@
 use crate::services::agent::Service as AgentService;
+use crate::services::flow_approval::Service as FlowApprovalService;
 use crate::state::AppState;
 use axum::{
     extract::{
@@ -38,3 +39,52 @@ async fn handle_socket(mut socket: WebSocket, state: AppState) {
         }
     }
 }
+
+pub async fn approvals_ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    ws.on_upgrade(|socket| handle_approvals_socket(socket, state))
+}
+
+async fn handle_approvals_socket(mut socket: WebSocket, state: AppState) {
+    // Pushes the pending approvals on connection and whenever the list changes
+    let mut ticker = interval(Duration::from_secs(2));
+    let mut last_sent = None;
+
+    loop {
+        // The list only changes on the server side, reading the socket detects the close
+        // of an idle connection before the next change
+        tokio::select! {
+            _ = ticker.tick() => {}
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        }
+
+        match FlowApprovalService::list_pending(&state.db).await {
+            Ok(approvals) => {
+                let json = match serde_json::to_string(&approvals) {
+                    Ok(json) => json,
+                    Err(_) => continue,
+                };
+                if last_sent.as_ref() == Some(&json) {
+                    continue;
+                }
+                if socket
+                    .send(Message::Text(json.clone().into()))
+                    .await
+                    .is_err()
+                {
+                    // Connection was closed by the client
+                    break;
+                }
+                last_sent = Some(json);
+            }
+            Err(_) => {
+                break;
+            }
+        }
+    }
+}
@@ -5,16 +5,63 @@ use serde_json::Value;
 
 #[async_trait]
 pub trait AiProvider: Send + Sync {
-    // Transforms internal task data into the specific provider's format.
-    async fn build_payload(
+    // Builds a chat request carrying the whole conversation and the tools the model may call.
+    async fn build_tool_payload(
         &self,
         model: &str,
-        system_prompt: &str,
-        input: &Value,
+        messages: &[Value],
+        tools: &[ToolDefinition],
     ) -> Result<Value, String>;
 
-    // Parses the raw JSON response from the provider into a clean string.
-    async fn extract_response(&self, response: Value) -> Result<Value, String>;
+    // Splits a chat response into the assistant message and the tool calls it requested.
+    async fn extract_tool_calls(&self, response: &Value) -> Result<AssistantTurn, String>;
+
+    // Wraps the output of a tool invocation as a message the provider understands.
+    fn tool_result_message(&self, call: &ToolCall, result: &Value) -> Value;
+}
+
+/// A callable tool exposed to the model, backed by an agent task.
+#[derive(Debug, Clone)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    /// JSON Schema of the arguments (taken from the task `input_contract`)
+    pub parameters: Value,
+}
+
+/// A single tool invocation requested by the model.
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// One assistant turn of a tool calling conversation.
+#[derive(Debug, Clone)]
+pub struct AssistantTurn {
+    /// Provider-native message to append to the conversation history
+    pub message: Value,
+    /// Final text content, if the model produced any
+    pub content: Option<String>,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+// Serializes tool definitions in the function-calling format shared by OpenAI and Ollama.
+pub fn function_tools(tools: &[ToolDefinition]) -> Vec<Value> {
+    tools
+        .iter()
+        .map(|tool| {
+            serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters,
+                }
+            })
+        })
+        .collect()
 }
 
 pub mod anthropic;
@@ -22,12 +69,18 @@ pub mod gemini;
 pub mod ollama;
 pub mod openai;
 
-// Factory to get the appropriate integration instance.
-// pub fn get_integration(provider_name: &str) -> Box<dyn AiProvider> {
-//     match provider_name.to_lowercase().as_str() {
-//         "openai" => Box::new(openai::OpenAiIntegration),
-//         "anthropic" => Box::new(anthropic::AnthropicIntegration),
-//         "gemini" => Box::new(gemini::GeminiIntegration),
-//         _ => Box::new(ollama::OllamaIntegration),
-//     }
-// }
+/// Providers with an integration.
+pub const PROVIDERS: &[&str] = &["ollama", "openai"];
+
+// Factory to get the appropriate integration instance, rejecting unknown providers.
+pub fn get_integration(provider_name: &str) -> Result<Box<dyn AiProvider>, String> {
+    match provider_name.to_lowercase().as_str() {
+        "ollama" => Ok(Box::new(ollama::OllamaIntegration)),
+        "openai" => Ok(Box::new(openai::OpenAiIntegration)),
+        other => Err(format!(
+            "Unknown provider {} (available: {})",
+            other,
+            PROVIDERS.join(", ")
+        )),
+    }
+}
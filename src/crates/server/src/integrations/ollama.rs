@@ -0,0 +1,103 @@
+use crate::integrations::{function_tools, AiProvider, AssistantTurn, ToolCall, ToolDefinition};
+use sea_orm::prelude::async_trait::async_trait;
+use serde_json::{json, Value};
+
+/// Integration for a local Ollama server (`/api/generate` and `/api/chat`).
+pub struct OllamaIntegration;
+
+#[async_trait]
+impl AiProvider for OllamaIntegration {
+    async fn build_tool_payload(
+        &self,
+        model: &str,
+        messages: &[Value],
+        tools: &[ToolDefinition],
+    ) -> Result<Value, String> {
+        Ok(json!({
+            "model": model,
+            "messages": messages,
+            "tools": function_tools(tools),
+            "stream": false,
+        }))
+    }
+
+    async fn extract_tool_calls(&self, response: &Value) -> Result<AssistantTurn, String> {
+        let message = response
+            .get("message")
+            .cloned()
+            .ok_or_else(|| "Ollama chat response has no message".to_string())?;
+
+        let content = message
+            .get("content")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+
+        // Ollama does not assign ids to tool calls, so we derive one from the position
+        let tool_calls = message
+            .get("tool_calls")
+            .and_then(|v| v.as_array())
+            .map(|calls| {
+                calls
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, call)| {
+                        let function = call.get("function")?;
+                        Some(ToolCall {
+                            id: format!("call_{}", i),
+                            name: function.get("name")?.as_str()?.to_string(),
+                            arguments: function.get("arguments").cloned().unwrap_or(json!({})),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(AssistantTurn {
+            message,
+            content,
+            tool_calls,
+        })
+    }
+
+    fn tool_result_message(&self, call: &ToolCall, result: &Value) -> Value {
+        json!({
+            "role": "tool",
+            "tool_name": call.name,
+            "content": result.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn extracts_tool_calls_and_final_answers() {
+        let response = json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [
+                    { "function": { "name": "search", "arguments": { "query": "rust" } } },
+                    { "function": { "name": "clock" } }
+                ]
+            }
+        });
+        let turn = OllamaIntegration
+            .extract_tool_calls(&response)
+            .await
+            .unwrap();
+        assert_eq!(turn.content, None);
+        let ids: Vec<&str> = turn.tool_calls.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["call_0", "call_1"]);
+        assert_eq!(turn.tool_calls[0].arguments, json!({ "query": "rust" }));
+        assert_eq!(turn.tool_calls[1].arguments, json!({}));
+
+        let answer = json!({ "message": { "role": "assistant", "content": "Done" } });
+        let turn = OllamaIntegration.extract_tool_calls(&answer).await.unwrap();
+        assert_eq!(turn.content.as_deref(), Some("Done"));
+        assert!(turn.tool_calls.is_empty());
+    }
+}
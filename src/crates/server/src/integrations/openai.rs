@@ -0,0 +1,118 @@
+use crate::integrations::{function_tools, AiProvider, AssistantTurn, ToolCall, ToolDefinition};
+use sea_orm::prelude::async_trait::async_trait;
+use serde_json::{json, Value};
+
+/// Integration for the OpenAI Chat Completions API and compatible servers.
+pub struct OpenAiIntegration;
+
+#[async_trait]
+impl AiProvider for OpenAiIntegration {
+    async fn build_tool_payload(
+        &self,
+        model: &str,
+        messages: &[Value],
+        tools: &[ToolDefinition],
+    ) -> Result<Value, String> {
+        Ok(json!({
+            "model": model,
+            "messages": messages,
+            "tools": function_tools(tools),
+        }))
+    }
+
+    async fn extract_tool_calls(&self, response: &Value) -> Result<AssistantTurn, String> {
+        let message = response
+            .pointer("/choices/0/message")
+            .cloned()
+            .ok_or_else(|| "OpenAI response has no message".to_string())?;
+
+        let content = message
+            .get("content")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+
+        let mut tool_calls = Vec::new();
+        if let Some(calls) = message.get("tool_calls").and_then(|v| v.as_array()) {
+            for call in calls {
+                let id = call.get("id").and_then(|v| v.as_str()).unwrap_or_default();
+                let function = call
+                    .get("function")
+                    .ok_or_else(|| "OpenAI tool call has no function".to_string())?;
+                let name = function
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| "OpenAI tool call has no name".to_string())?;
+
+                // Arguments arrive as a JSON encoded string
+                let raw_args = function
+                    .get("arguments")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("{}");
+                let arguments = serde_json::from_str(raw_args)
+                    .map_err(|e| format!("Invalid arguments for tool {}: {}", name, e))?;
+
+                tool_calls.push(ToolCall {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    arguments,
+                });
+            }
+        }
+
+        Ok(AssistantTurn {
+            message,
+            content,
+            tool_calls,
+        })
+    }
+
+    fn tool_result_message(&self, call: &ToolCall, result: &Value) -> Value {
+        json!({
+            "role": "tool",
+            "tool_call_id": call.id,
+            "content": result.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn extracts_tool_calls_with_encoded_arguments() {
+        let response = json!({
+            "choices": [{ "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{
+                    "id": "call_abc",
+                    "type": "function",
+                    "function": { "name": "search", "arguments": "{\"query\":\"rust\"}" }
+                }]
+            }}]
+        });
+        let turn = OpenAiIntegration
+            .extract_tool_calls(&response)
+            .await
+            .unwrap();
+        assert_eq!(turn.content, None);
+        assert_eq!(turn.tool_calls.len(), 1);
+        assert_eq!(turn.tool_calls[0].id, "call_abc");
+        assert_eq!(turn.tool_calls[0].name, "search");
+        assert_eq!(turn.tool_calls[0].arguments, json!({ "query": "rust" }));
+
+        let broken = json!({
+            "choices": [{ "message": { "tool_calls": [{
+                "id": "call_abc",
+                "function": { "name": "search", "arguments": "{not json" }
+            }]}}]
+        });
+        assert!(OpenAiIntegration.extract_tool_calls(&broken).await.is_err());
+        assert!(OpenAiIntegration
+            .extract_tool_calls(&json!({}))
+            .await
+            .is_err());
+    }
+}
@@ -28,9 +28,35 @@ pub mod state;
             handlers::agent::CreateAgentPayload,
             handlers::agent_task::CreateAgentTaskPayload,
             handlers::gateway::ExecuteAgentPayload, handlers::gateway::ExecuteAgentResponse,
+            handlers::gateway::AgentCallAccepted,
             models::flow::ExecuteFlowPayload, models::flow::ExecuteFlowResponse,
-            models::flow::CreateFlowPayload, models::flow_step::CreateFlowStepPayload,
-            models::flow_step::FlowStepWithTask, models::flow::FlowWithSteps
+            models::flow::FlowExecutionAccepted, models::flow::FlowSimulation,
+            models::flow::SimulatedStep, models::flow_execution::Model,
+            models::flow_execution::StepProgress, models::flow_step_execution::Model,
+            models::flow::CreateFlowPayload, models::flow::UpdateFlowPayload,
+            models::flow_step::CreateFlowStepPayload, models::flow_step::UpdateFlowStepPayload,
+            models::flow_step::ReorderFlowStepsPayload,
+            models::flow_step::FlowStepWithTask, models::flow::FlowWithSteps,
+            models::flow_approval::Model, models::flow_approval::DecideApprovalPayload,
+            models::flow_schedule::Model, models::flow_schedule_run::Model,
+            models::flow_schedule::CreateFlowSchedulePayload,
+            models::flow_schedule::UpdateFlowSchedulePayload,
+            models::flow_webhook::Model, models::flow_webhook::ConfigureWebhookPayload,
+            models::flow_event_subscription::Model,
+            models::flow_event_subscription::CreateSubscriptionPayload,
+            models::callback_delivery::Model,
+            models::flow_version::Model, models::flow_version::PublishFlowPayload,
+            models::flow_version::FlowVersionDiff, models::flow_version::StepChange,
+            models::flow_version::FieldChange,
+            models::flow_document::FlowDocument, models::flow_document::FlowDefinition,
+            models::flow_document::AgentDefinition, models::flow_document::TaskDefinition,
+            models::flow_document::StepDefinition, models::flow_document::FlowImportReport,
+            models::session::Model, models::session_message::Model,
+            models::session::CreateSessionPayload, models::session::SessionWithMessages,
+            models::provider_limit::Model, models::provider_limit::UpsertProviderLimitPayload,
+            services::rate_limiter::AgentRateLimit,
+            services::contract::ContractViolation, services::contract::Violation,
+            services::contract::FlowCheck, services::contract::EdgeCheck
         )
     ),
     tags(
@@ -81,16 +107,46 @@ async fn main() {
         .build()
         .expect("Failed to build HTTP client");
 
+    // In-memory response cache, bounded by RESPONSE_CACHE_MAX_ENTRIES
+    let cache_max_entries = env::var("RESPONSE_CACHE_MAX_ENTRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1000);
+    let cache = services::response_cache::ResponseCache::new(cache_max_entries);
+
+    // Rate limiter of agent calls, seeded with the stored provider credential limits
+    let limiter = services::rate_limiter::RateLimiter::new();
+    match services::provider_limit::Service::get_all_limits(&db).await {
+        Ok(limits) => limiter.set_provider_limits(limits),
+        Err(e) => tracing::warn!("Failed to load provider limits: {}", e),
+    }
+
     // Bundle context dependencies to inject into Axum handlers
     let app_state = state::AppState {
         db: db.clone(),
         director,
         http_client: http_client.clone(),
+        cache,
+        limiter,
+        executions: services::flow_worker::RunningExecutions::new(),
     };
 
     // Spawn the background worker that pings agents to monitor their health
     services::monitor::Monitor::start_health_check(db.clone(), http_client.clone());
 
+    // Spawn the executor workers of the flow execution queue (FLOW_WORKERS, default 4)
+    let flow_workers = env::var("FLOW_WORKERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(4);
+    services::flow_worker::Service::start(app_state.clone(), flow_workers);
+
+    // Spawn the scheduler starting the executions of the cron schedules
+    services::flow_schedule::Service::start(app_state.clone());
+
+    // Spawn the dispatcher posting execution and agent call events to their callbacks
+    services::callback::Service::start(app_state.clone());
+
     // Load the Router and collect API docs from routes
     let (router, api) = routes::create_router();
 
@@ -1,6 +1,20 @@
 pub mod agent;
 pub mod agent_log;
 pub mod agent_task;
+pub mod callback_delivery;
 pub mod flow;
+pub mod flow_approval;
+pub mod flow_document;
+pub mod flow_event_subscription;
 pub mod flow_execution;
+pub mod flow_schedule;
+pub mod flow_schedule_run;
 pub mod flow_step;
+pub mod flow_step_execution;
+pub mod flow_version;
+pub mod flow_webhook;
+pub mod flow_webhook_delivery;
+pub mod provider_limit;
+pub mod response_cache;
+pub mod session;
+pub mod session_message;
@@ -44,6 +44,9 @@ pub struct Model {
 
     /* The generic source path or description where the agent is located */
     pub source: Option<String>,
+
+    /* Requests per second, burst and concurrency limits applied to calls to this agent */
+    pub rate_limit: Option<serde_json::Value>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
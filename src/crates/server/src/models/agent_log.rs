@@ -15,6 +15,14 @@ pub struct Model {
 
     pub retries: i32,
 
+    pub task_id: Option<String>,
+
+    /* True when the response was served from the response cache */
+    pub cache_hit: bool,
+
+    /* Fallback target that handled the call, null when the task has no fallback chain */
+    pub target: Option<serde_json::Value>,
+
     pub created_at: Option<DateTimeWithTimeZone>,
 }
 
@@ -0,0 +1,67 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const EVENT_EXECUTION_COMPLETED: &str = "execution.completed";
+pub const EVENT_EXECUTION_FAILED: &str = "execution.failed";
+pub const EVENT_EXECUTION_CANCELLED: &str = "execution.cancelled";
+pub const EVENT_AGENT_CALL_COMPLETED: &str = "agent_call.completed";
+pub const EVENT_AGENT_CALL_FAILED: &str = "agent_call.failed";
+
+/// Events flows can be subscribed to.
+pub const EXECUTION_EVENTS: [&str; 3] = [
+    EVENT_EXECUTION_COMPLETED,
+    EVENT_EXECUTION_FAILED,
+    EVENT_EXECUTION_CANCELLED,
+];
+
+pub const DELIVERY_PENDING: &str = "Pending";
+pub const DELIVERY_DELIVERED: &str = "Delivered";
+/// Every attempt failed, the delivery is only retried on request.
+pub const DELIVERY_DEAD_LETTER: &str = "DeadLetter";
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "callback_deliveries")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    pub event: String,
+
+    pub url: String,
+
+    /* Body posted, { "id", "event", "created_at", "data" } */
+    pub payload: serde_json::Value,
+
+    #[schema(value_type = Option<String>)]
+    pub execution_id: Option<String>,
+
+    /* Subscription signing the event, null for callback URLs */
+    #[schema(value_type = Option<String>)]
+    pub subscription_id: Option<String>,
+
+    /* Pending, Delivered or DeadLetter */
+    pub status: String,
+
+    pub attempts: i32,
+
+    #[schema(value_type = Option<String>)]
+    pub next_attempt_at: Option<DateTimeWithTimeZone>,
+
+    /* HTTP status answered to the last attempt */
+    pub response_status: Option<i32>,
+
+    #[schema(value_type = Option<String>)]
+    pub last_error: Option<String>,
+
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub delivered_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,6 +1,7 @@
 use crate::models::flow_step::FlowStepWithTask;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use utoipa::ToSchema;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
@@ -48,11 +49,46 @@ pub struct CreateFlowPayload {
     pub description: Option<String>,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateFlowPayload {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
 #[derive(Deserialize, ToSchema)]
 pub struct ExecuteFlowPayload {
     /// Initial payload to send to the first agent in the flow sequence
     #[schema(value_type = Object)]
     pub payload: serde_json::Value,
+    /// Optional session: its history is prepended to the first step and the run is appended to it
+    pub session_id: Option<String>,
+    /// Run in the background: answer 202 with the execution id instead of waiting for the output
+    #[serde(default, rename = "async")]
+    pub run_async: bool,
+    /// With `async`: URL posted the completed, failed or cancelled event of the execution,
+    /// signed with the CALLBACK_SECRET key of the server
+    pub callback_url: Option<String>,
+    /// Published version to run, the latest one when omitted (the draft for flows never published)
+    pub version: Option<i32>,
+    /// Run the draft steps instead of a published version
+    #[serde(default)]
+    pub draft: bool,
+    /// Simulate the run: render the payload of every step without calling any agent
+    #[serde(default)]
+    pub dry_run: bool,
+    /// With `dry_run`: output of steps keyed by step name (or id), used instead of a placeholder
+    #[schema(value_type = Option<Object>)]
+    pub mocks: Option<HashMap<String, serde_json::Value>>,
+    /// With `dry_run`: execution whose recorded step outputs are used for the steps not mocked
+    pub mock_execution_id: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FlowExecutionAccepted {
+    pub execution_id: String,
+    pub status: String,
+    /// Where the progress and the output of the execution are polled
+    pub status_url: String,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -62,6 +98,51 @@ pub struct ExecuteFlowResponse {
     pub response: serde_json::Value,
 }
 
+/// Output of a simulated step taken from the `mocks` of the request.
+pub const SOURCE_MOCK: &str = "mock";
+/// Output of a simulated step recorded by an earlier execution.
+pub const SOURCE_RECORDED: &str = "recorded";
+/// Input of a simulated router or approval step passed on as output.
+pub const SOURCE_PASSTHROUGH: &str = "passthrough";
+/// Generated output of a simulated step without mock nor recording.
+pub const SOURCE_PLACEHOLDER: &str = "placeholder";
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SimulatedStep {
+    pub step_id: String,
+    /// Step name, or its id
+    pub name: String,
+    pub step_type: String,
+    /// "Completed", "Skipped" or "Failed"
+    pub status: String,
+    #[schema(value_type = Option<Object>)]
+    pub input: Option<serde_json::Value>,
+    /// Payload the agent would receive (a list of payloads for map steps)
+    #[schema(value_type = Option<Object>)]
+    pub payload: Option<serde_json::Value>,
+    #[schema(value_type = Option<Object>)]
+    pub output: Option<serde_json::Value>,
+    /// "mock", "recorded", "passthrough" or "placeholder"
+    pub output_source: Option<String>,
+    /// Route chosen by router steps
+    #[schema(value_type = Option<Object>)]
+    pub decision: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+/// Result of a dry run: nothing is called nor recorded.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FlowSimulation {
+    /// Published version simulated, none for the draft
+    pub version: Option<i32>,
+    /// Steps in the order they would run
+    pub steps: Vec<SimulatedStep>,
+    /// Output the flow would return, none when a step failed
+    #[schema(value_type = Option<Object>)]
+    pub output: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct FlowWithSteps {
     #[serde(flatten)]
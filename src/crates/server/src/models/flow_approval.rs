@@ -0,0 +1,89 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const APPROVAL_PENDING: &str = "Pending";
+pub const APPROVAL_APPROVED: &str = "Approved";
+pub const APPROVAL_REJECTED: &str = "Rejected";
+pub const APPROVAL_TIMED_OUT: &str = "TimedOut";
+pub const APPROVAL_CANCELLED: &str = "Cancelled";
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "flow_approvals")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    pub execution_id: String,
+
+    pub flow_id: String,
+
+    pub step_id: String,
+
+    /* Step name, or its id */
+    pub step_name: String,
+
+    /* Record of the step in `flow_step_executions` */
+    #[schema(value_type = Option<String>)]
+    pub step_execution_id: Option<String>,
+
+    /* Pending, Approved, Rejected, TimedOut (rejected by its timeout) or Cancelled */
+    pub status: String,
+
+    /* Instructions for the reviewer, rendered from the step config */
+    #[schema(value_type = Option<String>)]
+    pub message: Option<String>,
+
+    /* Data submitted for review: the input of the step */
+    pub data: Option<serde_json::Value>,
+
+    /* Approved data passed on to the next steps, edited by the reviewer or unchanged */
+    pub response: Option<serde_json::Value>,
+
+    #[schema(value_type = Option<String>)]
+    pub reviewer: Option<String>,
+
+    #[schema(value_type = Option<String>)]
+    pub comment: Option<String>,
+
+    /* "reject" or "approve", applied once `expires_at` is reached */
+    pub on_timeout: String,
+
+    #[schema(value_type = Option<String>)]
+    pub expires_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub decided_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::models::flow_execution::Entity",
+        from = "Column::ExecutionId",
+        to = "crate::models::flow_execution::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    FlowExecution,
+}
+
+impl Related<crate::models::flow_execution::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FlowExecution.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DecideApprovalPayload {
+    /// Edited data passed on instead of the submitted one (approvals only)
+    #[schema(value_type = Option<Object>)]
+    pub data: Option<serde_json::Value>,
+    pub reviewer: Option<String>,
+    pub comment: Option<String>,
+}
@@ -0,0 +1,106 @@
+use crate::models::flow_version::{FieldChange, FlowVersionDiff};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Format of the documents written by this server.
+pub const DOCUMENT_FORMAT: u32 = 1;
+
+/// Portable definition of a flow: steps reference their tasks (fallback tasks of error
+/// policies included) as `<agent slug>/<task name>` and their sub-flows by name, never by
+/// database id. Task settings reference their tools and fallback targets the same way,
+/// fallback agents by slug.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FlowDocument {
+    /// Format of the document, currently 1
+    pub format: u32,
+    pub flow: FlowDefinition,
+    /// Agents of the referenced tasks, registered on import when missing
+    #[serde(default)]
+    pub agents: Vec<AgentDefinition>,
+    /// Tasks run by the steps or referenced by their tasks, created or updated on import
+    #[serde(default)]
+    pub tasks: Vec<TaskDefinition>,
+    pub steps: Vec<StepDefinition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FlowDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AgentDefinition {
+    pub slug: String,
+    /// Endpoint of the agent, only used when the agent is not registered yet
+    pub endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TaskDefinition {
+    /// Slug of the agent running the task
+    pub agent: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub task_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub input_contract: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub output_contract: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub settings: Option<serde_json::Value>,
+}
+
+/// Step of a flow document. Named steps are matched by name on import, unnamed ones by id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StepDefinition {
+    /// Only for unnamed steps, referenced by this id in `depends_on`, router targets, error
+    /// handlers and `steps` template variables
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub step_type: String,
+    pub step_order: i32,
+    /// Task run by the step, as `<agent slug>/<task name>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+    /// Name of the flow run by sub-flow steps and map steps, replacing `flow_id` in the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub config: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<String>>,
+}
+
+/// Changes made, or that would be made on a dry run, by importing a flow document.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FlowImportReport {
+    pub dry_run: bool,
+    /// Flow updated or created, not set on the dry run of a new flow
+    pub flow_id: Option<String>,
+    /// The document creates a new flow
+    pub created: bool,
+    /// Changes of the flow name and description
+    pub flow_changes: Vec<FieldChange>,
+    /// Slugs of the agents registered
+    pub agents_created: Vec<String>,
+    /// Tasks created and updated, as `<agent slug>/<task name>`
+    pub tasks_created: Vec<String>,
+    pub tasks_updated: Vec<String>,
+    /// Changes of the draft steps
+    pub steps: FlowVersionDiff,
+}
@@ -0,0 +1,64 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "flow_event_subscriptions")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    pub flow_id: String,
+
+    pub url: String,
+
+    /* HMAC-SHA256 key, events are signed in the X-AetherFlow-Signature header */
+    pub secret: String,
+
+    /* Names of the events posted, e.g. ["execution.failed"] */
+    #[schema(value_type = Vec<String>)]
+    pub events: serde_json::Value,
+
+    pub enabled: bool,
+
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::models::flow::Entity",
+        from = "Column::FlowId",
+        to = "crate::models::flow::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Flow,
+}
+
+impl Related<crate::models::flow::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Flow.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn subscribes_to(&self, event: &str) -> bool {
+        self.events
+            .as_array()
+            .is_some_and(|events| events.iter().any(|e| e.as_str() == Some(event)))
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateSubscriptionPayload {
+    pub url: String,
+    /// Events posted to the URL, every execution event when omitted:
+    /// "execution.completed", "execution.failed", "execution.cancelled"
+    pub events: Option<Vec<String>>,
+    /// Signing key, generated when omitted
+    pub secret: Option<String>,
+}
@@ -1,7 +1,16 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+pub const STEP_PENDING: &str = "Pending";
+pub const STEP_RUNNING: &str = "Running";
+pub const STEP_COMPLETED: &str = "Completed";
+pub const STEP_FAILED: &str = "Failed";
+pub const STEP_SKIPPED: &str = "Skipped";
+pub const STEP_CANCELLED: &str = "Cancelled";
+pub const STEP_WAITING: &str = "WaitingForApproval";
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
 #[sea_orm(table_name = "flow_executions")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
@@ -9,17 +18,81 @@ pub struct Model {
 
     pub flow_id: String,
 
+    /* Published version of the flow run, null when the draft steps were run */
+    #[schema(value_type = Option<String>)]
+    pub version_id: Option<String>,
+
+    /* Pending (queued in the background), Running, WaitingForApproval, Completed, Failed, Cancelled */
     pub status: String,
 
     pub input_data: Option<serde_json::Value>,
 
     pub output_data: Option<serde_json::Value>,
 
+    /* Decisions of the router steps: evaluated value, matching route and selected steps */
+    pub branches: Option<serde_json::Value>,
+
+    /* Progress of every step, see `StepProgress` */
+    pub steps: Option<serde_json::Value>,
+
+    /* Session extended by the run */
+    #[schema(value_type = Option<String>)]
+    pub session_id: Option<String>,
+
+    /* Worker running the execution, see `flow_worker` */
+    #[schema(value_type = Option<String>)]
+    pub claimed_by: Option<String>,
+
+    #[schema(value_type = Option<String>)]
+    pub heartbeat_at: Option<DateTimeWithTimeZone>,
+
+    /* Times a worker claimed the execution */
+    pub attempts: i32,
+
+    /* Set by the cancel endpoint, stops the worker running the execution */
+    pub cancel_requested: bool,
+
+    /* Execution and step running this one as a sub-flow, null for top-level executions */
+    #[schema(value_type = Option<String>)]
+    pub parent_execution_id: Option<String>,
+
+    #[schema(value_type = Option<String>)]
+    pub parent_step_id: Option<String>,
+
+    /* Nesting level below the top-level execution */
+    pub depth: i32,
+
+    /* Posted the completed, failed or cancelled event of the execution */
+    #[schema(value_type = Option<String>)]
+    pub callback_url: Option<String>,
+
+    /* Events of the last settlement queued for delivery */
+    #[schema(value_type = Option<String>)]
+    pub notified_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
     pub started_at: Option<DateTimeWithTimeZone>,
 
+    #[schema(value_type = Option<String>)]
     pub completed_at: Option<DateTimeWithTimeZone>,
 }
 
+/// Progress of a step within an execution.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StepProgress {
+    pub step_id: String,
+    /// Step name, or its id
+    pub name: String,
+    /// Pending, Running, WaitingForApproval, Completed, Failed, Skipped or Cancelled
+    pub status: String,
+    #[schema(value_type = Option<String>)]
+    pub started_at: Option<String>,
+    #[schema(value_type = Option<String>)]
+    pub completed_at: Option<String>,
+    #[schema(value_type = Option<String>)]
+    pub error: Option<String>,
+}
+
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(
@@ -0,0 +1,106 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// An occurrence is skipped while the previous execution of the schedule runs (default).
+pub const OVERLAP_SKIP: &str = "skip";
+/// Every occurrence starts an execution, even when the previous one still runs.
+pub const OVERLAP_ALLOW: &str = "allow";
+/// The previous execution is cancelled before the new one starts.
+pub const OVERLAP_CANCEL_PREVIOUS: &str = "cancel_previous";
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "flow_schedules")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    pub flow_id: String,
+
+    #[schema(value_type = Option<String>)]
+    pub name: Option<String>,
+
+    /* 5 fields (minute precision) or 6 fields starting with seconds */
+    pub cron: String,
+
+    /* IANA timezone the cron expression is evaluated in */
+    pub timezone: String,
+
+    /* Fixed input of every execution */
+    pub input: Option<serde_json::Value>,
+
+    pub enabled: bool,
+
+    /* "skip", "allow" or "cancel_previous" */
+    pub overlap_policy: String,
+
+    #[schema(value_type = Option<String>)]
+    pub next_run_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub last_run_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub updated_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::models::flow::Entity",
+        from = "Column::FlowId",
+        to = "crate::models::flow::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Flow,
+    #[sea_orm(has_many = "crate::models::flow_schedule_run::Entity")]
+    FlowScheduleRun,
+}
+
+impl Related<crate::models::flow::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Flow.def()
+    }
+}
+
+impl Related<crate::models::flow_schedule_run::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FlowScheduleRun.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateFlowSchedulePayload {
+    pub flow_id: String,
+    pub name: Option<String>,
+    /// Cron expression, e.g. "0 9 * * 1-5" (Unix day numbers, Sunday is 0 or 7). With a
+    /// leading seconds field, days are numbered 1-7 from Sunday
+    pub cron: String,
+    /// IANA timezone, "UTC" when omitted
+    pub timezone: Option<String>,
+    /// Input of every execution
+    #[schema(value_type = Option<Object>)]
+    pub input: Option<serde_json::Value>,
+    /// Enabled when omitted
+    pub enabled: Option<bool>,
+    /// "skip" (default), "allow" or "cancel_previous"
+    pub overlap_policy: Option<String>,
+}
+
+/// Changes of a schedule, omitted fields are kept.
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateFlowSchedulePayload {
+    pub name: Option<String>,
+    pub cron: Option<String>,
+    pub timezone: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub input: Option<serde_json::Value>,
+    pub enabled: Option<bool>,
+    pub overlap_policy: Option<String>,
+}
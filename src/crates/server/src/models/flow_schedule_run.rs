@@ -0,0 +1,54 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const RUN_TRIGGERED: &str = "Triggered";
+pub const RUN_SKIPPED: &str = "Skipped";
+pub const RUN_FAILED: &str = "Failed";
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "flow_schedule_runs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    pub schedule_id: String,
+
+    /* Execution started by the occurrence, null when it was skipped or failed to start */
+    #[schema(value_type = Option<String>)]
+    pub execution_id: Option<String>,
+
+    /* Triggered, Skipped or Failed */
+    pub status: String,
+
+    /* Occurrence of the cron expression */
+    #[schema(value_type = Option<String>)]
+    pub scheduled_for: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub triggered_at: Option<DateTimeWithTimeZone>,
+
+    /* Why the occurrence was skipped or failed */
+    #[schema(value_type = Option<String>)]
+    pub error: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::models::flow_schedule::Entity",
+        from = "Column::ScheduleId",
+        to = "crate::models::flow_schedule::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    FlowSchedule,
+}
+
+impl Related<crate::models::flow_schedule::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FlowSchedule.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,7 +1,18 @@
 use sea_orm::entity::prelude::*;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use utoipa::ToSchema;
 
+/// Step running an agent task (default).
+pub const STEP_TYPE_TASK: &str = "task";
+/// Step choosing which of its downstream steps run, see `services::flow_router`.
+pub const STEP_TYPE_ROUTER: &str = "router";
+/// Step running its task (or a sub-flow) over every item of an array, see `services::flow_map`.
+pub const STEP_TYPE_MAP: &str = "map";
+/// Step running another flow in a child execution, see `services::flow_subflow`.
+pub const STEP_TYPE_FLOW: &str = "flow";
+/// Step pausing the execution until a reviewer approves its input, see `services::flow_approval`.
+pub const STEP_TYPE_APPROVAL: &str = "approval";
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
 #[sea_orm(table_name = "flow_steps")]
 pub struct Model {
@@ -10,12 +21,24 @@ pub struct Model {
 
     pub flow_id: String,
 
-    pub task_id: String,
+    /* Task run by the step, null for steps that do not call an agent (routers, sub-flows, approvals) */
+    #[schema(value_type = Option<String>)]
+    pub task_id: Option<String>,
+
+    /* "task", "router", "map", "flow" or "approval" */
+    pub step_type: String,
 
     pub step_order: i32,
 
     pub config: Option<serde_json::Value>,
 
+    /* Optional name, unique in the flow, usable in `depends_on` and as fan-in key */
+    #[schema(value_type = Option<String>)]
+    pub name: Option<String>,
+
+    /* Ids or names of the upstream steps, null means the previous step by order */
+    pub depends_on: Option<serde_json::Value>,
+
     #[schema(value_type = Option<String>)]
     pub created_at: Option<DateTimeWithTimeZone>,
 }
@@ -54,11 +77,93 @@ impl Related<crate::models::agent_task::Entity> for Entity {
 
 impl ActiveModelBehavior for ActiveModel {}
 
+/// Test fixtures: a task step of flow "flow" running task "task", named after its id.
+#[cfg(test)]
+impl Model {
+    pub fn fixture(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            flow_id: "flow".to_string(),
+            task_id: Some("task".to_string()),
+            step_type: STEP_TYPE_TASK.to_string(),
+            step_order: 0,
+            config: None,
+            name: Some(id.to_string()),
+            depends_on: None,
+            created_at: None,
+        }
+    }
+
+    pub fn with_order(mut self, step_order: i32) -> Self {
+        self.step_order = step_order;
+        self
+    }
+
+    pub fn with_type(mut self, step_type: &str) -> Self {
+        self.step_type = step_type.to_string();
+        self
+    }
+
+    pub fn with_config(mut self, config: serde_json::Value) -> Self {
+        self.config = Some(config);
+        self
+    }
+}
+
 #[derive(Deserialize, ToSchema)]
 pub struct CreateFlowStepPayload {
-    pub task_id: String,
+    /// Task run by the step, required for "task" steps
+    pub task_id: Option<String>,
+    /// "task" (default), "router" (config holds the routes to steps depending on it), "map"
+    /// (config holds the items path), "flow" (config holds the `flow_id` of the sub-flow) or
+    /// "approval" (optional config with the reviewer message and the timeout)
+    pub step_type: Option<String>,
+    /// Position of the step, unique in the flow
     pub step_order: i32,
+    /// Payload template of the step. Task, map and sub-flow steps may add an `error_policy`
+    /// (retries, fallback task, continue on error or error handler, a step depending on it)
     pub config: Option<serde_json::Value>,
+    /// Optional name, unique in the flow, used as key of fan-in maps
+    pub name: Option<String>,
+    /// Ids or names of the steps this one waits for. Omitted: the previous step by order,
+    /// empty: a root step receiving the flow input. Several entries make a fan-in step
+    /// receiving a map of the upstream outputs keyed by step name (or id).
+    pub depends_on: Option<Vec<String>>,
+}
+
+// Tells an explicit null (`Some(None)`) from an omitted field (`None`)
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Changes of a step, omitted fields are kept and null clears the nullable ones.
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateFlowStepPayload {
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub task_id: Option<Option<String>>,
+    pub step_type: Option<String>,
+    pub step_order: Option<i32>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<Object>)]
+    pub config: Option<Option<serde_json::Value>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub name: Option<Option<String>>,
+    /// Null goes back to depending on the previous step by order
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub depends_on: Option<Option<Vec<String>>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReorderFlowStepsPayload {
+    /// Every step id of the flow, in the new order: `step_order` becomes 1, 2, 3...
+    pub step_ids: Vec<String>,
 }
 
 #[derive(Serialize, ToSchema)]
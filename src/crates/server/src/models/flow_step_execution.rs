@@ -0,0 +1,67 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "flow_step_executions")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    pub execution_id: String,
+
+    pub step_id: String,
+
+    /* Step name, or its id */
+    pub step_name: String,
+
+    /* Running, WaitingForApproval, Completed, Failed, Skipped, Cancelled, or Superseded once a
+     * retried step ran again */
+    pub status: String,
+
+    /* Payload sent to the task once the step config is rendered */
+    pub payload: Option<serde_json::Value>,
+
+    pub response: Option<serde_json::Value>,
+
+    #[schema(value_type = Option<String>)]
+    pub error: Option<String>,
+
+    /* Retries of the agent calls, the attempts of the error policy are in `recovery` */
+    pub retries: i32,
+
+    /* Last agent log written by the step */
+    #[schema(value_type = Option<String>)]
+    pub agent_log_id: Option<String>,
+
+    /* How the error policy of the step dealt with its failures, see `flow_error_policy::Recovery` */
+    pub recovery: Option<serde_json::Value>,
+
+    #[schema(value_type = Option<String>)]
+    pub started_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub completed_at: Option<DateTimeWithTimeZone>,
+
+    pub duration_ms: Option<i64>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::models::flow_execution::Entity",
+        from = "Column::ExecutionId",
+        to = "crate::models::flow_execution::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    FlowExecution,
+}
+
+impl Related<crate::models::flow_execution::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FlowExecution.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
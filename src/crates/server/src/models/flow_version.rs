@@ -0,0 +1,80 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "flow_versions")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    pub flow_id: String,
+
+    /* 1, 2, ... per flow */
+    pub version: i32,
+
+    /* Steps of the draft when it was published, see `flow_step::Model` */
+    #[schema(value_type = Vec<Object>)]
+    pub steps: serde_json::Value,
+
+    #[schema(value_type = Option<String>)]
+    pub notes: Option<String>,
+
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::models::flow::Entity",
+        from = "Column::FlowId",
+        to = "crate::models::flow::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Flow,
+}
+
+impl Related<crate::models::flow::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Flow.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PublishFlowPayload {
+    /// What changed since the previous version
+    pub notes: Option<String>,
+}
+
+/// Field of a step that differs between two revisions.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FieldChange {
+    pub field: String,
+    #[schema(value_type = Object)]
+    pub from: serde_json::Value,
+    #[schema(value_type = Object)]
+    pub to: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct StepChange {
+    pub step_id: String,
+    /// Step name in the newer revision, or its id
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Differences between the steps of two revisions, steps being matched by id.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FlowVersionDiff {
+    /// Version number, or "draft"
+    pub from: String,
+    pub to: String,
+    pub added: Vec<crate::models::flow_step::Model>,
+    pub removed: Vec<crate::models::flow_step::Model>,
+    pub changed: Vec<StepChange>,
+}
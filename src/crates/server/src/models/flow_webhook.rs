@@ -0,0 +1,72 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "flow_webhooks")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    #[sea_orm(unique)]
+    pub flow_id: String,
+
+    /* The webhook URL is /hooks/{token} */
+    #[sea_orm(unique)]
+    pub token: String,
+
+    /* HMAC-SHA256 key, senders sign the raw body in the X-AetherFlow-Signature header */
+    pub secret: String,
+
+    /* Template mapping the request (body, headers, query) to the flow input */
+    #[schema(value_type = Option<String>)]
+    pub input_template: Option<String>,
+
+    pub enabled: bool,
+
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub updated_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::models::flow::Entity",
+        from = "Column::FlowId",
+        to = "crate::models::flow::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Flow,
+    #[sea_orm(has_many = "crate::models::flow_webhook_delivery::Entity")]
+    FlowWebhookDelivery,
+}
+
+impl Related<crate::models::flow::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Flow.def()
+    }
+}
+
+impl Related<crate::models::flow_webhook_delivery::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FlowWebhookDelivery.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Webhook settings of a flow, omitted fields are kept (or defaulted on creation).
+#[derive(Deserialize, ToSchema)]
+pub struct ConfigureWebhookPayload {
+    /// Signing key, generated when the webhook is created without one
+    pub secret: Option<String>,
+    /// Template rendering the flow input from `body`, `headers` and `query`,
+    /// e.g. `{"customer": {{ body.data.email | json }}}`. An empty string removes it.
+    pub input_template: Option<String>,
+    /// Enabled when omitted
+    pub enabled: Option<bool>,
+}
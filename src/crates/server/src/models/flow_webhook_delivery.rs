@@ -0,0 +1,42 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "flow_webhook_deliveries")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    pub webhook_id: String,
+
+    /* Idempotency-Key header of the request */
+    pub idempotency_key: String,
+
+    /* Execution started by the delivery, null while it is being started */
+    #[schema(value_type = Option<String>)]
+    pub execution_id: Option<String>,
+
+    #[schema(value_type = Option<String>)]
+    pub received_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::models::flow_webhook::Entity",
+        from = "Column::WebhookId",
+        to = "crate::models::flow_webhook::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    FlowWebhook,
+}
+
+impl Related<crate::models::flow_webhook::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FlowWebhook.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,38 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "provider_limits")]
+pub struct Model {
+    /* Name of the environment variable holding the provider API key (e.g. "OPENAI_API_KEY") */
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub credential: String,
+
+    pub requests_per_minute: Option<i32>,
+
+    /* Estimated prompt tokens sent with the credential per minute */
+    pub tokens_per_minute: Option<i32>,
+
+    /* Behaviour once exhausted: "queue" waits for capacity, "reject" fails with 429 */
+    pub mode: String,
+
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub updated_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpsertProviderLimitPayload {
+    pub requests_per_minute: Option<i32>,
+    pub tokens_per_minute: Option<i32>,
+    /// "queue" (default) or "reject"
+    pub mode: Option<String>,
+}
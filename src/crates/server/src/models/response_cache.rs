@@ -0,0 +1,38 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "response_cache")]
+pub struct Model {
+    /* SHA-256 of the task id and the normalized payload */
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub key: String,
+
+    pub task_id: String,
+
+    pub response: serde_json::Value,
+
+    pub expires_at: DateTimeWithTimeZone,
+
+    pub created_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::models::agent_task::Entity",
+        from = "Column::TaskId",
+        to = "crate::models::agent_task::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    AgentTask,
+}
+
+impl Related<crate::models::agent_task::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AgentTask.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,61 @@
+use crate::models::session_message;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "sessions")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    pub name: Option<String>,
+
+    /* Token budget of the stored history, older messages are compacted beyond it */
+    pub max_tokens: Option<i32>,
+
+    /* Compaction policy: "truncate" drops old messages, "summarize" condenses them */
+    pub strategy: String,
+
+    /* Task used to condense old messages when the strategy is "summarize" */
+    pub summarizer_task_id: Option<String>,
+
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTimeWithTimeZone>,
+
+    #[schema(value_type = Option<String>)]
+    pub updated_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "crate::models::session_message::Entity")]
+    SessionMessage,
+}
+
+impl Related<crate::models::session_message::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SessionMessage.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateSessionPayload {
+    pub name: Option<String>,
+    /// Maximum number of tokens kept in the history (unlimited when omitted)
+    pub max_tokens: Option<i32>,
+    /// "truncate" (default) or "summarize"
+    pub strategy: Option<String>,
+    /// Task condensing old messages, required by the "summarize" strategy
+    pub summarizer_task_id: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SessionWithMessages {
+    #[serde(flatten)]
+    pub session: Model,
+    pub messages: Vec<session_message::Model>,
+    pub total_tokens: i32,
+}
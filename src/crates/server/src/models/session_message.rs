@@ -0,0 +1,46 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "session_messages")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: String,
+
+    pub session_id: String,
+
+    /* Position of the message inside the conversation */
+    pub position: i32,
+
+    /* "system", "user" or "assistant" */
+    pub role: String,
+
+    pub content: String,
+
+    /* Estimated token count of the content */
+    pub token_count: i32,
+
+    #[schema(value_type = Option<String>)]
+    pub created_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::models::session::Entity",
+        from = "Column::SessionId",
+        to = "crate::models::session::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Session,
+}
+
+impl Related<crate::models::session::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Session.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
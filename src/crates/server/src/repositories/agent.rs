@@ -15,6 +15,16 @@ impl Repository {
         Agent::find_by_id(id).one(db).await
     }
 
+    pub async fn find_by_slug(
+        db: &DatabaseConnection,
+        slug: String,
+    ) -> Result<Option<agent::Model>, DbErr> {
+        Agent::find()
+            .filter(agent::Column::Slug.eq(slug))
+            .one(db)
+            .await
+    }
+
     pub async fn create(
         db: &DatabaseConnection,
         data: agent::ActiveModel,
@@ -22,6 +32,22 @@ impl Repository {
         data.insert(db).await
     }
 
+    pub async fn update_rate_limit(
+        db: &DatabaseConnection,
+        id: String,
+        rate_limit: Option<serde_json::Value>,
+    ) -> Result<Option<agent::Model>, DbErr> {
+        let agent = Agent::find_by_id(id).one(db).await?;
+        if let Some(agent) = agent {
+            let mut active_agent: agent::ActiveModel = agent.into();
+            active_agent.rate_limit = Set(rate_limit);
+            let updated = active_agent.update(db).await?;
+            Ok(Some(updated))
+        } else {
+            Ok(None)
+        }
+    }
+
     pub async fn update_status(
         db: &DatabaseConnection,
         id: String,
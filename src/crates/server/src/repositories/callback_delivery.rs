@@ -0,0 +1,140 @@
+use crate::models::callback_delivery::{
+    self, Column, Entity as CallbackDelivery, DELIVERY_DEAD_LETTER, DELIVERY_DELIVERED,
+    DELIVERY_PENDING,
+};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+
+pub struct Repository;
+
+impl Repository {
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        data: callback_delivery::ActiveModel,
+    ) -> Result<callback_delivery::Model, DbErr> {
+        data.insert(db).await
+    }
+
+    /// Claims up to `limit` Pending deliveries whose attempt is due, postponing them by
+    /// `lease_secs` so that no other instance sends them meanwhile.
+    pub async fn claim_due(
+        db: &DatabaseConnection,
+        limit: u64,
+        lease_secs: i64,
+    ) -> Result<Vec<callback_delivery::Model>, DbErr> {
+        let sql = r#"
+            UPDATE callback_deliveries
+            SET next_attempt_at = now() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM callback_deliveries
+                WHERE status = 'Pending' AND next_attempt_at <= now()
+                ORDER BY next_attempt_at
+                FOR UPDATE SKIP LOCKED
+                LIMIT $1
+            )
+            RETURNING *"#;
+        CallbackDelivery::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                [(limit as i64).into(), (lease_secs as f64).into()],
+            ))
+            .all(db)
+            .await
+    }
+
+    pub async fn mark_delivered(
+        db: &DatabaseConnection,
+        id: String,
+        attempts: i32,
+        response_status: i32,
+    ) -> Result<(), DbErr> {
+        CallbackDelivery::update_many()
+            .col_expr(Column::Status, Expr::value(DELIVERY_DELIVERED))
+            .col_expr(Column::Attempts, Expr::value(attempts))
+            .col_expr(Column::ResponseStatus, Expr::value(response_status))
+            .col_expr(Column::LastError, Expr::value(Option::<String>::None))
+            .col_expr(Column::DeliveredAt, Expr::current_timestamp().into())
+            .filter(Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Records a failed attempt, scheduling the next one at `next_attempt_at`, or moving
+    /// the delivery to the dead letters without one.
+    pub async fn mark_failed(
+        db: &DatabaseConnection,
+        id: String,
+        attempts: i32,
+        response_status: Option<i32>,
+        error: String,
+        next_attempt_at: Option<DateTimeWithTimeZone>,
+    ) -> Result<(), DbErr> {
+        let status = if next_attempt_at.is_some() {
+            DELIVERY_PENDING
+        } else {
+            DELIVERY_DEAD_LETTER
+        };
+        CallbackDelivery::update_many()
+            .col_expr(Column::Status, Expr::value(status))
+            .col_expr(Column::Attempts, Expr::value(attempts))
+            .col_expr(Column::ResponseStatus, Expr::value(response_status))
+            .col_expr(Column::LastError, Expr::value(error))
+            .col_expr(Column::NextAttemptAt, Expr::value(next_attempt_at))
+            .filter(Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<callback_delivery::Model>, DbErr> {
+        CallbackDelivery::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_dead_letters(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<callback_delivery::Model>, DbErr> {
+        CallbackDelivery::find()
+            .filter(Column::Status.eq(DELIVERY_DEAD_LETTER))
+            .order_by_desc(Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_by_execution(
+        db: &DatabaseConnection,
+        execution_id: String,
+    ) -> Result<Vec<callback_delivery::Model>, DbErr> {
+        CallbackDelivery::find()
+            .filter(Column::ExecutionId.eq(execution_id))
+            .order_by_asc(Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    /// Queues a dead-lettered delivery again with a fresh set of attempts.
+    /// Returns `None` when the delivery is not dead-lettered.
+    pub async fn requeue(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<callback_delivery::Model>, DbErr> {
+        let sql = r#"
+            UPDATE callback_deliveries
+            SET status = 'Pending', attempts = 0, next_attempt_at = now()
+            WHERE id = $1 AND status = 'DeadLetter'
+            RETURNING *"#;
+        CallbackDelivery::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                [id.into()],
+            ))
+            .one(db)
+            .await
+    }
+}
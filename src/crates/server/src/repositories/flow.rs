@@ -32,4 +32,26 @@ impl Repository {
     ) -> Result<Option<flow::Model>, DbErr> {
         Flow::find_by_id(id).one(db).await
     }
+
+    pub async fn update(
+        db: &DatabaseConnection,
+        data: flow::ActiveModel,
+    ) -> Result<flow::Model, DbErr> {
+        data.update(db).await
+    }
+
+    pub async fn delete(db: &DatabaseConnection, id: String) -> Result<u64, DbErr> {
+        let result = Flow::delete_by_id(id).exec(db).await?;
+        Ok(result.rows_affected)
+    }
+
+    pub async fn find_by_name(
+        db: &DatabaseConnection,
+        name: String,
+    ) -> Result<Vec<flow::Model>, DbErr> {
+        Flow::find()
+            .filter(flow::Column::Name.eq(name))
+            .all(db)
+            .await
+    }
 }
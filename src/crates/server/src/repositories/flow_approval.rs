@@ -0,0 +1,144 @@
+use crate::models::flow_approval::{
+    self, Entity as FlowApproval, APPROVAL_CANCELLED, APPROVAL_PENDING,
+};
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use uuid::Uuid;
+
+pub struct Repository;
+
+impl Repository {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create(
+        db: &DatabaseConnection,
+        execution_id: String,
+        flow_id: String,
+        step_id: String,
+        step_name: String,
+        step_execution_id: Option<String>,
+        message: Option<String>,
+        data: serde_json::Value,
+        on_timeout: String,
+        expires_at: Option<prelude::DateTimeWithTimeZone>,
+    ) -> Result<flow_approval::Model, DbErr> {
+        let approval = flow_approval::ActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            execution_id: Set(execution_id),
+            flow_id: Set(flow_id),
+            step_id: Set(step_id),
+            step_name: Set(step_name),
+            step_execution_id: Set(step_execution_id),
+            status: Set(APPROVAL_PENDING.to_string()),
+            message: Set(message),
+            data: Set(Some(data)),
+            response: Set(None),
+            reviewer: Set(None),
+            comment: Set(None),
+            on_timeout: Set(on_timeout),
+            expires_at: Set(expires_at),
+            created_at: Set(Some(chrono::Utc::now().into())),
+            decided_at: Set(None),
+        };
+        approval.insert(db).await
+    }
+
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_approval::Model>, DbErr> {
+        FlowApproval::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_by_ids(
+        db: &DatabaseConnection,
+        ids: Vec<String>,
+    ) -> Result<Vec<flow_approval::Model>, DbErr> {
+        FlowApproval::find()
+            .filter(flow_approval::Column::Id.is_in(ids))
+            .all(db)
+            .await
+    }
+
+    /// Lists the approvals waiting for a reviewer, oldest first.
+    pub async fn find_pending(db: &DatabaseConnection) -> Result<Vec<flow_approval::Model>, DbErr> {
+        FlowApproval::find()
+            .filter(flow_approval::Column::Status.eq(APPROVAL_PENDING))
+            .order_by_asc(flow_approval::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    /// Lists the Pending approvals whose timeout is reached.
+    pub async fn find_expired(db: &DatabaseConnection) -> Result<Vec<flow_approval::Model>, DbErr> {
+        FlowApproval::find()
+            .filter(flow_approval::Column::Status.eq(APPROVAL_PENDING))
+            .filter(flow_approval::Column::ExpiresAt.lt(chrono::Utc::now()))
+            .all(db)
+            .await
+    }
+
+    pub async fn count_pending(
+        db: &DatabaseConnection,
+        execution_id: String,
+    ) -> Result<u64, DbErr> {
+        FlowApproval::find()
+            .filter(flow_approval::Column::ExecutionId.eq(execution_id))
+            .filter(flow_approval::Column::Status.eq(APPROVAL_PENDING))
+            .count(db)
+            .await
+    }
+
+    /// Settles a Pending approval. Returns `None` when the approval does not exist or
+    /// was already decided, so that concurrent decisions (or a timeout) apply only once.
+    pub async fn decide(
+        db: &DatabaseConnection,
+        id: String,
+        status: &str,
+        response: Option<serde_json::Value>,
+        reviewer: Option<String>,
+        comment: Option<String>,
+    ) -> Result<Option<flow_approval::Model>, DbErr> {
+        let sql = r#"
+            UPDATE flow_approvals
+            SET status = $2, response = $3, reviewer = $4, comment = $5, decided_at = now()
+            WHERE id = $1 AND status = 'Pending'
+            RETURNING *"#;
+        FlowApproval::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                [
+                    id.into(),
+                    status.into(),
+                    response.into(),
+                    reviewer.into(),
+                    comment.into(),
+                ],
+            ))
+            .one(db)
+            .await
+    }
+
+    /// Cancels the Pending approvals of an execution that stopped.
+    pub async fn cancel_pending(
+        db: &DatabaseConnection,
+        execution_id: String,
+        comment: &str,
+    ) -> Result<u64, DbErr> {
+        let result = FlowApproval::update_many()
+            .col_expr(
+                flow_approval::Column::Status,
+                Expr::value(APPROVAL_CANCELLED),
+            )
+            .col_expr(flow_approval::Column::Comment, Expr::value(comment))
+            .col_expr(
+                flow_approval::Column::DecidedAt,
+                Expr::current_timestamp().into(),
+            )
+            .filter(flow_approval::Column::ExecutionId.eq(execution_id))
+            .filter(flow_approval::Column::Status.eq(APPROVAL_PENDING))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+}
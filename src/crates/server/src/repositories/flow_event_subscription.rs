@@ -0,0 +1,44 @@
+use crate::models::flow_event_subscription::{self, Column, Entity as FlowEventSubscription};
+use sea_orm::*;
+
+pub struct Repository;
+
+impl Repository {
+    pub async fn create(
+        db: &DatabaseConnection,
+        data: flow_event_subscription::ActiveModel,
+    ) -> Result<flow_event_subscription::Model, DbErr> {
+        data.insert(db).await
+    }
+
+    pub async fn find_by_flow(
+        db: &DatabaseConnection,
+        flow_id: String,
+    ) -> Result<Vec<flow_event_subscription::Model>, DbErr> {
+        FlowEventSubscription::find()
+            .filter(Column::FlowId.eq(flow_id))
+            .order_by_asc(Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_event_subscription::Model>, DbErr> {
+        FlowEventSubscription::find_by_id(id).one(db).await
+    }
+
+    pub async fn delete(
+        db: &DatabaseConnection,
+        flow_id: String,
+        id: String,
+    ) -> Result<u64, DbErr> {
+        let result = FlowEventSubscription::delete_many()
+            .filter(Column::FlowId.eq(flow_id))
+            .filter(Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+}
@@ -1,4 +1,5 @@
 use crate::models::flow_execution::{self, Entity as FlowExecution};
+use sea_orm::sea_query::Expr;
 use sea_orm::*;
 use uuid::Uuid;
 
@@ -8,20 +9,131 @@ impl Repository {
     pub async fn create(
         db: &DatabaseConnection,
         flow_id: String,
+        version_id: Option<String>,
         input_data: Option<serde_json::Value>,
+        session_id: Option<String>,
+        status: &str,
+        callback_url: Option<String>,
     ) -> Result<flow_execution::Model, DbErr> {
         let execution = flow_execution::ActiveModel {
             id: Set(Uuid::new_v4().to_string()),
             flow_id: Set(flow_id),
-            status: Set("Running".to_string()),
+            version_id: Set(version_id),
+            status: Set(status.to_string()),
             input_data: Set(input_data),
             output_data: Set(None),
+            branches: Set(None),
+            steps: Set(None),
+            session_id: Set(session_id),
+            claimed_by: Set(None),
+            heartbeat_at: Set(Some(chrono::Utc::now().into())),
+            attempts: Set(0),
+            cancel_requested: Set(false),
+            parent_execution_id: Set(None),
+            parent_step_id: Set(None),
+            depth: Set(0),
+            callback_url: Set(callback_url),
+            notified_at: Set(None),
             started_at: Set(None), // DB handles default
             completed_at: Set(None),
         };
         execution.insert(db).await
     }
 
+    /// Creates the Running execution of a sub-flow, run by `parent_step_id` of its parent.
+    pub async fn create_child(
+        db: &DatabaseConnection,
+        flow_id: String,
+        version_id: Option<String>,
+        input_data: Option<serde_json::Value>,
+        parent_execution_id: String,
+        parent_step_id: String,
+        depth: i32,
+    ) -> Result<flow_execution::Model, DbErr> {
+        let execution = flow_execution::ActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            flow_id: Set(flow_id),
+            version_id: Set(version_id),
+            status: Set("Running".to_string()),
+            input_data: Set(input_data),
+            output_data: Set(None),
+            branches: Set(None),
+            steps: Set(None),
+            session_id: Set(None),
+            claimed_by: Set(None),
+            heartbeat_at: Set(Some(chrono::Utc::now().into())),
+            attempts: Set(0),
+            cancel_requested: Set(false),
+            parent_execution_id: Set(Some(parent_execution_id)),
+            parent_step_id: Set(Some(parent_step_id)),
+            depth: Set(depth),
+            callback_url: Set(None),
+            notified_at: Set(None),
+            started_at: Set(None),
+            completed_at: Set(None),
+        };
+        execution.insert(db).await
+    }
+
+    /// Lists the sub-flow executions started by an execution, oldest first.
+    pub async fn find_children(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Vec<flow_execution::Model>, DbErr> {
+        FlowExecution::find()
+            .filter(flow_execution::Column::ParentExecutionId.eq(id))
+            .order_by_asc(flow_execution::Column::StartedAt)
+            .all(db)
+            .await
+    }
+
+    /// Cancels the sub-flow executions (at any depth) still Pending or Running below an
+    /// execution that stopped, since nothing runs them anymore.
+    pub async fn cancel_children(
+        db: &DatabaseConnection,
+        id: String,
+        reason: &str,
+    ) -> Result<u64, DbErr> {
+        let sql = r#"
+            WITH RECURSIVE tree AS (
+                SELECT id FROM flow_executions WHERE parent_execution_id = $1
+                UNION ALL
+                SELECT child.id FROM flow_executions child JOIN tree ON child.parent_execution_id = tree.id
+            )
+            UPDATE flow_executions
+            SET status = 'Cancelled', claimed_by = NULL, completed_at = now(),
+                output_data = jsonb_build_object('error', $2::text)
+            WHERE id IN (SELECT id FROM tree) AND status IN ('Pending', 'Running')"#;
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            sql,
+            [id.into(), reason.into()],
+        ))
+        .await
+        .map(|result| result.rows_affected())
+    }
+
+    /// Whether executions of the flow are still Pending, Running or WaitingForApproval.
+    pub async fn has_active(db: &DatabaseConnection, flow_id: String) -> Result<bool, DbErr> {
+        let count = FlowExecution::find()
+            .filter(flow_execution::Column::FlowId.eq(flow_id))
+            .filter(flow_execution::Column::Status.is_in([
+                "Pending",
+                "Running",
+                "WaitingForApproval",
+            ]))
+            .count(db)
+            .await?;
+        Ok(count > 0)
+    }
+
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_execution::Model>, DbErr> {
+        FlowExecution::find_by_id(id).one(db).await
+    }
+
     pub async fn update_status(
         db: &DatabaseConnection,
         id: String,
@@ -36,8 +148,14 @@ impl Repository {
                 active_exec.output_data = Set(output_data);
             }
             // If status is terminal, set completed_at
-            if status == "Completed" || status == "Failed" {
+            if status == "Completed" || status == "Failed" || status == "Cancelled" {
                 active_exec.completed_at = Set(Some(chrono::Utc::now().into()));
+            } else {
+                active_exec.completed_at = Set(None);
+            }
+            // A requeued execution starts again without the cancellation of its last run
+            if status == "Pending" {
+                active_exec.cancel_requested = Set(false);
             }
             let updated = active_exec.update(db).await?;
             Ok(Some(updated))
@@ -45,4 +163,285 @@ impl Repository {
             Ok(None)
         }
     }
+
+    /// Moves an execution from status `from` to `status`, telling whether it was in `from`.
+    /// Like `update_status`, moving to Pending drops the cancel request of the last run.
+    pub async fn transition<C: ConnectionTrait>(
+        db: &C,
+        id: String,
+        from: &str,
+        status: &str,
+        output_data: Option<serde_json::Value>,
+    ) -> Result<bool, DbErr> {
+        let terminal = status == "Completed" || status == "Failed" || status == "Cancelled";
+        let mut update = FlowExecution::update_many()
+            .col_expr(flow_execution::Column::Status, Expr::value(status))
+            .col_expr(
+                flow_execution::Column::CompletedAt,
+                if terminal {
+                    Expr::current_timestamp().into()
+                } else {
+                    Expr::value(Option::<prelude::DateTimeWithTimeZone>::None)
+                },
+            );
+        if let Some(output_data) = output_data {
+            update = update.col_expr(flow_execution::Column::OutputData, Expr::value(output_data));
+        }
+        if status == "Pending" {
+            update = update.col_expr(flow_execution::Column::CancelRequested, Expr::value(false));
+        }
+        let result = update
+            .filter(flow_execution::Column::Id.eq(id))
+            .filter(flow_execution::Column::Status.eq(from))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    pub async fn update_branches(
+        db: &DatabaseConnection,
+        id: String,
+        branches: serde_json::Value,
+    ) -> Result<(), DbErr> {
+        let exec = FlowExecution::find_by_id(id).one(db).await?;
+        if let Some(exec) = exec {
+            let mut active_exec: flow_execution::ActiveModel = exec.into();
+            active_exec.branches = Set(Some(branches));
+            active_exec.update(db).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn update_steps(
+        db: &DatabaseConnection,
+        id: String,
+        steps: serde_json::Value,
+    ) -> Result<(), DbErr> {
+        let exec = FlowExecution::find_by_id(id).one(db).await?;
+        if let Some(exec) = exec {
+            let mut active_exec: flow_execution::ActiveModel = exec.into();
+            active_exec.steps = Set(Some(steps));
+            active_exec.update(db).await?;
+        }
+        Ok(())
+    }
+
+    /// Claims the oldest Pending execution for `worker_id`. `SKIP LOCKED` lets several
+    /// workers (and gateway instances) poll the queue without claiming the same row.
+    pub async fn claim_next(
+        db: &DatabaseConnection,
+        worker_id: &str,
+    ) -> Result<Option<flow_execution::Model>, DbErr> {
+        let sql = r#"
+            UPDATE flow_executions
+            SET status = 'Running', claimed_by = $1, heartbeat_at = now(), attempts = attempts + 1
+            WHERE id = (
+                SELECT id FROM flow_executions
+                WHERE status = 'Pending'
+                ORDER BY started_at
+                FOR UPDATE SKIP LOCKED
+                LIMIT 1
+            )
+            RETURNING *"#;
+        FlowExecution::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                [worker_id.into()],
+            ))
+            .one(db)
+            .await
+    }
+
+    /// Refreshes the heartbeat of a Running execution and tells whether its
+    /// cancellation was requested.
+    pub async fn heartbeat(db: &DatabaseConnection, id: String) -> Result<bool, DbErr> {
+        let row = db
+            .query_one(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "UPDATE flow_executions SET heartbeat_at = now()
+                WHERE id = $1 AND status = 'Running'
+                RETURNING cancel_requested",
+                [id.into()],
+            ))
+            .await?;
+        match row {
+            Some(row) => row.try_get("", "cancel_requested"),
+            None => Ok(false),
+        }
+    }
+
+    /// Requests the cancellation of a Pending, Running or WaitingForApproval execution.
+    /// Executions no worker runs are cancelled right away, Running ones once their worker
+    /// notices the request.
+    /// Returns `None` when the execution does not exist or already settled.
+    pub async fn request_cancel(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_execution::Model>, DbErr> {
+        // Compare-and-set on the status read, read again when a worker changed it meanwhile
+        loop {
+            let execution = match FlowExecution::find_by_id(id.clone()).one(db).await? {
+                Some(execution) => execution,
+                None => return Ok(None),
+            };
+            let (status, error) = match Self::cancel_outcome(&execution.status) {
+                Some(outcome) => outcome,
+                None => return Ok(None),
+            };
+
+            let mut update = FlowExecution::update_many()
+                .col_expr(flow_execution::Column::CancelRequested, Expr::value(true))
+                .col_expr(flow_execution::Column::Status, Expr::value(status));
+            if status == "Cancelled" {
+                update = update.col_expr(
+                    flow_execution::Column::CompletedAt,
+                    Expr::current_timestamp().into(),
+                );
+            }
+            if let Some(error) = error {
+                update = update.col_expr(
+                    flow_execution::Column::OutputData,
+                    Expr::value(serde_json::json!({ "error": error })),
+                );
+            }
+            let updated = update
+                .filter(flow_execution::Column::Id.eq(id.clone()))
+                .filter(flow_execution::Column::Status.eq(execution.status))
+                .exec_with_returning(db)
+                .await?;
+            if let Some(execution) = updated.into_iter().next() {
+                return Ok(Some(execution));
+            }
+        }
+    }
+
+    /// Status taken by an execution in `status` on a cancel request, with the error stored
+    /// as its output. Running executions keep running until their worker notices the
+    /// request, settled ones cannot be cancelled (`None`).
+    pub fn cancel_outcome(status: &str) -> Option<(&'static str, Option<&'static str>)> {
+        match status {
+            "Pending" => Some(("Cancelled", Some("Cancelled before start"))),
+            "WaitingForApproval" => {
+                Some(("Cancelled", Some("Cancelled while waiting for approval")))
+            }
+            "Running" => Some(("Running", None)),
+            _ => None,
+        }
+    }
+
+    /// Requeues Running executions whose heartbeat is older than `stale_secs` (their
+    /// worker died), failing those already claimed `max_attempts` times. Sub-flow
+    /// executions are failed instead, their parent runs them again once resumed, and
+    /// executions whose cancellation was requested are cancelled.
+    /// Returns the number of executions requeued and settled.
+    pub async fn recover_stale(
+        db: &DatabaseConnection,
+        stale_secs: i64,
+        max_attempts: i32,
+    ) -> Result<(u64, u64), DbErr> {
+        let stale = r#"
+            status = 'Running'
+            AND (heartbeat_at IS NULL OR heartbeat_at < now() - make_interval(secs => $1))"#;
+
+        let cancelled = db
+            .execute(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                format!(
+                    "UPDATE flow_executions
+                    SET status = 'Cancelled', claimed_by = NULL, completed_at = now(),
+                        output_data = jsonb_build_object('error', 'Cancelled while running')
+                    WHERE {} AND cancel_requested",
+                    stale
+                ),
+                [(stale_secs as f64).into()],
+            ))
+            .await?
+            .rows_affected();
+
+        let failed = db
+            .execute(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                format!(
+                    "UPDATE flow_executions
+                    SET status = 'Failed', claimed_by = NULL, completed_at = now(),
+                        output_data = jsonb_build_object('error', CASE
+                            WHEN parent_execution_id IS NULL THEN 'Abandoned after ' || attempts || ' attempts'
+                            ELSE 'Interrupted with its parent execution' END)
+                    WHERE {} AND (attempts >= $2 OR parent_execution_id IS NOT NULL)",
+                    stale
+                ),
+                [(stale_secs as f64).into(), max_attempts.into()],
+            ))
+            .await?
+            .rows_affected();
+
+        let requeued = db
+            .execute(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                format!(
+                    "UPDATE flow_executions SET status = 'Pending', claimed_by = NULL
+                    WHERE {} AND parent_execution_id IS NULL",
+                    stale
+                ),
+                [(stale_secs as f64).into()],
+            ))
+            .await?
+            .rows_affected();
+
+        Ok((requeued, failed + cancelled))
+    }
+
+    /// Claims up to `limit` top-level executions settled since their events were last
+    /// queued. An execution resumed and settled again is claimed again.
+    pub async fn claim_unnotified<C: ConnectionTrait>(
+        db: &C,
+        limit: u64,
+    ) -> Result<Vec<flow_execution::Model>, DbErr> {
+        let sql = r#"
+            UPDATE flow_executions SET notified_at = now()
+            WHERE id IN (
+                SELECT id FROM flow_executions
+                WHERE status IN ('Completed', 'Failed', 'Cancelled')
+                    AND parent_execution_id IS NULL
+                    AND (notified_at IS NULL OR notified_at < completed_at)
+                ORDER BY completed_at
+                FOR UPDATE SKIP LOCKED
+                LIMIT $1
+            )
+            RETURNING *"#;
+        FlowExecution::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                [(limit as i64).into()],
+            ))
+            .all(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancels_executions_no_worker_runs() {
+        assert_eq!(
+            Repository::cancel_outcome("Pending"),
+            Some(("Cancelled", Some("Cancelled before start")))
+        );
+        assert_eq!(
+            Repository::cancel_outcome("WaitingForApproval"),
+            Some(("Cancelled", Some("Cancelled while waiting for approval")))
+        );
+        // The worker settles the execution, keeping the output of its last step
+        assert_eq!(
+            Repository::cancel_outcome("Running"),
+            Some(("Running", None))
+        );
+        for settled in ["Completed", "Failed", "Cancelled"] {
+            assert_eq!(Repository::cancel_outcome(settled), None);
+        }
+    }
 }
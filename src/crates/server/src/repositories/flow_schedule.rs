@@ -0,0 +1,72 @@
+use crate::models::flow_schedule::{self, Column, Entity as FlowSchedule};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+
+pub struct Repository;
+
+impl Repository {
+    pub async fn create(
+        db: &DatabaseConnection,
+        data: flow_schedule::ActiveModel,
+    ) -> Result<flow_schedule::Model, DbErr> {
+        data.insert(db).await
+    }
+
+    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<flow_schedule::Model>, DbErr> {
+        FlowSchedule::find()
+            .order_by_asc(Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_schedule::Model>, DbErr> {
+        FlowSchedule::find_by_id(id).one(db).await
+    }
+
+    pub async fn update(
+        db: &DatabaseConnection,
+        data: flow_schedule::ActiveModel,
+    ) -> Result<flow_schedule::Model, DbErr> {
+        data.update(db).await
+    }
+
+    pub async fn delete(db: &DatabaseConnection, id: String) -> Result<u64, DbErr> {
+        let result = FlowSchedule::delete_by_id(id).exec(db).await?;
+        Ok(result.rows_affected)
+    }
+
+    /// Lists the enabled schedules whose next occurrence is reached.
+    pub async fn find_due(
+        db: &DatabaseConnection,
+        now: DateTimeWithTimeZone,
+    ) -> Result<Vec<flow_schedule::Model>, DbErr> {
+        FlowSchedule::find()
+            .filter(Column::Enabled.eq(true))
+            .filter(Column::NextRunAt.lte(now))
+            .order_by_asc(Column::NextRunAt)
+            .all(db)
+            .await
+    }
+
+    /// Moves a schedule from its occurrence `scheduled_for` to the next one. Tells whether
+    /// this call claimed the occurrence, so that a single gateway instance triggers it.
+    pub async fn advance(
+        db: &DatabaseConnection,
+        id: String,
+        scheduled_for: DateTimeWithTimeZone,
+        next_run_at: Option<DateTimeWithTimeZone>,
+    ) -> Result<bool, DbErr> {
+        let result = FlowSchedule::update_many()
+            .col_expr(Column::NextRunAt, Expr::value(next_run_at))
+            .col_expr(Column::LastRunAt, Expr::current_timestamp().into())
+            .filter(Column::Id.eq(id))
+            .filter(Column::NextRunAt.eq(scheduled_for))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+}
@@ -0,0 +1,64 @@
+use crate::models::flow_execution::{self, Entity as FlowExecution};
+use crate::models::flow_schedule_run::{self, Column, Entity as FlowScheduleRun};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::*;
+use uuid::Uuid;
+
+pub struct Repository;
+
+impl Repository {
+    pub async fn create(
+        db: &DatabaseConnection,
+        schedule_id: String,
+        execution_id: Option<String>,
+        status: &str,
+        scheduled_for: Option<DateTimeWithTimeZone>,
+        error: Option<String>,
+    ) -> Result<flow_schedule_run::Model, DbErr> {
+        let run = flow_schedule_run::ActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            schedule_id: Set(schedule_id),
+            execution_id: Set(execution_id),
+            status: Set(status.to_string()),
+            scheduled_for: Set(scheduled_for),
+            triggered_at: Set(Some(chrono::Utc::now().into())),
+            error: Set(error),
+        };
+        run.insert(db).await
+    }
+
+    /// Lists the latest runs of a schedule, most recent first.
+    pub async fn find_by_schedule(
+        db: &DatabaseConnection,
+        schedule_id: String,
+        limit: u64,
+    ) -> Result<Vec<flow_schedule_run::Model>, DbErr> {
+        FlowScheduleRun::find()
+            .filter(Column::ScheduleId.eq(schedule_id))
+            .order_by_desc(Column::TriggeredAt)
+            .limit(limit)
+            .all(db)
+            .await
+    }
+
+    /// Latest execution started by a schedule that did not settle yet.
+    pub async fn find_active_execution(
+        db: &DatabaseConnection,
+        schedule_id: String,
+    ) -> Result<Option<flow_execution::Model>, DbErr> {
+        let sql = r#"
+            SELECT e.* FROM flow_executions e
+            JOIN flow_schedule_runs r ON r.execution_id = e.id
+            WHERE r.schedule_id = $1 AND e.status IN ('Pending', 'Running', 'WaitingForApproval')
+            ORDER BY r.triggered_at DESC
+            LIMIT 1"#;
+        FlowExecution::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                [schedule_id.into()],
+            ))
+            .one(db)
+            .await
+    }
+}
@@ -1,22 +1,30 @@
 use crate::models::flow_step::{self, Column, Entity as FlowStep};
+use sea_orm::sea_query::Expr;
 use sea_orm::{QueryOrder, *};
 
 pub struct Repository;
 
 impl Repository {
+    #[allow(clippy::too_many_arguments)]
     pub async fn create(
         db: &DatabaseConnection,
         flow_id: String,
-        task_id: String,
+        task_id: Option<String>,
+        step_type: String,
         step_order: i32,
         config: Option<serde_json::Value>,
+        name: Option<String>,
+        depends_on: Option<serde_json::Value>,
     ) -> Result<flow_step::Model, DbErr> {
         let step = flow_step::ActiveModel {
             id: Set(uuid::Uuid::new_v4().to_string()),
             flow_id: Set(flow_id),
             task_id: Set(task_id),
+            step_type: Set(step_type),
             step_order: Set(step_order),
             config: Set(config),
+            name: Set(name),
+            depends_on: Set(depends_on),
             created_at: Set(None),
         };
         step.insert(db).await
@@ -29,6 +37,55 @@ impl Repository {
         FlowStep::find()
             .filter(Column::FlowId.eq(flow_id))
             .order_by_asc(Column::StepOrder)
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await
+    }
+
+    pub async fn update(
+        db: &DatabaseConnection,
+        data: flow_step::ActiveModel,
+    ) -> Result<flow_step::Model, DbErr> {
+        data.update(db).await
+    }
+
+    pub async fn delete(db: &DatabaseConnection, id: String) -> Result<u64, DbErr> {
+        let result = FlowStep::delete_by_id(id).exec(db).await?;
+        Ok(result.rows_affected)
+    }
+
+    /// Sets the `step_order` of every step at once, from 1 in the order of `step_ids`.
+    pub async fn reorder(
+        db: &DatabaseConnection,
+        flow_id: String,
+        step_ids: Vec<String>,
+    ) -> Result<(), DbErr> {
+        let txn = db.begin().await?;
+        for (i, id) in step_ids.into_iter().enumerate() {
+            FlowStep::update_many()
+                .col_expr(Column::StepOrder, Expr::value(i as i32 + 1))
+                .filter(Column::Id.eq(id))
+                .filter(Column::FlowId.eq(flow_id.clone()))
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await
+    }
+
+    /// Steps of other flows running `flow_id` as a sub-flow.
+    pub async fn find_sub_flow_callers(
+        db: &DatabaseConnection,
+        flow_id: String,
+    ) -> Result<Vec<flow_step::Model>, DbErr> {
+        let sql = r#"
+            SELECT * FROM flow_steps
+            WHERE step_type IN ('flow', 'map') AND config->>'flow_id' = $1 AND flow_id <> $1"#;
+        FlowStep::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                [flow_id.into()],
+            ))
             .all(db)
             .await
     }
@@ -0,0 +1,143 @@
+use crate::models::flow_step_execution::{self, Entity as FlowStepExecution};
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use uuid::Uuid;
+
+pub struct Repository;
+
+impl Repository {
+    pub async fn create(
+        db: &DatabaseConnection,
+        execution_id: String,
+        step_id: String,
+        step_name: String,
+        status: &str,
+        payload: Option<serde_json::Value>,
+    ) -> Result<flow_step_execution::Model, DbErr> {
+        let record = flow_step_execution::ActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            execution_id: Set(execution_id),
+            step_id: Set(step_id),
+            step_name: Set(step_name),
+            status: Set(status.to_string()),
+            payload: Set(payload),
+            response: Set(None),
+            error: Set(None),
+            retries: Set(0),
+            agent_log_id: Set(None),
+            recovery: Set(None),
+            started_at: Set(Some(chrono::Utc::now().into())),
+            completed_at: Set(None),
+            duration_ms: Set(None),
+        };
+        record.insert(db).await
+    }
+
+    /// Stores the outcome of a step and its duration since `started_at`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn finish(
+        db: &DatabaseConnection,
+        record: flow_step_execution::Model,
+        status: &str,
+        response: Option<serde_json::Value>,
+        error: Option<String>,
+        retries: i32,
+        agent_log_id: Option<String>,
+        recovery: Option<serde_json::Value>,
+    ) -> Result<flow_step_execution::Model, DbErr> {
+        let now = chrono::Utc::now();
+        let duration_ms = record
+            .started_at
+            .map(|started| (now - started.with_timezone(&chrono::Utc)).num_milliseconds());
+
+        let mut active: flow_step_execution::ActiveModel = record.into();
+        active.status = Set(status.to_string());
+        active.response = Set(response);
+        active.error = Set(error);
+        active.retries = Set(retries);
+        active.agent_log_id = Set(agent_log_id);
+        active.recovery = Set(recovery);
+        active.completed_at = Set(Some(now.into()));
+        active.duration_ms = Set(duration_ms);
+        active.update(db).await
+    }
+
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_step_execution::Model>, DbErr> {
+        FlowStepExecution::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_by_execution(
+        db: &DatabaseConnection,
+        execution_id: String,
+    ) -> Result<Vec<flow_step_execution::Model>, DbErr> {
+        FlowStepExecution::find()
+            .filter(flow_step_execution::Column::ExecutionId.eq(execution_id))
+            .order_by_asc(flow_step_execution::Column::StartedAt)
+            .all(db)
+            .await
+    }
+
+    /// Fails the records left Running by a run that never finished (its worker died).
+    pub async fn fail_running(
+        db: &DatabaseConnection,
+        execution_id: String,
+        error: &str,
+    ) -> Result<u64, DbErr> {
+        let result = FlowStepExecution::update_many()
+            .col_expr(flow_step_execution::Column::Status, Expr::value("Failed"))
+            .col_expr(flow_step_execution::Column::Error, Expr::value(error))
+            .col_expr(
+                flow_step_execution::Column::CompletedAt,
+                Expr::current_timestamp().into(),
+            )
+            .filter(flow_step_execution::Column::ExecutionId.eq(execution_id))
+            .filter(flow_step_execution::Column::Status.eq("Running"))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// Cancels the records of the steps still waiting for approval in a cancelled execution.
+    pub async fn cancel_waiting(
+        db: &DatabaseConnection,
+        execution_id: String,
+    ) -> Result<u64, DbErr> {
+        let result = FlowStepExecution::update_many()
+            .col_expr(
+                flow_step_execution::Column::Status,
+                Expr::value("Cancelled"),
+            )
+            .col_expr(
+                flow_step_execution::Column::CompletedAt,
+                Expr::current_timestamp().into(),
+            )
+            .filter(flow_step_execution::Column::ExecutionId.eq(execution_id))
+            .filter(flow_step_execution::Column::Status.eq("WaitingForApproval"))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// Marks the Completed records of the given steps as Superseded, so that the next run
+    /// of the execution does not reuse their output.
+    pub async fn supersede<C: ConnectionTrait>(
+        db: &C,
+        execution_id: String,
+        step_ids: Vec<String>,
+    ) -> Result<u64, DbErr> {
+        let result = FlowStepExecution::update_many()
+            .col_expr(
+                flow_step_execution::Column::Status,
+                Expr::value("Superseded"),
+            )
+            .filter(flow_step_execution::Column::ExecutionId.eq(execution_id))
+            .filter(flow_step_execution::Column::StepId.is_in(step_ids))
+            .filter(flow_step_execution::Column::Status.eq("Completed"))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+}
@@ -0,0 +1,77 @@
+use crate::models::flow_version::{self, Column, Entity as FlowVersion};
+use sea_orm::*;
+use uuid::Uuid;
+
+pub struct Repository;
+
+impl Repository {
+    /// Stores the next version of a flow, numbered after its latest one.
+    pub async fn create_next(
+        db: &DatabaseConnection,
+        flow_id: String,
+        steps: serde_json::Value,
+        notes: Option<String>,
+    ) -> Result<flow_version::Model, DbErr> {
+        let sql = r#"
+            INSERT INTO flow_versions (id, flow_id, version, steps, notes)
+            SELECT $1, $2, COALESCE(MAX(version), 0) + 1, $3, $4
+            FROM flow_versions WHERE flow_id = $2
+            RETURNING *"#;
+        FlowVersion::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                [
+                    Uuid::new_v4().to_string().into(),
+                    flow_id.into(),
+                    steps.into(),
+                    notes.into(),
+                ],
+            ))
+            .one(db)
+            .await?
+            .ok_or_else(|| DbErr::RecordNotInserted)
+    }
+
+    /// Lists the versions of a flow, latest first.
+    pub async fn find_by_flow(
+        db: &DatabaseConnection,
+        flow_id: String,
+    ) -> Result<Vec<flow_version::Model>, DbErr> {
+        FlowVersion::find()
+            .filter(Column::FlowId.eq(flow_id))
+            .order_by_desc(Column::Version)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_version::Model>, DbErr> {
+        FlowVersion::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_by_number(
+        db: &DatabaseConnection,
+        flow_id: String,
+        version: i32,
+    ) -> Result<Option<flow_version::Model>, DbErr> {
+        FlowVersion::find()
+            .filter(Column::FlowId.eq(flow_id))
+            .filter(Column::Version.eq(version))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_latest(
+        db: &DatabaseConnection,
+        flow_id: String,
+    ) -> Result<Option<flow_version::Model>, DbErr> {
+        FlowVersion::find()
+            .filter(Column::FlowId.eq(flow_id))
+            .order_by_desc(Column::Version)
+            .one(db)
+            .await
+    }
+}
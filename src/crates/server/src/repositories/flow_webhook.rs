@@ -0,0 +1,48 @@
+use crate::models::flow_webhook::{self, Column, Entity as FlowWebhook};
+use sea_orm::*;
+
+pub struct Repository;
+
+impl Repository {
+    pub async fn create(
+        db: &DatabaseConnection,
+        data: flow_webhook::ActiveModel,
+    ) -> Result<flow_webhook::Model, DbErr> {
+        data.insert(db).await
+    }
+
+    pub async fn update(
+        db: &DatabaseConnection,
+        data: flow_webhook::ActiveModel,
+    ) -> Result<flow_webhook::Model, DbErr> {
+        data.update(db).await
+    }
+
+    pub async fn find_by_flow(
+        db: &DatabaseConnection,
+        flow_id: String,
+    ) -> Result<Option<flow_webhook::Model>, DbErr> {
+        FlowWebhook::find()
+            .filter(Column::FlowId.eq(flow_id))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_by_token(
+        db: &DatabaseConnection,
+        token: String,
+    ) -> Result<Option<flow_webhook::Model>, DbErr> {
+        FlowWebhook::find()
+            .filter(Column::Token.eq(token))
+            .one(db)
+            .await
+    }
+
+    pub async fn delete_by_flow(db: &DatabaseConnection, flow_id: String) -> Result<u64, DbErr> {
+        let result = FlowWebhook::delete_many()
+            .filter(Column::FlowId.eq(flow_id))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+}
@@ -0,0 +1,84 @@
+use crate::models::flow_webhook_delivery::{self, Column, Entity as FlowWebhookDelivery};
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use uuid::Uuid;
+
+pub struct Repository;
+
+impl Repository {
+    /// Records the first delivery of an idempotency key. Returns `None` when the key was
+    /// already received by the webhook, unless that delivery never recorded its execution
+    /// within `lease_secs` (its receiver failed): the new delivery takes it over.
+    pub async fn claim(
+        db: &DatabaseConnection,
+        webhook_id: String,
+        idempotency_key: String,
+        lease_secs: i64,
+    ) -> Result<Option<flow_webhook_delivery::Model>, DbErr> {
+        let sql = r#"
+            INSERT INTO flow_webhook_deliveries (id, webhook_id, idempotency_key)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (webhook_id, idempotency_key) DO UPDATE
+                SET id = EXCLUDED.id, received_at = now()
+                WHERE flow_webhook_deliveries.execution_id IS NULL
+                    AND flow_webhook_deliveries.received_at < now() - make_interval(secs => $4)
+            RETURNING *"#;
+        FlowWebhookDelivery::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                [
+                    Uuid::new_v4().to_string().into(),
+                    webhook_id.into(),
+                    idempotency_key.into(),
+                    (lease_secs as f64).into(),
+                ],
+            ))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_by_key(
+        db: &DatabaseConnection,
+        webhook_id: String,
+        idempotency_key: String,
+    ) -> Result<Option<flow_webhook_delivery::Model>, DbErr> {
+        FlowWebhookDelivery::find()
+            .filter(Column::WebhookId.eq(webhook_id))
+            .filter(Column::IdempotencyKey.eq(idempotency_key))
+            .one(db)
+            .await
+    }
+
+    pub async fn set_execution(
+        db: &DatabaseConnection,
+        id: String,
+        execution_id: String,
+    ) -> Result<(), DbErr> {
+        FlowWebhookDelivery::update_many()
+            .col_expr(Column::ExecutionId, Expr::value(execution_id))
+            .filter(Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Forgets the deliveries received more than `retention_secs` ago, their idempotency
+    /// keys start an execution again.
+    pub async fn delete_older_than(
+        db: &DatabaseConnection,
+        retention_secs: i64,
+    ) -> Result<u64, DbErr> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::seconds(retention_secs);
+        let result = FlowWebhookDelivery::delete_many()
+            .filter(Column::ReceivedAt.lt(cutoff))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    pub async fn delete(db: &DatabaseConnection, id: String) -> Result<(), DbErr> {
+        FlowWebhookDelivery::delete_by_id(id).exec(db).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use crate::models::provider_limit::{self, Column, Entity as ProviderLimit};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::*;
+
+pub struct Repository;
+
+impl Repository {
+    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<provider_limit::Model>, DbErr> {
+        ProviderLimit::find()
+            .order_by_asc(Column::Credential)
+            .all(db)
+            .await
+    }
+
+    pub async fn upsert(
+        db: &DatabaseConnection,
+        data: provider_limit::ActiveModel,
+    ) -> Result<provider_limit::Model, DbErr> {
+        ProviderLimit::insert(data)
+            .on_conflict(
+                OnConflict::column(Column::Credential)
+                    .update_columns([
+                        Column::RequestsPerMinute,
+                        Column::TokensPerMinute,
+                        Column::Mode,
+                        Column::UpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_with_returning(db)
+            .await
+    }
+
+    pub async fn delete(db: &DatabaseConnection, credential: String) -> Result<u64, DbErr> {
+        let result = ProviderLimit::delete_by_id(credential).exec(db).await?;
+        Ok(result.rows_affected)
+    }
+}
@@ -0,0 +1,40 @@
+use crate::models::response_cache::{self, Column, Entity as ResponseCache};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::*;
+
+pub struct Repository;
+
+impl Repository {
+    pub async fn find_valid(
+        db: &DatabaseConnection,
+        key: String,
+    ) -> Result<Option<response_cache::Model>, DbErr> {
+        ResponseCache::find_by_id(key)
+            .filter(Column::ExpiresAt.gt(chrono::Utc::now()))
+            .one(db)
+            .await
+    }
+
+    pub async fn upsert(
+        db: &DatabaseConnection,
+        data: response_cache::ActiveModel,
+    ) -> Result<(), DbErr> {
+        ResponseCache::insert(data)
+            .on_conflict(
+                OnConflict::column(Column::Key)
+                    .update_columns([Column::TaskId, Column::Response, Column::ExpiresAt])
+                    .to_owned(),
+            )
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_expired(db: &DatabaseConnection) -> Result<u64, DbErr> {
+        let result = ResponseCache::delete_many()
+            .filter(Column::ExpiresAt.lte(chrono::Utc::now()))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+}
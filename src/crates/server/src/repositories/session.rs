@@ -0,0 +1,48 @@
+use crate::models::session::{self, Entity as Session};
+use sea_orm::*;
+
+pub struct Repository;
+
+impl Repository {
+    pub async fn create(
+        db: &DatabaseConnection,
+        data: session::ActiveModel,
+    ) -> Result<session::Model, DbErr> {
+        data.insert(db).await
+    }
+
+    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<session::Model>, DbErr> {
+        Session::find()
+            .order_by_desc(session::Column::UpdatedAt)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<session::Model>, DbErr> {
+        Session::find_by_id(id).one(db).await
+    }
+
+    /// Locks the session row until the end of the transaction, serializing the writes
+    /// to its history.
+    pub async fn lock<C: ConnectionTrait>(
+        db: &C,
+        id: String,
+    ) -> Result<Option<session::Model>, DbErr> {
+        Session::find_by_id(id).lock_exclusive().one(db).await
+    }
+
+    pub async fn touch(db: &DatabaseConnection, session: session::Model) -> Result<(), DbErr> {
+        let mut active_session: session::ActiveModel = session.into();
+        active_session.updated_at = Set(Some(chrono::Utc::now().into()));
+        active_session.update(db).await?;
+        Ok(())
+    }
+
+    pub async fn delete(db: &DatabaseConnection, id: String) -> Result<u64, DbErr> {
+        let result = Session::delete_by_id(id).exec(db).await?;
+        Ok(result.rows_affected)
+    }
+}
@@ -0,0 +1,32 @@
+use crate::models::session_message::{self, Column, Entity as SessionMessage};
+use sea_orm::{QueryOrder, *};
+
+pub struct Repository;
+
+impl Repository {
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        data: session_message::ActiveModel,
+    ) -> Result<session_message::Model, DbErr> {
+        data.insert(db).await
+    }
+
+    pub async fn find_by_session<C: ConnectionTrait>(
+        db: &C,
+        session_id: String,
+    ) -> Result<Vec<session_message::Model>, DbErr> {
+        SessionMessage::find()
+            .filter(Column::SessionId.eq(session_id))
+            .order_by_asc(Column::Position)
+            .all(db)
+            .await
+    }
+
+    pub async fn delete_many<C: ConnectionTrait>(db: &C, ids: Vec<String>) -> Result<u64, DbErr> {
+        let result = SessionMessage::delete_many()
+            .filter(Column::Id.is_in(ids))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+}
@@ -6,7 +6,13 @@ use utoipa_axum::router::OpenApiRouter;
 
 mod agent;
 mod agent_task;
+mod approval;
+mod callback;
 mod flow;
+mod provider_limit;
+mod schedule;
+mod session;
+mod webhook;
 
 pub fn create_router() -> (Router<AppState>, OpenApi) {
     // We create the router and collect the OpenAPI documentation
@@ -16,9 +22,19 @@ pub fn create_router() -> (Router<AppState>, OpenApi) {
             get(|| async { "AetherFlow: Online (ORM Active)" }),
         )
         .route("/ws", get(crate::handlers::ws::ws_handler))
+        .route(
+            "/ws/approvals",
+            get(crate::handlers::ws::approvals_ws_handler),
+        )
         .nest("/agents", agent::router())
         .nest("/tasks", agent_task::router())
         .nest("/flows", flow::router())
+        .nest("/approvals", approval::router())
+        .nest("/schedules", schedule::router())
+        .nest("/hooks", webhook::router())
+        .nest("/callbacks", callback::router())
+        .nest("/sessions", session::router())
+        .nest("/provider-limits", provider_limit::router())
         .split_for_parts();
 
     (router, api)
@@ -7,5 +7,6 @@ pub fn router() -> OpenApiRouter<AppState> {
     OpenApiRouter::new()
         .routes(routes!(agent::create_agent, agent::list_agents))
         .routes(routes!(agent::get_agent))
+        .routes(routes!(agent::update_rate_limit))
         .routes(routes!(gateway::execute_agent_task))
 }
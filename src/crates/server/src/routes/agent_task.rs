@@ -1,4 +1,4 @@
-use crate::handlers::agent_task;
+use crate::handlers::{agent_task, gateway};
 use crate::state::AppState;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
@@ -7,4 +7,5 @@ pub fn router() -> OpenApiRouter<AppState> {
         .routes(routes!(agent_task::create_task, agent_task::list_all_tasks))
         .routes(routes!(agent_task::list_tasks_for_agent))
         .routes(routes!(agent_task::get_task, agent_task::delete_task))
+        .routes(routes!(gateway::execute_task))
 }
@@ -0,0 +1,11 @@
+use crate::handlers::approval;
+use crate::state::AppState;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(approval::list_pending_approvals))
+        .routes(routes!(approval::get_approval))
+        .routes(routes!(approval::approve))
+        .routes(routes!(approval::reject))
+}
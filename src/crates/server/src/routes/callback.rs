@@ -0,0 +1,9 @@
+use crate::handlers::callback;
+use crate::state::AppState;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(callback::list_dead_letters))
+        .routes(routes!(callback::retry_delivery))
+}
@@ -1,11 +1,41 @@
-use crate::handlers::flow;
+use crate::handlers::{callback, document, flow, version, webhook};
 use crate::state::AppState;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 pub fn router() -> OpenApiRouter<AppState> {
     OpenApiRouter::new()
         .routes(routes!(flow::list_flows, flow::create_flow))
-        .routes(routes!(flow::get_flow))
+        .routes(routes!(
+            flow::get_flow,
+            flow::update_flow,
+            flow::delete_flow
+        ))
         .routes(routes!(flow::get_flow_steps, flow::add_flow_step))
+        .routes(routes!(flow::reorder_flow_steps))
+        .routes(routes!(flow::update_flow_step, flow::delete_flow_step))
+        .routes(routes!(flow::duplicate_flow_step))
         .routes(routes!(flow::execute_flow_task))
+        .routes(routes!(flow::check_flow))
+        .routes(routes!(document::export_flow))
+        .routes(routes!(document::import_flow))
+        .routes(routes!(version::list_flow_versions, version::publish_flow))
+        .routes(routes!(version::get_flow_version))
+        .routes(routes!(version::diff_flow_versions))
+        .routes(routes!(
+            webhook::get_webhook,
+            webhook::configure_webhook,
+            webhook::delete_webhook
+        ))
+        .routes(routes!(
+            callback::list_subscriptions,
+            callback::create_subscription
+        ))
+        .routes(routes!(callback::delete_subscription))
+        .routes(routes!(flow::get_execution))
+        .routes(routes!(flow::list_step_executions))
+        .routes(routes!(flow::list_child_executions))
+        .routes(routes!(callback::list_execution_callbacks))
+        .routes(routes!(flow::resume_execution))
+        .routes(routes!(flow::retry_step))
+        .routes(routes!(flow::cancel_execution))
 }
@@ -0,0 +1,12 @@
+use crate::handlers::provider_limit;
+use crate::state::AppState;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(provider_limit::list_limits))
+        .routes(routes!(
+            provider_limit::upsert_limit,
+            provider_limit::delete_limit
+        ))
+}
@@ -0,0 +1,14 @@
+use crate::handlers::schedule;
+use crate::state::AppState;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(schedule::create_schedule, schedule::list_schedules))
+        .routes(routes!(
+            schedule::get_schedule,
+            schedule::update_schedule,
+            schedule::delete_schedule
+        ))
+        .routes(routes!(schedule::list_schedule_runs))
+}
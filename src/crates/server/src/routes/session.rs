@@ -0,0 +1,9 @@
+use crate::handlers::session;
+use crate::state::AppState;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(session::create_session, session::list_sessions))
+        .routes(routes!(session::get_session, session::delete_session))
+}
@@ -0,0 +1,7 @@
+use crate::handlers::webhook;
+use crate::state::AppState;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new().routes(routes!(webhook::receive_webhook))
+}
@@ -2,6 +2,29 @@ pub mod agent;
 pub mod agent_client;
 pub mod agent_log;
 pub mod agent_task;
+pub mod agentic;
+pub mod callback;
+pub mod contract;
+pub mod fallback;
 pub mod flow;
+pub mod flow_approval;
+pub mod flow_document;
+pub mod flow_error_policy;
 pub mod flow_executor;
+pub mod flow_graph;
+pub mod flow_map;
+pub mod flow_router;
+pub mod flow_schedule;
+pub mod flow_simulation;
+pub mod flow_subflow;
+pub mod flow_template;
+pub mod flow_version;
+pub mod flow_webhook;
+pub mod flow_worker;
+pub mod json_path;
 pub mod monitor;
+pub mod provider_limit;
+pub mod rate_limiter;
+pub mod response_cache;
+pub mod session;
+pub mod task_runner;
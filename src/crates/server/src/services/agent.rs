@@ -1,5 +1,6 @@
 use crate::models::agent;
 use crate::repositories::agent::Repository as AgentRepository;
+use crate::services::rate_limiter::AgentRateLimit;
 
 use sea_orm::*;
 use uuid::Uuid;
@@ -19,6 +20,7 @@ impl Service {
             endpoint: Set(endpoint),
             status: Set(agent::AgentStatus::Pending),
             source: Set(source),
+            rate_limit: Set(None),
         };
 
         AgentRepository::create(db, new_agent).await
@@ -35,6 +37,24 @@ impl Service {
         AgentRepository::find_by_id(db, id).await
     }
 
+    /// Replaces the rate limits of an agent, `None` removes them.
+    pub async fn update_rate_limit(
+        db: &DatabaseConnection,
+        id: String,
+        rate_limit: Option<AgentRateLimit>,
+    ) -> Result<Option<agent::Model>, String> {
+        let value = match rate_limit {
+            Some(limit) => {
+                limit.validate()?;
+                Some(serde_json::to_value(limit).map_err(|e| e.to_string())?)
+            }
+            None => None,
+        };
+        AgentRepository::update_rate_limit(db, id, value)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
     pub async fn update_status(
         db: &DatabaseConnection,
         id: String,
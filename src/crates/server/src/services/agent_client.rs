@@ -1,16 +1,107 @@
+use crate::models::agent;
+use crate::services::{
+    contract::ContractViolation, rate_limiter::RateLimiter, session::Service as SessionService,
+};
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+/*
+ * Classification of a failed agent call, used to decide whether a fallback target is tried.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /* The agent could not be reached (connection refused, DNS, reset...) */
+    Network,
+    /* The call exceeded the HTTP timeout or the latency budget of the task */
+    Timeout,
+    /* The agent answered with a 5xx status */
+    ServerError,
+    /* The agent rejected the request with a 4xx status (other than 429) */
+    ClientError,
+    /* The agent or provider answered 429 Too Many Requests */
+    RateLimited,
+    /* The agent answered 2xx with a body that is not valid JSON */
+    InvalidResponse,
+    /* Failures on our side (database lookups, misconfiguration) */
+    Internal,
+    /* The payload or the response does not satisfy the contract of the task */
+    ContractViolation,
+}
+
+/// Error of an agent call, carrying the retries consumed and its class.
+#[derive(Debug, Clone)]
+pub struct AgentCallError {
+    pub message: String,
+    pub retries: i32,
+    pub class: ErrorClass,
+    /* Details of the broken contract for `ErrorClass::ContractViolation` */
+    pub contract: Option<Box<ContractViolation>>,
+}
+
+impl AgentCallError {
+    pub fn new(class: ErrorClass, message: impl Into<String>, retries: i32) -> Self {
+        Self {
+            message: message.into(),
+            retries,
+            class,
+            contract: None,
+        }
+    }
+
+    /// Failure that happened before or around the HTTP call itself.
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorClass::Internal, message, 0)
+    }
+
+    /// Payload or response rejected by the contract of the task.
+    pub fn contract(violation: ContractViolation, retries: i32) -> Self {
+        let message = violation.error.clone();
+        Self {
+            contract: Some(Box::new(violation)),
+            ..Self::new(ErrorClass::ContractViolation, message, retries)
+        }
+    }
+}
 
 pub struct Service;
 
 impl Service {
-    /// Executes a given task on a specific remote agent endpoint.
-    /// Implements a Resilience Engineering wrapper holding the complex Backoff mechanism.
+    /// Executes a given task on a specific remote agent endpoint once the rate limits of
+    /// the agent, and of the provider credential if any, grant capacity. The concurrency
+    /// slot of the agent is held until the call (retries included) completes.
     pub async fn execute_task(
         client: &Client,
+        limiter: &RateLimiter,
+        agent: &agent::Model,
         endpoint: &str,
         payload: &serde_json::Value,
-    ) -> Result<(serde_json::Value, i32), (String, i32)> {
-        Self::send_with_retry(client, endpoint, payload).await
+        credential: Option<&str>,
+    ) -> Result<(serde_json::Value, i32), AgentCallError> {
+        let _permit = limiter.acquire_agent(agent).await?;
+
+        // `credential` names the environment variable holding the provider API key
+        let api_key = match credential {
+            Some(credential) => {
+                let tokens = SessionService::estimate_tokens(&payload.to_string());
+                limiter.acquire_credential(credential, tokens).await?;
+                std::env::var(credential).ok()
+            }
+            None => None,
+        };
+
+        Self::execute_authorized(client, endpoint, payload, api_key.as_deref()).await
+    }
+
+    /// Implements a Resilience Engineering wrapper holding the complex Backoff mechanism,
+    /// authenticating with a bearer token when one is given (hosted LLM providers).
+    pub async fn execute_authorized(
+        client: &Client,
+        endpoint: &str,
+        payload: &serde_json::Value,
+        api_key: Option<&str>,
+    ) -> Result<(serde_json::Value, i32), AgentCallError> {
+        Self::send_with_retry(client, endpoint, payload, api_key).await
     }
 
     /// Internal helper handling exponential backoff and request isolation.
@@ -18,25 +109,32 @@ impl Service {
         client: &Client,
         endpoint: &str,
         payload: &serde_json::Value,
-    ) -> Result<(serde_json::Value, i32), (String, i32)> {
+        api_key: Option<&str>,
+    ) -> Result<(serde_json::Value, i32), AgentCallError> {
         let max_retries = 3;
         let mut base_delay = std::time::Duration::from_millis(500);
 
         for attempt in 1..=max_retries {
-            let res = client
+            let mut request = client
                 .post(endpoint)
                 .header("Content-Type", "application/json")
-                .json(payload)
-                .send()
-                .await;
+                .json(payload);
+            if let Some(key) = api_key {
+                request = request.bearer_auth(key);
+            }
+            let res = request.send().await;
 
             match res {
                 Ok(response) => {
                     if response.status().is_success() {
                         let json = response.json::<serde_json::Value>().await.map_err(|e| {
-                            (format!("Failed to parse JSON: {}", e), attempt as i32)
+                            AgentCallError::new(
+                                ErrorClass::InvalidResponse,
+                                format!("Failed to parse JSON: {}", e),
+                                attempt,
+                            )
                         })?;
-                        return Ok((json, attempt as i32));
+                        return Ok((json, attempt));
                     } else if response.status().is_server_error() && attempt < max_retries {
                         // 5xx internal agent errors, retry up to `max_retries`
                         tracing::warn!(
@@ -48,24 +146,38 @@ impl Service {
                         // 4xx errors (client faults) or max retries reached on 5xx
                         // We abort directly and pass the error info.
                         let status = response.status();
+                        let class = if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                            ErrorClass::RateLimited
+                        } else if status.is_server_error() {
+                            ErrorClass::ServerError
+                        } else {
+                            ErrorClass::ClientError
+                        };
                         let err_body = response
                             .text()
                             .await
                             .unwrap_or_else(|_| "Unknown error".to_string());
-                        return Err((
+                        return Err(AgentCallError::new(
+                            class,
                             format!("Agent returned HTTP {}: {}", status.as_u16(), err_body),
-                            attempt as i32,
+                            attempt,
                         ));
                     }
                 }
                 Err(e) => {
                     if attempt >= max_retries {
-                        return Err((
+                        let class = if e.is_timeout() {
+                            ErrorClass::Timeout
+                        } else {
+                            ErrorClass::Network
+                        };
+                        return Err(AgentCallError::new(
+                            class,
                             format!(
                                 "Failed to reach agent at {} after {} attempts: {}",
                                 endpoint, max_retries, e
                             ),
-                            attempt as i32,
+                            attempt,
                         ));
                     }
                     tracing::warn!(
@@ -85,9 +197,10 @@ impl Service {
             }
         }
 
-        Err((
-            "Failed to execute task: Unexpected state".to_string(),
-            max_retries as i32,
+        Err(AgentCallError::new(
+            ErrorClass::Internal,
+            "Failed to execute task: Unexpected state",
+            max_retries,
         ))
     }
 }
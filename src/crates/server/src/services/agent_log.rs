@@ -2,26 +2,33 @@ use crate::models::agent_log;
 use crate::repositories::agent_log::Repository as AgentLogRepository;
 
 use sea_orm::*;
-use uuid::Uuid;
 
 pub struct Service;
 
 impl Service {
     /// Inserts a new task log entry into the database.
     /// Used by the gateway to permanently trace all AI executions.
+    #[allow(clippy::too_many_arguments)]
     pub async fn create(
         db: &DatabaseConnection,
+        id: String,
         agent_id: String,
         prompt: serde_json::Value,
         response: serde_json::Value,
         retries: i32,
+        task_id: Option<String>,
+        cache_hit: bool,
+        target: Option<serde_json::Value>,
     ) -> Result<agent_log::Model, DbErr> {
         let log = agent_log::ActiveModel {
-            id: Set(Uuid::new_v4().to_string()),
+            id: Set(id),
             agent_id: Set(agent_id),
             prompt: Set(prompt),
             response: Set(response),
             retries: Set(retries),
+            task_id: Set(task_id),
+            cache_hit: Set(cache_hit),
+            target: Set(target),
             created_at: Set(None), // DB handles default timestamp
         };
 
@@ -7,6 +7,7 @@ use uuid::Uuid;
 pub struct Service;
 
 impl Service {
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_task(
         db: &DatabaseConnection,
         agent_id: String,
@@ -0,0 +1,335 @@
+use crate::integrations::{get_integration, ToolDefinition};
+use crate::models::agent_task;
+use crate::services::{
+    agent_client::{AgentCallError, ErrorClass},
+    agent_task::Service as AgentTaskService,
+    task_runner::Service as TaskRunner,
+};
+use crate::state::AppState;
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Task type of LLM tasks that may call other tasks as tools.
+pub const TASK_TYPE: &str = "agentic";
+
+/*
+ * Settings stored in `agent_tasks.settings` for agentic tasks, e.g.
+ * { "provider": "ollama", "model": "llama3.1", "tools": ["<task id>"], "max_iterations": 5 }
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct AgenticSettings {
+    /* Integration used to talk to the model ("ollama", "openai") */
+    #[serde(default = "default_provider")]
+    pub provider: String,
+
+    pub model: String,
+
+    pub system_prompt: Option<String>,
+
+    /* Ids of the agent tasks exposed to the model as tools */
+    #[serde(default)]
+    pub tools: Vec<String>,
+
+    /* Upper bound of model round trips before giving up on a final answer */
+    #[serde(default = "default_max_iterations")]
+    pub max_iterations: u32,
+
+    /* Name of the environment variable holding the provider API key */
+    pub api_key_env: Option<String>,
+}
+
+fn default_provider() -> String {
+    "ollama".to_string()
+}
+
+fn default_max_iterations() -> u32 {
+    5
+}
+
+impl AgenticSettings {
+    pub fn from_settings(settings: Option<&Value>) -> Result<Self, String> {
+        let settings = settings.ok_or_else(|| "Agentic tasks require settings".to_string())?;
+        let parsed: Self = serde_json::from_value(settings.clone())
+            .map_err(|e| format!("Invalid agentic settings: {}", e))?;
+
+        if parsed.max_iterations == 0 {
+            return Err("max_iterations must be greater than zero".to_string());
+        }
+        get_integration(&parsed.provider)?;
+        Ok(parsed)
+    }
+}
+
+pub struct Service;
+
+impl Service {
+    /// Checks that the settings of an agentic task are well formed and that every
+    /// referenced tool is an existing, non agentic task.
+    pub async fn validate(db: &DatabaseConnection, settings: Option<&Value>) -> Result<(), String> {
+        Self::validate_with(db, settings, &HashMap::new()).await
+    }
+
+    /// Same as `validate`, the tools being looked up by id in `pending` before the database:
+    /// tasks about to be created or updated along with the agentic task.
+    pub async fn validate_with(
+        db: &DatabaseConnection,
+        settings: Option<&Value>,
+        pending: &HashMap<String, agent_task::Model>,
+    ) -> Result<(), String> {
+        let settings = AgenticSettings::from_settings(settings)?;
+        Self::load_tools(db, &settings, pending).await.map(|_| ())
+    }
+
+    /// Runs the tool calling loop: the model receives the referenced tasks as tools,
+    /// every requested call is executed through the task runner and fed back, until
+    /// the model answers without tool calls or `max_iterations` is reached.
+    pub async fn run(
+        state: &AppState,
+        task: &agent_task::Model,
+        payload: &Value,
+        target: Option<&Value>,
+    ) -> Result<(Value, i32), AgentCallError> {
+        let settings = AgenticSettings::from_settings(task.settings.as_ref())
+            .map_err(AgentCallError::internal)?;
+        let tools = Self::load_tools(&state.db, &settings, &HashMap::new())
+            .await
+            .map_err(AgentCallError::internal)?;
+        let definitions: Vec<ToolDefinition> = tools.values().map(Self::tool_definition).collect();
+
+        let provider = get_integration(&settings.provider).map_err(AgentCallError::internal)?;
+        let agent = TaskRunner::find_agent(&state.db, task.agent_id.clone()).await?;
+        let endpoint = TaskRunner::resolve_endpoint(&agent, task);
+
+        // The incoming payload may override the model and system prompt of the task
+        let model = payload
+            .get("model")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&settings.model)
+            .to_string();
+        let mut messages = Self::initial_messages(&settings, payload);
+
+        let mut total_retries = 0;
+        let mut trace = Vec::new();
+
+        for iteration in 1..=settings.max_iterations {
+            let request = provider
+                .build_tool_payload(&model, &messages, &definitions)
+                .await
+                .map_err(|e| AgentCallError::new(ErrorClass::Internal, e, total_retries))?;
+
+            let (response, retries) = TaskRunner::call_and_log(
+                state,
+                &agent,
+                Some(&task.id),
+                &endpoint,
+                &request,
+                settings.api_key_env.as_deref(),
+                target,
+            )
+            .await
+            .map_err(|mut e| {
+                e.retries += total_retries;
+                e
+            })?;
+            total_retries += retries;
+
+            let turn = provider
+                .extract_tool_calls(&response)
+                .await
+                .map_err(|e| AgentCallError::new(ErrorClass::InvalidResponse, e, total_retries))?;
+            messages.push(turn.message.clone());
+
+            if turn.tool_calls.is_empty() {
+                return Ok((
+                    json!({
+                        "response": turn.content.unwrap_or_default(),
+                        "iterations": iteration,
+                        "tool_calls": trace,
+                    }),
+                    total_retries,
+                ));
+            }
+
+            for call in &turn.tool_calls {
+                // Tool failures are reported back to the model instead of aborting the loop
+                let result = match tools.get(&call.name) {
+                    Some(tool_task) => {
+                        // Boxed since tool tasks go through the task runner again
+                        match Box::pin(TaskRunner::execute(state, tool_task, &call.arguments)).await
+                        {
+                            Ok((output, retries)) => {
+                                total_retries += retries;
+                                output
+                            }
+                            Err(e) => {
+                                total_retries += e.retries;
+                                json!({ "error": e.message })
+                            }
+                        }
+                    }
+                    None => json!({ "error": format!("Unknown tool {}", call.name) }),
+                };
+
+                trace.push(json!({
+                    "tool": call.name,
+                    "arguments": call.arguments,
+                    "result": result,
+                }));
+                messages.push(provider.tool_result_message(call, &result));
+            }
+        }
+
+        Err(AgentCallError::new(
+            ErrorClass::Internal,
+            format!(
+                "Agentic task {} reached {} iterations without a final answer",
+                task.name, settings.max_iterations
+            ),
+            total_retries,
+        ))
+    }
+
+    /// Fetches the tool tasks keyed by the name exposed to the model.
+    async fn load_tools(
+        db: &DatabaseConnection,
+        settings: &AgenticSettings,
+        pending: &HashMap<String, agent_task::Model>,
+    ) -> Result<HashMap<String, agent_task::Model>, String> {
+        let mut tools = HashMap::new();
+        for tool_id in &settings.tools {
+            let tool = match pending.get(tool_id) {
+                Some(tool) => tool.clone(),
+                None => AgentTaskService::get_task_by_id(db, tool_id.clone())
+                    .await
+                    .map_err(|e| format!("Database error fetching tool task: {}", e))?
+                    .ok_or_else(|| format!("Tool task {} not found", tool_id))?,
+            };
+
+            // Nested loops would make the iteration limit meaningless
+            if tool.task_type == TASK_TYPE {
+                return Err(format!("Tool task {} cannot be agentic itself", tool.name));
+            }
+
+            let name = Self::tool_name(&tool.name);
+            if tools.contains_key(&name) {
+                return Err(format!("Duplicate tool name {}", name));
+            }
+            tools.insert(name, tool);
+        }
+        Ok(tools)
+    }
+
+    /// Builds the tool definition of a task, using its input contract as the argument schema.
+    fn tool_definition(task: &agent_task::Model) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::tool_name(&task.name),
+            description: task
+                .description
+                .clone()
+                .unwrap_or_else(|| task.name.clone()),
+            parameters: task
+                .input_contract
+                .clone()
+                .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+        }
+    }
+
+    /// Providers only accept `[a-zA-Z0-9_-]` in function names.
+    fn tool_name(task_name: &str) -> String {
+        task_name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+
+    /// Seeds the conversation from the incoming payload. A `messages` array is used as is,
+    /// otherwise the `prompt` field (or the whole payload) becomes the user message.
+    fn initial_messages(settings: &AgenticSettings, payload: &Value) -> Vec<Value> {
+        let mut messages = Vec::new();
+
+        let system_prompt = payload
+            .get("system")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .or_else(|| settings.system_prompt.clone());
+        if let Some(system_prompt) = system_prompt {
+            messages.push(json!({ "role": "system", "content": system_prompt }));
+        }
+
+        if let Some(history) = payload.get("messages").and_then(|v| v.as_array()) {
+            messages.extend(history.iter().cloned());
+            return messages;
+        }
+
+        let content = match payload {
+            Value::String(s) => s.clone(),
+            Value::Object(obj) => match obj.get("prompt").and_then(|v| v.as_str()) {
+                Some(prompt) => prompt.to_string(),
+                None => payload.to_string(),
+            },
+            other => other.to_string(),
+        };
+        messages.push(json!({ "role": "user", "content": content }));
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(raw: Value) -> Result<AgenticSettings, String> {
+        AgenticSettings::from_settings(Some(&raw))
+    }
+
+    #[test]
+    fn rejects_unknown_providers() {
+        let parsed = settings(json!({ "model": "llama3.1" })).unwrap();
+        assert_eq!(parsed.provider, "ollama");
+        assert!(settings(json!({ "provider": "OpenAI", "model": "gpt-4o" })).is_ok());
+        assert!(settings(json!({ "provider": "anthropic", "model": "claude" })).is_err());
+        assert!(settings(json!({ "provider": "olama", "model": "llama3.1" })).is_err());
+        assert!(settings(json!({ "model": "llama3.1", "max_iterations": 0 })).is_err());
+    }
+
+    #[test]
+    fn maps_task_names_to_tool_names() {
+        assert_eq!(Service::tool_name("Search Docs"), "search_docs");
+        assert_eq!(Service::tool_name("fetch-url_v2"), "fetch-url_v2");
+        assert_eq!(Service::tool_name("résumé.parse"), "r_sum__parse");
+    }
+
+    #[test]
+    fn seeds_the_conversation_from_the_payload() {
+        let parsed = settings(json!({ "model": "llama3.1", "system_prompt": "Be brief" })).unwrap();
+
+        let messages = Service::initial_messages(&parsed, &json!({ "prompt": "Hi" }));
+        assert_eq!(
+            messages,
+            vec![
+                json!({ "role": "system", "content": "Be brief" }),
+                json!({ "role": "user", "content": "Hi" }),
+            ]
+        );
+
+        let history = json!({
+            "system": "Override",
+            "messages": [{ "role": "user", "content": "Earlier" }]
+        });
+        let messages = Service::initial_messages(&parsed, &history);
+        assert_eq!(messages[0]["content"], json!("Override"));
+        assert_eq!(messages[1], json!({ "role": "user", "content": "Earlier" }));
+
+        let raw = json!({ "city": "Paris" });
+        let messages = Service::initial_messages(&parsed, &raw);
+        assert_eq!(messages[1]["content"], json!(raw.to_string()));
+    }
+}
@@ -0,0 +1,468 @@
+use crate::models::callback_delivery::{
+    self, DELIVERY_PENDING, EVENT_EXECUTION_CANCELLED, EVENT_EXECUTION_COMPLETED,
+    EVENT_EXECUTION_FAILED, EXECUTION_EVENTS,
+};
+use crate::models::flow_event_subscription::{self, CreateSubscriptionPayload};
+use crate::models::flow_execution;
+use crate::repositories::{
+    callback_delivery::Repository as CallbackDeliveryRepository,
+    flow::Repository as FlowRepository,
+    flow_event_subscription::Repository as FlowEventSubscriptionRepository,
+    flow_execution::Repository as FlowExecutionRepository,
+};
+use crate::services::flow_webhook::{random_key, sign, SIGNATURE_HEADER};
+use crate::state::AppState;
+use futures::future::join_all;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, Set, TransactionTrait};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+use tracing::info;
+
+/// Interval at which settled executions and due deliveries are looked for.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Deliveries sent per poll.
+const BATCH_SIZE: u64 = 50;
+/// Time a claimed delivery is hidden from the other instances while it is sent. The
+/// deliveries of a batch are posted concurrently, so it only has to cover one request.
+const LEASE_SECS: i64 = 60;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const _: () = assert!(LEASE_SECS as u64 > 2 * REQUEST_TIMEOUT.as_secs());
+/// Attempts after which a delivery is dead-lettered.
+const MAX_ATTEMPTS: i32 = 8;
+const BASE_RETRY_DELAY_SECS: u64 = 10;
+const MAX_RETRY_DELAY_SECS: u64 = 3600;
+
+/// Header naming the event of a delivery.
+pub const EVENT_HEADER: &str = "x-aetherflow-event";
+/// Header carrying the delivery id, identical across the retries of a delivery.
+pub const DELIVERY_HEADER: &str = "x-aetherflow-delivery";
+
+/// Delay before the attempt following `attempts` failed ones, doubling from 10s up to
+/// an hour, `None` once the attempts are exhausted.
+pub fn retry_delay(attempts: i32) -> Option<Duration> {
+    if attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    let secs = BASE_RETRY_DELAY_SECS
+        .saturating_mul(2u64.pow(exponent))
+        .min(MAX_RETRY_DELAY_SECS);
+    Some(Duration::from_secs(secs))
+}
+
+/// Event of a settled execution.
+pub fn execution_event(status: &str) -> &'static str {
+    match status {
+        "Completed" => EVENT_EXECUTION_COMPLETED,
+        "Cancelled" => EVENT_EXECUTION_CANCELLED,
+        _ => EVENT_EXECUTION_FAILED,
+    }
+}
+
+/// Rejects URLs events cannot be posted to: schemes other than http(s), and hosts
+/// inside the network of the gateway (loopback, private, link-local or metadata
+/// addresses) unless CALLBACK_ALLOW_PRIVATE_HOSTS is set, e.g. for local setups.
+/// Only literal addresses and well-known names are caught, callers remain trusted.
+pub fn check_url(url: &str) -> Result<(), String> {
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => parsed,
+        _ => return Err(format!("Invalid callback URL {}", url)),
+    };
+    let allow_private = env::var("CALLBACK_ALLOW_PRIVATE_HOSTS").is_ok_and(|v| v == "true");
+    if !allow_private && is_private_host(&parsed) {
+        return Err(format!("Callback URL {} targets a private address", url));
+    }
+    Ok(())
+}
+
+/// Rejects the callback URL of a request, whose events are signed with the
+/// CALLBACK_SECRET key: it must be set.
+pub fn check_callback_url(url: &str) -> Result<(), String> {
+    if callback_secret().is_none() {
+        return Err("callback_url requires the CALLBACK_SECRET key to sign its events".to_string());
+    }
+    check_url(url)
+}
+
+/// Whether the host of a URL is internal to the network of the gateway.
+pub fn is_private_host(url: &reqwest::Url) -> bool {
+    let host = match url.host_str() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => return true,
+    };
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => is_private_ipv4(ip),
+        Ok(IpAddr::V6(ip)) => {
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || (first & 0xfe00) == 0xfc00 // unique local
+                || (first & 0xffc0) == 0xfe80 // link-local
+                || ip.to_ipv4_mapped().is_some_and(is_private_ipv4)
+        }
+        Err(_) => {
+            let domain = host.to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost") || domain.ends_with(".internal")
+        }
+    }
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local() // cloud metadata endpoints (169.254.169.254)
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || (first == 100 && (second & 0xc0) == 64) // carrier-grade NAT
+}
+
+fn callback_secret() -> Option<String> {
+    env::var("CALLBACK_SECRET").ok().filter(|s| !s.is_empty())
+}
+
+pub struct Service;
+
+impl Service {
+    /// Spawns the dispatcher queueing the events of settled executions and posting the
+    /// due deliveries. Callback URLs are signed with the CALLBACK_SECRET key (required to
+    /// accept them), subscriptions with their own secret. Every gateway instance can run a dispatcher.
+    pub fn start(state: AppState) {
+        // Redirects are not followed: a public callback URL could otherwise hand the
+        // delivery over to a private host
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to build the callback HTTP client");
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = Self::queue_execution_events(&state.db).await {
+                    tracing::warn!("Callbacks: failed to queue execution events: {}", e);
+                }
+                match CallbackDeliveryRepository::claim_due(&state.db, BATCH_SIZE, LEASE_SECS).await
+                {
+                    Ok(deliveries) => {
+                        join_all(
+                            deliveries
+                                .into_iter()
+                                .map(|delivery| Self::send(&state.db, &client, delivery)),
+                        )
+                        .await;
+                    }
+                    Err(e) => tracing::warn!("Callbacks: failed to claim deliveries: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Queues an event for `url`, posted by the dispatcher.
+    pub async fn enqueue<C: ConnectionTrait>(
+        db: &C,
+        event: &str,
+        url: String,
+        execution_id: Option<String>,
+        subscription_id: Option<String>,
+        data: Value,
+    ) -> Result<callback_delivery::Model, DbErr> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = chrono::Utc::now();
+        let payload = json!({
+            "id": id,
+            "event": event,
+            "created_at": now.to_rfc3339(),
+            "data": data,
+        });
+        let delivery = callback_delivery::ActiveModel {
+            id: Set(id),
+            event: Set(event.to_string()),
+            url: Set(url),
+            payload: Set(payload),
+            execution_id: Set(execution_id),
+            subscription_id: Set(subscription_id),
+            status: Set(DELIVERY_PENDING.to_string()),
+            attempts: Set(0),
+            next_attempt_at: Set(Some(now.into())),
+            response_status: Set(None),
+            last_error: Set(None),
+            created_at: Set(Some(now.into())),
+            delivered_at: Set(None),
+        };
+        CallbackDeliveryRepository::create(db, delivery).await
+    }
+
+    // Queues the event of every settled execution for its callback URL and the
+    // subscriptions of its flow. The executions are claimed in the transaction queuing
+    // their deliveries, a failure leaves the whole batch to the next poll.
+    async fn queue_execution_events(db: &DatabaseConnection) -> Result<(), DbErr> {
+        let txn = db.begin().await?;
+        let executions = FlowExecutionRepository::claim_unnotified(&txn, BATCH_SIZE).await?;
+        let mut subscriptions: HashMap<String, Vec<flow_event_subscription::Model>> =
+            HashMap::new();
+        for execution in executions {
+            if !subscriptions.contains_key(&execution.flow_id) {
+                let found =
+                    FlowEventSubscriptionRepository::find_by_flow(db, execution.flow_id.clone())
+                        .await?;
+                subscriptions.insert(execution.flow_id.clone(), found);
+            }
+
+            let event = execution_event(&execution.status);
+            let data = Self::execution_data(&execution);
+            if let Some(url) = &execution.callback_url {
+                Self::enqueue(
+                    &txn,
+                    event,
+                    url.clone(),
+                    Some(execution.id.clone()),
+                    None,
+                    data.clone(),
+                )
+                .await?;
+            }
+            for subscription in subscriptions[&execution.flow_id]
+                .iter()
+                .filter(|s| s.enabled && s.subscribes_to(event))
+            {
+                Self::enqueue(
+                    &txn,
+                    event,
+                    subscription.url.clone(),
+                    Some(execution.id.clone()),
+                    Some(subscription.id.clone()),
+                    data.clone(),
+                )
+                .await?;
+            }
+        }
+        txn.commit().await
+    }
+
+    fn execution_data(execution: &flow_execution::Model) -> Value {
+        json!({
+            "execution_id": execution.id,
+            "flow_id": execution.flow_id,
+            "status": execution.status,
+            "output": execution.output_data,
+            "started_at": execution.started_at.map(|t| t.to_rfc3339()),
+            "completed_at": execution.completed_at.map(|t| t.to_rfc3339()),
+            "status_url": format!("/flows/executions/{}", execution.id),
+        })
+    }
+
+    // Posts a delivery once, recording the outcome and scheduling its retry.
+    async fn send(
+        db: &DatabaseConnection,
+        client: &reqwest::Client,
+        delivery: callback_delivery::Model,
+    ) {
+        let secret = match &delivery.subscription_id {
+            Some(id) => match FlowEventSubscriptionRepository::find_by_id(db, id.clone()).await {
+                Ok(Some(subscription)) => Some(subscription.secret),
+                // Deleted subscriptions cascade to their deliveries
+                Ok(None) => return,
+                Err(e) => {
+                    tracing::warn!("Callbacks: failed to fetch subscription {}: {}", id, e);
+                    return;
+                }
+            },
+            None => callback_secret(),
+        };
+
+        let body = delivery.payload.to_string();
+        let mut request = client
+            .post(&delivery.url)
+            .header("content-type", "application/json")
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, &delivery.id);
+        if let Some(secret) = &secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, body.as_bytes()));
+        }
+
+        let attempts = delivery.attempts + 1;
+        let (response_status, error) = match request.body(body).send().await {
+            Ok(response) if response.status().is_success() => {
+                let status = response.status().as_u16() as i32;
+                if let Err(e) =
+                    CallbackDeliveryRepository::mark_delivered(db, delivery.id, attempts, status)
+                        .await
+                {
+                    tracing::warn!("Callbacks: failed to record a delivery: {}", e);
+                }
+                return;
+            }
+            Ok(response) => (
+                Some(response.status().as_u16() as i32),
+                format!("Answered {}", response.status()),
+            ),
+            Err(e) => (None, e.to_string()),
+        };
+
+        let next_attempt_at = retry_delay(attempts).map(|delay| {
+            (chrono::Utc::now() + chrono::Duration::seconds(delay.as_secs() as i64)).into()
+        });
+        if next_attempt_at.is_none() {
+            info!(
+                "Callbacks: dead-lettered {} to {} after {} attempts: {}",
+                delivery.event, delivery.url, attempts, error
+            );
+        }
+        if let Err(e) = CallbackDeliveryRepository::mark_failed(
+            db,
+            delivery.id,
+            attempts,
+            response_status,
+            error,
+            next_attempt_at,
+        )
+        .await
+        {
+            tracing::warn!("Callbacks: failed to record a delivery: {}", e);
+        }
+    }
+
+    pub async fn list_dead_letters(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<callback_delivery::Model>, DbErr> {
+        CallbackDeliveryRepository::find_dead_letters(db).await
+    }
+
+    pub async fn get_execution_deliveries(
+        db: &DatabaseConnection,
+        execution_id: String,
+    ) -> Result<Vec<callback_delivery::Model>, DbErr> {
+        CallbackDeliveryRepository::find_by_execution(db, execution_id).await
+    }
+
+    /// Queues a dead-lettered delivery again. Returns `None` when the delivery does not
+    /// exist, an error when it is not dead-lettered.
+    pub async fn retry_delivery(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<callback_delivery::Model>, String> {
+        let delivery = match CallbackDeliveryRepository::find_by_id(db, id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            Some(delivery) => delivery,
+            None => return Ok(None),
+        };
+        CallbackDeliveryRepository::requeue(db, id)
+            .await
+            .map_err(|e| e.to_string())?
+            .map(Some)
+            .ok_or_else(|| format!("Delivery {} is {}", delivery.id, delivery.status))
+    }
+
+    pub async fn list_subscriptions(
+        db: &DatabaseConnection,
+        flow_id: String,
+    ) -> Result<Vec<flow_event_subscription::Model>, DbErr> {
+        FlowEventSubscriptionRepository::find_by_flow(db, flow_id).await
+    }
+
+    /// Subscribes a URL to the execution events of a flow. Returns `None` when the flow
+    /// does not exist.
+    pub async fn subscribe(
+        db: &DatabaseConnection,
+        flow_id: String,
+        payload: CreateSubscriptionPayload,
+    ) -> Result<Option<flow_event_subscription::Model>, String> {
+        if FlowRepository::find_by_id(db, flow_id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        check_url(&payload.url)?;
+        let events = payload
+            .events
+            .unwrap_or_else(|| EXECUTION_EVENTS.iter().map(|e| e.to_string()).collect());
+        if events.is_empty() {
+            return Err("A subscription needs at least one event".to_string());
+        }
+        if let Some(unknown) = events
+            .iter()
+            .find(|e| !EXECUTION_EVENTS.contains(&e.as_str()))
+        {
+            return Err(format!(
+                "Unknown event {} (available: {})",
+                unknown,
+                EXECUTION_EVENTS.join(", ")
+            ));
+        }
+        if payload.secret.as_ref().is_some_and(|s| s.is_empty()) {
+            return Err("The subscription secret cannot be empty".to_string());
+        }
+
+        let subscription = flow_event_subscription::ActiveModel {
+            id: Set(uuid::Uuid::new_v4().to_string()),
+            flow_id: Set(flow_id),
+            url: Set(payload.url),
+            secret: Set(payload.secret.unwrap_or_else(random_key)),
+            events: Set(json!(events)),
+            enabled: Set(true),
+            created_at: Set(Some(chrono::Utc::now().into())),
+        };
+        FlowEventSubscriptionRepository::create(db, subscription)
+            .await
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn unsubscribe(
+        db: &DatabaseConnection,
+        flow_id: String,
+        id: String,
+    ) -> Result<u64, DbErr> {
+        FlowEventSubscriptionRepository::delete(db, flow_id, id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_until_dead_lettered() {
+        assert_eq!(retry_delay(1), Some(Duration::from_secs(10)));
+        assert_eq!(retry_delay(2), Some(Duration::from_secs(20)));
+        assert_eq!(retry_delay(4), Some(Duration::from_secs(80)));
+        assert_eq!(
+            retry_delay(MAX_ATTEMPTS - 1),
+            Some(Duration::from_secs(640))
+        );
+        assert_eq!(retry_delay(MAX_ATTEMPTS), None);
+    }
+
+    #[test]
+    fn maps_settled_statuses_to_events() {
+        assert_eq!(execution_event("Completed"), EVENT_EXECUTION_COMPLETED);
+        assert_eq!(execution_event("Failed"), EVENT_EXECUTION_FAILED);
+        assert_eq!(execution_event("Cancelled"), EVENT_EXECUTION_CANCELLED);
+        assert!(check_url("https://example.com/hook").is_ok());
+        assert!(check_url("ftp://example.com").is_err());
+        assert!(check_url("not a url").is_err());
+    }
+
+    #[test]
+    fn flags_private_hosts() {
+        let private = |url: &str| is_private_host(&reqwest::Url::parse(url).unwrap());
+        assert!(private("http://localhost:8080/hook"));
+        assert!(private("http://127.0.0.1/hook"));
+        assert!(private("http://10.1.2.3/hook"));
+        assert!(private("http://192.168.0.10/hook"));
+        assert!(private("http://169.254.169.254/latest/meta-data"));
+        assert!(private("http://metadata.google.internal/"));
+        assert!(private("http://[::1]/hook"));
+        assert!(private("http://[::ffff:10.0.0.1]/hook"));
+        assert!(!private("https://example.com/hook"));
+        assert!(!private("https://93.184.216.34/hook"));
+    }
+}
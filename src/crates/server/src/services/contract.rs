@@ -0,0 +1,501 @@
+use crate::models::agent_task;
+use crate::models::flow_step::{self, STEP_TYPE_APPROVAL, STEP_TYPE_ROUTER, STEP_TYPE_TASK};
+use crate::services::flow_executor::Service as FlowExecutor;
+use crate::services::flow_graph::FlowGraph;
+use crate::services::flow_template::TemplateContext;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Contract checked on the payload sent to the agent.
+pub const CONTRACT_INPUT: &str = "input";
+/// Contract checked on the response of the agent.
+pub const CONTRACT_OUTPUT: &str = "output";
+
+/// A value that does not satisfy a JSON Schema.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Violation {
+    /// JSON Pointer of the offending value (empty for the root)
+    pub path: String,
+    pub message: String,
+}
+
+/// Body of the 422 answered when a payload or a response breaks the contract of its task.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ContractViolation {
+    pub error: String,
+    /// "input" or "output"
+    pub contract: String,
+    pub task_id: String,
+    pub violations: Vec<Violation>,
+}
+
+/// Compatibility of one edge of a flow: the output of `from` feeding the input of `to`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EdgeCheck {
+    pub from: String,
+    pub to: String,
+    /// "compatible", "incompatible" or "unchecked"
+    pub status: String,
+    /// Why the edge could not be checked
+    #[schema(value_type = Option<String>)]
+    pub reason: Option<String>,
+    pub violations: Vec<Violation>,
+}
+
+/// Result of `POST /flows/{id}/check`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FlowCheck {
+    pub valid: bool,
+    pub edges: Vec<EdgeCheck>,
+}
+
+/// Rejects contracts that are not valid JSON Schemas.
+pub fn validate_schema(schema: &Value) -> Result<(), String> {
+    jsonschema::validator_for(schema)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid JSON Schema: {}", e))
+}
+
+/// Validates `instance` against `schema`, listing every violation.
+pub fn validate(schema: &Value, instance: &Value) -> Result<(), Vec<Violation>> {
+    let validator = jsonschema::validator_for(schema).map_err(|e| {
+        vec![Violation {
+            path: String::new(),
+            message: format!("Invalid JSON Schema: {}", e),
+        }]
+    })?;
+    let violations: Vec<Violation> = validator
+        .iter_errors(instance)
+        .map(|e| Violation {
+            path: e.instance_path.as_str().to_string(),
+            message: e.to_string(),
+        })
+        .collect();
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// Checks the payload sent to a task against its `input_contract`, if any.
+pub fn check_input(task: &agent_task::Model, payload: &Value) -> Result<(), ContractViolation> {
+    check(task, task.input_contract.as_ref(), CONTRACT_INPUT, payload)
+}
+
+/// Checks the response of a task against its `output_contract`, if any.
+pub fn check_output(task: &agent_task::Model, response: &Value) -> Result<(), ContractViolation> {
+    check(
+        task,
+        task.output_contract.as_ref(),
+        CONTRACT_OUTPUT,
+        response,
+    )
+}
+
+fn check(
+    task: &agent_task::Model,
+    schema: Option<&Value>,
+    contract: &str,
+    instance: &Value,
+) -> Result<(), ContractViolation> {
+    let schema = match schema {
+        Some(schema) if !schema.is_null() => schema,
+        _ => return Ok(()),
+    };
+    validate(schema, instance).map_err(|violations| ContractViolation {
+        error: format!(
+            "The {} of task {} does not satisfy its {} contract",
+            if contract == CONTRACT_INPUT {
+                "payload"
+            } else {
+                "response"
+            },
+            task.name,
+            contract
+        ),
+        contract: contract.to_string(),
+        task_id: task.id.clone(),
+        violations,
+    })
+}
+
+/*
+ * Static compatibility of two schemas: what `output` may produce has to be accepted by
+ * `input`. Only the structure is compared (types, required properties, nested properties
+ * and array items), keywords such as `pattern` or `minimum` are left to runtime validation.
+ */
+pub fn compatibility(output: &Value, input: &Value) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    compare(output, input, "", &mut violations);
+    violations
+}
+
+fn compare(output: &Value, input: &Value, path: &str, violations: &mut Vec<Violation>) {
+    let (output, input) = match (output.as_object(), input.as_object()) {
+        (Some(output), Some(input)) => (output, input),
+        _ => return,
+    };
+
+    let produced = types_of(output.get("type"));
+    let accepted = types_of(input.get("type"));
+    if !produced.is_empty() && !accepted.is_empty() {
+        let rejected: Vec<&str> = produced
+            .iter()
+            .filter(|t| {
+                let widened = **t == "integer" && accepted.contains(&"number");
+                !(accepted.contains(t) || widened)
+            })
+            .copied()
+            .collect();
+        if !rejected.is_empty() {
+            violations.push(Violation {
+                path: path.to_string(),
+                message: format!(
+                    "produces {} but {} is expected",
+                    rejected.join(" or "),
+                    accepted.join(" or ")
+                ),
+            });
+            return;
+        }
+    }
+
+    let output_properties = output.get("properties").and_then(|p| p.as_object());
+    let output_required = strings_of(output.get("required"));
+    for key in strings_of(input.get("required")) {
+        if !output_required.contains(&key) {
+            let message = if output_properties.is_some_and(|p| p.contains_key(key)) {
+                format!("required property {} is optional in the output", key)
+            } else {
+                format!("required property {} is not produced", key)
+            };
+            violations.push(Violation {
+                path: format!("{}/{}", path, key),
+                message,
+            });
+        }
+    }
+
+    if let (Some(output_properties), Some(input_properties)) = (
+        output_properties,
+        input.get("properties").and_then(|p| p.as_object()),
+    ) {
+        for (key, input_schema) in input_properties {
+            if let Some(output_schema) = output_properties.get(key) {
+                compare(
+                    output_schema,
+                    input_schema,
+                    &format!("{}/{}", path, key),
+                    violations,
+                );
+            }
+        }
+    }
+
+    if let (Some(output_items), Some(input_items)) = (output.get("items"), input.get("items")) {
+        compare(
+            output_items,
+            input_items,
+            &format!("{}/*", path),
+            violations,
+        );
+    }
+}
+
+fn types_of(value: Option<&Value>) -> Vec<&str> {
+    match value {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(types)) => types.iter().filter_map(|t| t.as_str()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn strings_of(value: Option<&Value>) -> Vec<&str> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|items| items.iter().filter_map(|i| i.as_str()).collect())
+        .unwrap_or_default()
+}
+
+/// Checks every edge of a flow feeding a task step. Steps without config forward their
+/// input unchanged to their task: the output contract of the upstream steps is checked.
+/// Routers and approvals pass their input through, so the edge is checked against the
+/// step feeding them. Fan-in inputs are checked as an object keyed by the upstream step
+/// names. Steps with a config send the payload built from it: a static template is
+/// rendered and validated, a template reading the input or the flow state only has the
+/// structure of its payload checked.
+pub fn check_flow(
+    steps: &[flow_step::Model],
+    tasks: &HashMap<String, agent_task::Model>,
+) -> Result<FlowCheck, String> {
+    let graph = FlowGraph::from_steps(steps)?;
+    let mut edges = Vec::new();
+
+    for (index, step) in steps.iter().enumerate() {
+        let deps = &graph.dependencies[index];
+        if step.step_type != STEP_TYPE_TASK || deps.is_empty() {
+            continue;
+        }
+        let edge = |reason: Option<&str>, violations: Vec<Violation>| EdgeCheck {
+            from: deps
+                .iter()
+                .map(|dep| graph.labels[*dep].clone())
+                .collect::<Vec<_>>()
+                .join(", "),
+            to: graph.labels[index].clone(),
+            status: match (reason, violations.is_empty()) {
+                (Some(_), _) => "unchecked",
+                (None, true) => "compatible",
+                (None, false) => "incompatible",
+            }
+            .to_string(),
+            reason: reason.map(|r| r.to_string()),
+            violations,
+        };
+
+        let input = match step
+            .task_id
+            .as_ref()
+            .and_then(|id| tasks.get(id))
+            .and_then(|task| task.input_contract.as_ref())
+        {
+            Some(input) if !input.is_null() => input,
+            _ => {
+                edges.push(edge(
+                    Some("the step task has no input contract"),
+                    Vec::new(),
+                ));
+                continue;
+            }
+        };
+        if let Some(config) = step.config.as_ref().filter(|c| c.is_object()) {
+            match config_payload(config) {
+                Some(ConfigPayload::Static(payload)) => edges.push(edge(
+                    None,
+                    validate(input, &payload).err().unwrap_or_default(),
+                )),
+                Some(ConfigPayload::Shape(shape)) => {
+                    edges.push(edge(None, compatibility(&shape, input)))
+                }
+                None => edges.push(edge(
+                    Some("the step config does not render a payload"),
+                    Vec::new(),
+                )),
+            }
+            continue;
+        }
+
+        let produced = match deps.as_slice() {
+            [dep] => output_schema(&graph, steps, tasks, *dep),
+            _ => {
+                let mut properties = serde_json::Map::new();
+                for dep in deps {
+                    match output_schema(&graph, steps, tasks, *dep) {
+                        Some(schema) => {
+                            properties.insert(graph.labels[*dep].clone(), schema);
+                        }
+                        None => {
+                            properties.clear();
+                            break;
+                        }
+                    }
+                }
+                // Branches may be skipped, so fan-in keys are never guaranteed
+                (!properties.is_empty())
+                    .then(|| json!({ "type": "object", "properties": properties }))
+            }
+        };
+        match produced {
+            Some(produced) => edges.push(edge(None, compatibility(&produced, input))),
+            None => edges.push(edge(
+                Some("an upstream step has no output contract"),
+                Vec::new(),
+            )),
+        }
+    }
+
+    Ok(FlowCheck {
+        valid: edges.iter().all(|e| e.status != "incompatible"),
+        edges,
+    })
+}
+
+// Payload built from a step config: rendered when its templates are static, otherwise
+// the schema of its structure, the rendered templates being strings.
+enum ConfigPayload {
+    Static(Value),
+    Shape(Value),
+}
+
+fn config_payload(config: &Value) -> Option<ConfigPayload> {
+    let templates = ["template", "prompt", "system_prompt"];
+    let rendered = templates
+        .iter()
+        .filter_map(|key| config.get(*key).and_then(|t| t.as_str()))
+        .all(TemplateContext::is_static);
+    // Without template the prompt is the text of the step input
+    let prompt = config.get("template").or_else(|| config.get("prompt"));
+    if rendered && prompt.is_some_and(|p| p.is_string()) {
+        return FlowExecutor::build_payload(config, &Value::Null, &TemplateContext::default())
+            .ok()
+            .map(ConfigPayload::Static);
+    }
+
+    let mut blank = config.clone();
+    if let Some(obj) = blank.as_object_mut() {
+        for key in templates {
+            if obj.get(key).is_some_and(|t| t.is_string()) {
+                obj.insert(key.to_string(), json!(""));
+            }
+        }
+    }
+    FlowExecutor::build_payload(&blank, &Value::Null, &TemplateContext::default())
+        .ok()
+        .map(|payload| ConfigPayload::Shape(schema_of(&payload)))
+}
+
+// Schema of the structure of a value: its type, and the properties of objects, all required.
+fn schema_of(value: &Value) -> Value {
+    match value {
+        Value::Object(obj) => json!({
+            "type": "object",
+            "required": obj.keys().collect::<Vec<_>>(),
+            "properties": obj
+                .iter()
+                .map(|(key, value)| (key.clone(), schema_of(value)))
+                .collect::<serde_json::Map<_, _>>(),
+        }),
+        Value::Array(_) => json!({ "type": "array" }),
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "type": "integer" }),
+        Value::Number(_) => json!({ "type": "number" }),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Null => json!({ "type": "null" }),
+    }
+}
+
+// Output contract of a step, looking through routers and approvals to the step feeding them.
+fn output_schema(
+    graph: &FlowGraph,
+    steps: &[flow_step::Model],
+    tasks: &HashMap<String, agent_task::Model>,
+    index: usize,
+) -> Option<Value> {
+    let step = &steps[index];
+    if step.step_type == STEP_TYPE_ROUTER || step.step_type == STEP_TYPE_APPROVAL {
+        return match graph.dependencies[index].as_slice() {
+            [dep] => output_schema(graph, steps, tasks, *dep),
+            _ => None,
+        };
+    }
+    if step.step_type != STEP_TYPE_TASK {
+        return None;
+    }
+    step.task_id
+        .as_ref()
+        .and_then(|id| tasks.get(id))
+        .and_then(|task| task.output_contract.clone())
+        .filter(|schema| !schema.is_null())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validation_reports_paths() {
+        let schema = json!({
+            "type": "object",
+            "required": ["prompt"],
+            "properties": { "prompt": { "type": "string" }, "tags": { "type": "array", "items": { "type": "string" } } }
+        });
+        assert!(validate(&schema, &json!({ "prompt": "hi" })).is_ok());
+
+        let violations = validate(&schema, &json!({ "tags": ["a", 1] })).unwrap_err();
+        assert_eq!(violations.len(), 2);
+        assert!(violations.iter().any(|v| v.path == "/tags/1"));
+        assert!(violations.iter().any(|v| v.message.contains("prompt")));
+    }
+
+    #[test]
+    fn compatibility_compares_types_and_required_properties() {
+        let output = json!({
+            "type": "object",
+            "required": ["summary"],
+            "properties": { "summary": { "type": "string" }, "score": { "type": "integer" } }
+        });
+        let input = json!({
+            "type": "object",
+            "required": ["summary"],
+            "properties": { "summary": { "type": "string" }, "score": { "type": "number" } }
+        });
+        assert!(compatibility(&output, &input).is_empty());
+
+        let strict = json!({
+            "type": "object",
+            "required": ["summary", "score", "lang"],
+            "properties": { "summary": { "type": "array" } }
+        });
+        let violations = compatibility(&output, &strict);
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, vec!["/score", "/lang", "/summary"]);
+
+        assert_eq!(
+            compatibility(&json!({ "type": "string" }), &json!({ "type": "object" })).len(),
+            1
+        );
+    }
+
+    fn task(input_contract: Value) -> agent_task::Model {
+        agent_task::Model {
+            id: "task".to_string(),
+            agent_id: "agent".to_string(),
+            name: "classify".to_string(),
+            description: None,
+            task_type: "http".to_string(),
+            path: None,
+            method: None,
+            input_contract: Some(input_contract),
+            output_contract: None,
+            settings: None,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn checks_payloads_built_from_configs() {
+        let input = json!({
+            "type": "object",
+            "required": ["prompt", "temperature"],
+            "properties": {
+                "prompt": { "type": "string", "minLength": 3 },
+                "temperature": { "type": "number" }
+            }
+        });
+        let tasks = HashMap::from([("task".to_string(), task(input))]);
+        let statuses = |config: Value| {
+            let steps = vec![
+                flow_step::Model::fixture("a"),
+                flow_step::Model::fixture("b").with_config(config),
+            ];
+            let check = check_flow(&steps, &tasks).unwrap();
+            let edge = check.edges.last().unwrap().clone();
+            (edge.status, edge.violations.len())
+        };
+
+        // Static templates are rendered and validated
+        let valid = json!({ "template": "Classify", "temperature": 0 });
+        assert_eq!(statuses(valid), ("compatible".to_string(), 0));
+        let short = json!({ "template": "Hi", "temperature": 0 });
+        assert_eq!(statuses(short), ("incompatible".to_string(), 1));
+
+        // Templates reading the input have their structure checked
+        let templated = json!({ "template": "{{input}}", "temperature": 0.2 });
+        assert_eq!(statuses(templated), ("compatible".to_string(), 0));
+        let missing = json!({ "template": "{{ previous.text }}" });
+        assert_eq!(statuses(missing), ("incompatible".to_string(), 1));
+    }
+}
@@ -0,0 +1,259 @@
+use crate::integrations::get_integration;
+use crate::models::agent_task;
+use crate::repositories::{
+    agent::Repository as AgentRepository, agent_task::Repository as AgentTaskRepository,
+};
+use crate::services::agent_client::ErrorClass;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/*
+ * Fallback chain read from `agent_tasks.settings.fallback`, e.g.
+ * { "fallback": { "targets": [{ "model": "llama3.2" }, { "agent_id": "..." }],
+ *                 "on": ["timeout", "server_error"], "latency_ms": 8000 } }
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct FallbackPolicy {
+    /* Ordered alternatives tried after the primary target fails */
+    #[serde(default)]
+    pub targets: Vec<FallbackTarget>,
+
+    /* Error classes that trigger the next target, other failures are returned as is */
+    #[serde(default = "default_error_classes")]
+    pub on: Vec<ErrorClass>,
+
+    /* Latency budget of every attempt, exceeding it counts as a timeout. The budget runs
+     * from the start of the attempt, the wait for the rate limits of the agent included:
+     * a target queued behind its limits is as slow as a target answering late */
+    pub latency_ms: Option<u64>,
+}
+
+pub fn default_error_classes() -> Vec<ErrorClass> {
+    vec![
+        ErrorClass::Network,
+        ErrorClass::Timeout,
+        ErrorClass::ServerError,
+        ErrorClass::RateLimited,
+    ]
+}
+
+/*
+ * A fallback target. Fields combine: e.g. `task_id` + `model` runs another task with another model.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FallbackTarget {
+    /* Run another task instead of the primary one */
+    pub task_id: Option<String>,
+
+    /* Run the same task against another agent */
+    pub agent_id: Option<String>,
+
+    /* Override the model of the payload (and of agentic settings) */
+    pub model: Option<String>,
+
+    /* Override the integration of agentic tasks ("ollama", "openai") */
+    pub provider: Option<String>,
+
+    /* Environment variable holding the API key of the overridden provider */
+    pub api_key_env: Option<String>,
+}
+
+impl FallbackPolicy {
+    /// Parses the fallback section of task settings, `None` when the task declares none.
+    pub fn from_settings(settings: Option<&Value>) -> Result<Option<Self>, String> {
+        let raw = match settings.and_then(|s| s.get("fallback")) {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+
+        let policy: Self = serde_json::from_value(raw.clone())
+            .map_err(|e| format!("Invalid fallback settings: {}", e))?;
+
+        for (index, target) in policy.targets.iter().enumerate() {
+            if target.task_id.is_none()
+                && target.agent_id.is_none()
+                && target.model.is_none()
+                && target.provider.is_none()
+            {
+                return Err(format!(
+                    "Fallback target {} does not change anything",
+                    index
+                ));
+            }
+            if let Some(provider) = &target.provider {
+                get_integration(provider)?;
+            }
+        }
+
+        if policy.targets.is_empty() && policy.latency_ms.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(policy))
+    }
+
+    pub fn from_task(task: &agent_task::Model) -> Result<Option<Self>, String> {
+        Self::from_settings(task.settings.as_ref())
+    }
+
+    /// Parses the fallback section of task settings and rejects targets running a task
+    /// or calling an agent that does not exist.
+    pub async fn validate(db: &DatabaseConnection, settings: Option<&Value>) -> Result<(), String> {
+        let policy = match Self::from_settings(settings)? {
+            Some(policy) => policy,
+            None => return Ok(()),
+        };
+        for (index, target) in policy.targets.iter().enumerate() {
+            if let Some(task_id) = &target.task_id {
+                let found = AgentTaskRepository::find_by_id(db, task_id.clone())
+                    .await
+                    .map_err(|e| format!("Database error fetching tasks: {}", e))?;
+                if found.is_none() {
+                    return Err(format!(
+                        "Fallback target {}: task {} not found",
+                        index, task_id
+                    ));
+                }
+            }
+            if let Some(agent_id) = &target.agent_id {
+                let found = AgentRepository::find_by_id(db, agent_id.clone())
+                    .await
+                    .map_err(|e| format!("Database error fetching agents: {}", e))?;
+                if found.is_none() {
+                    return Err(format!(
+                        "Fallback target {}: agent {} not found",
+                        index, agent_id
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FallbackTarget {
+    /// Applies the agent/model/provider overrides to a task and its payload.
+    /// Replacing the task itself (`task_id`) is resolved by the caller beforehand.
+    pub fn apply(&self, task: &agent_task::Model, payload: &Value) -> (agent_task::Model, Value) {
+        let mut task = task.clone();
+        let mut payload = payload.clone();
+
+        if let Some(agent_id) = &self.agent_id {
+            task.agent_id = agent_id.clone();
+        }
+
+        if let Some(model) = &self.model {
+            if let Some(obj) = payload.as_object_mut() {
+                obj.insert("model".to_string(), Value::String(model.clone()));
+            }
+        }
+
+        // Provider level overrides only make sense for tasks driving an LLM integration
+        let overrides = [
+            ("model", &self.model),
+            ("provider", &self.provider),
+            ("api_key_env", &self.api_key_env),
+        ];
+        if overrides.iter().any(|(_, v)| v.is_some()) {
+            let mut settings = task
+                .settings
+                .clone()
+                .unwrap_or_else(|| serde_json::json!({}));
+            if let Some(obj) = settings.as_object_mut() {
+                for (key, value) in overrides {
+                    if let Some(value) = value {
+                        obj.insert(key.to_string(), Value::String(value.clone()));
+                    }
+                }
+            }
+            task.settings = Some(settings);
+        }
+
+        (task, payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn task(settings: Option<Value>) -> agent_task::Model {
+        agent_task::Model {
+            id: "task".to_string(),
+            agent_id: "agent".to_string(),
+            name: "classify".to_string(),
+            description: None,
+            task_type: "http".to_string(),
+            path: None,
+            method: None,
+            input_contract: None,
+            output_contract: None,
+            settings,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn parses_and_validates_fallback_settings() {
+        assert!(FallbackPolicy::from_settings(None).unwrap().is_none());
+        assert!(
+            FallbackPolicy::from_settings(Some(&json!({ "fallback": {} })))
+                .unwrap()
+                .is_none()
+        );
+
+        let policy = FallbackPolicy::from_settings(Some(&json!({
+            "fallback": { "targets": [{ "model": "llama3.2" }], "latency_ms": 8000 }
+        })))
+        .unwrap()
+        .unwrap();
+        assert_eq!(policy.targets.len(), 1);
+        assert_eq!(policy.on, default_error_classes());
+        assert_eq!(policy.latency_ms, Some(8000));
+
+        let budget_only = json!({ "fallback": { "latency_ms": 100 } });
+        assert!(FallbackPolicy::from_settings(Some(&budget_only))
+            .unwrap()
+            .is_some());
+
+        for invalid in [
+            json!({ "fallback": { "targets": [{}] } }),
+            json!({ "fallback": { "targets": [{ "provider": "gemini" }] } }),
+            json!({ "fallback": { "on": ["unknown"] } }),
+        ] {
+            assert!(FallbackPolicy::from_settings(Some(&invalid)).is_err());
+        }
+    }
+
+    #[test]
+    fn applies_target_overrides() {
+        let primary = task(Some(json!({ "provider": "ollama", "temperature": 0 })));
+        let payload = json!({ "prompt": "hi", "model": "llama3" });
+
+        let target = FallbackTarget {
+            agent_id: Some("backup".to_string()),
+            ..Default::default()
+        };
+        let (task, applied) = target.apply(&primary, &payload);
+        assert_eq!(task.agent_id, "backup");
+        assert_eq!(task.settings, primary.settings);
+        assert_eq!(applied, payload);
+
+        let target = FallbackTarget {
+            model: Some("gpt-4o-mini".to_string()),
+            provider: Some("openai".to_string()),
+            api_key_env: Some("OPENAI_API_KEY".to_string()),
+            ..Default::default()
+        };
+        let (task, applied) = target.apply(&primary, &payload);
+        assert_eq!(task.agent_id, "agent");
+        assert_eq!(applied["model"], json!("gpt-4o-mini"));
+        assert_eq!(applied["prompt"], json!("hi"));
+        let settings = task.settings.unwrap();
+        assert_eq!(settings["provider"], json!("openai"));
+        assert_eq!(settings["model"], json!("gpt-4o-mini"));
+        assert_eq!(settings["api_key_env"], json!("OPENAI_API_KEY"));
+        assert_eq!(settings["temperature"], json!(0));
+    }
+}
@@ -1,10 +1,28 @@
-use crate::models::flow::FlowWithSteps;
-use crate::models::flow_step::FlowStepWithTask;
-use crate::models::{agent_task, flow, flow_step};
+use crate::models::flow::{FlowWithSteps, UpdateFlowPayload};
+use crate::models::flow_step::{
+    CreateFlowStepPayload, FlowStepWithTask, ReorderFlowStepsPayload, UpdateFlowStepPayload,
+};
+use crate::models::flow_step::{
+    STEP_TYPE_APPROVAL, STEP_TYPE_FLOW, STEP_TYPE_MAP, STEP_TYPE_ROUTER, STEP_TYPE_TASK,
+};
+use crate::models::{agent_task, flow, flow_execution, flow_step, flow_step_execution};
 use crate::repositories::{
-    flow::Repository as FlowRepository, flow_step::Repository as FlowStepRepository,
+    agent_task::Repository as AgentTaskRepository, flow::Repository as FlowRepository,
+    flow_execution::Repository as FlowExecutionRepository,
+    flow_step::Repository as FlowStepRepository,
+    flow_step_execution::Repository as FlowStepExecutionRepository,
 };
-use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use crate::services::contract::{self, FlowCheck};
+use crate::services::flow_approval::ApprovalConfig;
+use crate::services::flow_error_policy::ErrorPolicy;
+use crate::services::flow_graph::FlowGraph;
+use crate::services::flow_map::MapConfig;
+use crate::services::flow_router::RouterConfig;
+use crate::services::flow_subflow::{Service as SubFlowService, SubFlowConfig};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set,
+};
+use std::collections::HashMap;
 
 pub struct Service;
 
@@ -37,15 +55,18 @@ impl Service {
                 .cloned()
                 .collect();
 
-            steps_for_flow.sort_by_key(|(step, _)| step.step_order);
+            steps_for_flow
+                .sort_by(|(a, _), (b, _)| (a.step_order, &a.id).cmp(&(b.step_order, &b.id)));
 
             let mut agents_chain = Vec::new();
             let steps = steps_for_flow
                 .into_iter()
                 .map(|(step, opt_task)| {
-                    let task_name = opt_task
-                        .map(|t| t.name)
-                        .unwrap_or_else(|| "Unknown Task".to_string());
+                    let task_name = match opt_task {
+                        Some(task) => task.name,
+                        None if step.step_type != STEP_TYPE_TASK => step.step_type.clone(),
+                        None => "Unknown Task".to_string(),
+                    };
                     agents_chain.push(task_name.clone());
                     FlowStepWithTask { step, task_name }
                 })
@@ -68,14 +89,583 @@ impl Service {
         FlowRepository::find_by_id(db, id).await
     }
 
+    /// Adds a step to a flow, rejecting dependencies on unknown steps and any cycle
+    /// the new step would introduce in the flow graph, or through its sub-flow, as well
+    /// as an insertion leaving an error handler or route target of another step behind.
     pub async fn add_flow_step(
         db: &DatabaseConnection,
         flow_id: String,
-        task_id: String,
+        payload: CreateFlowStepPayload,
+    ) -> Result<flow_step::Model, String> {
+        let mut steps = FlowStepRepository::get_steps_for_flow(db, flow_id.clone())
+            .await
+            .map_err(|e| format!("Failed to fetch flow steps: {}", e))?;
+
+        // Same ordering as the repository, step orders being unique
+        Self::check_step_order(&steps, None, payload.step_order)?;
+        let step_type = payload
+            .step_type
+            .unwrap_or_else(|| STEP_TYPE_TASK.to_string());
+        let new_step = flow_step::Model {
+            id: "<new step>".to_string(),
+            flow_id: flow_id.clone(),
+            task_id: payload.task_id,
+            step_type,
+            step_order: payload.step_order,
+            config: payload.config,
+            name: payload.name,
+            depends_on: payload.depends_on.map(|deps| serde_json::json!(deps)),
+            created_at: None,
+        };
+        steps.push(new_step.clone());
+        steps.sort_by_key(|s| s.step_order);
+        let index = steps
+            .iter()
+            .position(|s| s.step_order == new_step.step_order)
+            .unwrap_or_default();
+        // Inserting the step may change the implicit dependencies of the steps after it
+        let graph = FlowGraph::from_steps(&steps)?;
+        Self::check_handlers(&graph, &steps)?;
+
+        Self::check_step(
+            db,
+            &flow_id,
+            &graph,
+            index,
+            &new_step.step_type,
+            new_step.task_id.is_some(),
+            new_step.config.as_ref(),
+        )
+        .await?;
+        Self::check_tasks(db, &new_step).await?;
+
+        FlowStepRepository::create(
+            db,
+            flow_id,
+            new_step.task_id,
+            new_step.step_type,
+            new_step.step_order,
+            new_step.config,
+            new_step.name,
+            new_step.depends_on,
+        )
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    pub async fn update_flow(
+        db: &DatabaseConnection,
+        id: String,
+        payload: UpdateFlowPayload,
+    ) -> Result<Option<flow::Model>, String> {
+        let flow = match FlowRepository::find_by_id(db, id)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            Some(flow) => flow,
+            None => return Ok(None),
+        };
+        if payload
+            .name
+            .as_ref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return Err("The flow name cannot be empty".to_string());
+        }
+
+        let mut active: flow::ActiveModel = flow.into();
+        if let Some(name) = payload.name {
+            active.name = Set(name);
+        }
+        if let Some(description) = payload.description {
+            active.description = Set(Some(description).filter(|d| !d.is_empty()));
+        }
+        active.updated_at = Set(Some(chrono::Utc::now().into()));
+        FlowRepository::update(db, active)
+            .await
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Deletes a flow with its steps, versions and executions. Refuses flows run as a
+    /// sub-flow by other flows, or with executions in progress. Returns `false` when the
+    /// flow does not exist.
+    pub async fn delete_flow(db: &DatabaseConnection, id: String) -> Result<bool, String> {
+        if FlowRepository::find_by_id(db, id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Ok(false);
+        }
+        let callers = FlowStepRepository::find_sub_flow_callers(db, id.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some(caller) = callers.first() {
+            return Err(format!(
+                "Flow {} runs this flow as a sub-flow (step {})",
+                caller.flow_id,
+                caller.name.as_ref().unwrap_or(&caller.id)
+            ));
+        }
+        if FlowExecutionRepository::has_active(db, id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            return Err("The flow has executions in progress".to_string());
+        }
+
+        FlowRepository::delete(db, id)
+            .await
+            .map(|deleted| deleted > 0)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Changes a step, validated like a new step against the other steps of the flow.
+    /// Returns `None` when the flow has no such step.
+    pub async fn update_flow_step(
+        db: &DatabaseConnection,
+        flow_id: String,
+        step_id: String,
+        payload: UpdateFlowStepPayload,
+    ) -> Result<Option<flow_step::Model>, String> {
+        let mut steps = FlowStepRepository::get_steps_for_flow(db, flow_id.clone())
+            .await
+            .map_err(|e| format!("Failed to fetch flow steps: {}", e))?;
+        let step = match steps.iter_mut().find(|s| s.id == step_id) {
+            Some(step) => step,
+            None => return Ok(None),
+        };
+
+        let moved_to = Self::apply_step_changes(step, payload);
+        let updated = step.clone();
+
+        if let Some(step_order) = moved_to {
+            Self::check_step_order(&steps, Some(&updated.id), step_order)?;
+        }
+        steps.sort_by(|a, b| (a.step_order, &a.id).cmp(&(b.step_order, &b.id)));
+        let graph = FlowGraph::from_steps(&steps)?;
+        Self::check_handlers(&graph, &steps)?;
+        let index = steps
+            .iter()
+            .position(|s| s.id == updated.id)
+            .unwrap_or_default();
+        Self::check_step(
+            db,
+            &flow_id,
+            &graph,
+            index,
+            &updated.step_type,
+            updated.task_id.is_some(),
+            updated.config.as_ref(),
+        )
+        .await?;
+        Self::check_tasks(db, &updated).await?;
+
+        let mut active: flow_step::ActiveModel = updated.into();
+        active = active.reset_all();
+        FlowStepRepository::update(db, active)
+            .await
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Deletes a step, refusing it while other steps depend on it or name it as their
+    /// error handler or route target. Returns `false` when the flow has no such step.
+    pub async fn delete_flow_step(
+        db: &DatabaseConnection,
+        flow_id: String,
+        step_id: String,
+    ) -> Result<bool, String> {
+        let mut steps = FlowStepRepository::get_steps_for_flow(db, flow_id)
+            .await
+            .map_err(|e| format!("Failed to fetch flow steps: {}", e))?;
+        let position = match steps.iter().position(|s| s.id == step_id) {
+            Some(position) => position,
+            None => return Ok(false),
+        };
+        Self::check_removal(&mut steps, position)?;
+
+        FlowStepRepository::delete(db, step_id)
+            .await
+            .map(|deleted| deleted > 0)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Rewrites the `step_order` of all the steps of a flow at once. Returns `None` when
+    /// the flow does not exist.
+    pub async fn reorder_flow_steps(
+        db: &DatabaseConnection,
+        flow_id: String,
+        payload: ReorderFlowStepsPayload,
+    ) -> Result<Option<Vec<flow_step::Model>>, String> {
+        if FlowRepository::find_by_id(db, flow_id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let steps = FlowStepRepository::get_steps_for_flow(db, flow_id.clone())
+            .await
+            .map_err(|e| format!("Failed to fetch flow steps: {}", e))?;
+
+        Self::reordered_steps(&steps, &payload.step_ids)?;
+
+        FlowStepRepository::reorder(db, flow_id.clone(), payload.step_ids)
+            .await
+            .map_err(|e| format!("Failed to reorder the steps: {}", e))?;
+        FlowStepRepository::get_steps_for_flow(db, flow_id)
+            .await
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Copies a step at the end of the flow, with the same task, config and dependencies,
+    /// named `<name>-copy` when the step has a name. A step with an error handler cannot
+    /// be copied, the handler depends on the original step only. Returns `None` when the flow has no
+    /// such step.
+    pub async fn duplicate_flow_step(
+        db: &DatabaseConnection,
+        flow_id: String,
+        step_id: String,
+    ) -> Result<Option<flow_step::Model>, String> {
+        let steps = FlowStepRepository::get_steps_for_flow(db, flow_id.clone())
+            .await
+            .map_err(|e| format!("Failed to fetch flow steps: {}", e))?;
+        let step = match steps.iter().find(|s| s.id == step_id) {
+            Some(step) => step.clone(),
+            None => return Ok(None),
+        };
+
+        let copy = Self::copy_step(&steps, &step)?;
+
+        FlowStepRepository::create(
+            db,
+            flow_id,
+            copy.task_id,
+            copy.step_type,
+            copy.step_order,
+            copy.config,
+            copy.name,
+            copy.depends_on,
+        )
+        .await
+        .map(Some)
+        .map_err(|e| e.to_string())
+    }
+
+    // Applies the changes of an update to a step, returning its new step order when it
+    // moved. Omitted fields are kept and null clears the nullable ones.
+    fn apply_step_changes(
+        step: &mut flow_step::Model,
+        payload: UpdateFlowStepPayload,
+    ) -> Option<i32> {
+        if let Some(task_id) = payload.task_id {
+            step.task_id = task_id;
+        }
+        if let Some(step_type) = payload.step_type {
+            step.step_type = step_type;
+        }
+        let moved_to = payload
+            .step_order
+            .filter(|step_order| *step_order != step.step_order);
+        if let Some(step_order) = payload.step_order {
+            step.step_order = step_order;
+        }
+        if let Some(config) = payload.config {
+            step.config = config;
+        }
+        if let Some(name) = payload.name {
+            step.name = name;
+        }
+        if let Some(depends_on) = payload.depends_on {
+            step.depends_on = depends_on.map(|deps| serde_json::json!(deps));
+        }
+        moved_to
+    }
+
+    // Removes the step at `position`, rejecting it while it is the error handler, a route
+    // target or an upstream step of another step
+    fn check_removal(steps: &mut Vec<flow_step::Model>, position: usize) -> Result<(), String> {
+        let step = steps.remove(position);
+        let label = step.name.as_ref().unwrap_or(&step.id);
+        let names_step =
+            |reference: &String| *reference == step.id || Some(reference) == step.name.as_ref();
+        for other in steps.iter() {
+            let role = if other.step_type == STEP_TYPE_ROUTER {
+                RouterConfig::from_config(other.config.as_ref())
+                    .ok()
+                    .filter(|router| router.targets().iter().any(names_step))
+                    .map(|_| "a route target")
+            } else {
+                ErrorPolicy::from_config(other.config.as_ref())
+                    .ok()
+                    .flatten()
+                    .and_then(|policy| policy.handler)
+                    .filter(names_step)
+                    .map(|_| "the error handler")
+            };
+            if let Some(role) = role {
+                return Err(format!(
+                    "Step {} cannot be removed: it is {} of step {}",
+                    label,
+                    role,
+                    other.name.as_ref().unwrap_or(&other.id)
+                ));
+            }
+        }
+        FlowGraph::from_steps(steps)
+            .map_err(|e| format!("Step {} cannot be removed: {}", label, e))?;
+        Ok(())
+    }
+
+    // The steps in the order of `step_ids`, which must list every step of the flow once
+    fn reordered_steps(
+        steps: &[flow_step::Model],
+        step_ids: &[String],
+    ) -> Result<Vec<flow_step::Model>, String> {
+        let mut reordered = Vec::with_capacity(steps.len());
+        for (i, id) in step_ids.iter().enumerate() {
+            let mut step = steps
+                .iter()
+                .find(|s| &s.id == id)
+                .cloned()
+                .ok_or_else(|| format!("Step {} is not a step of the flow", id))?;
+            if reordered.iter().any(|s: &flow_step::Model| &s.id == id) {
+                return Err(format!("Step {} is listed several times", id));
+            }
+            step.step_order = i as i32 + 1;
+            reordered.push(step);
+        }
+        if reordered.len() != steps.len() {
+            return Err(format!(
+                "Every step of the flow must be listed ({} of {})",
+                reordered.len(),
+                steps.len()
+            ));
+        }
+        let graph = FlowGraph::from_steps(&reordered)?;
+        Self::check_handlers(&graph, &reordered)?;
+        Ok(reordered)
+    }
+
+    // Copy of a step placed after the last step. Implicit dependencies are resolved, the
+    // copy waits for the same steps as the original and not for the last step.
+    fn copy_step(
+        steps: &[flow_step::Model],
+        step: &flow_step::Model,
+    ) -> Result<flow_step::Model, String> {
+        let name = step.name.as_ref().map(|name| {
+            let taken =
+                |candidate: &String| steps.iter().any(|s| s.name.as_ref() == Some(candidate));
+            let mut candidate = format!("{}-copy", name);
+            let mut n = 2;
+            while taken(&candidate) {
+                candidate = format!("{}-copy-{}", name, n);
+                n += 1;
+            }
+            candidate
+        });
+        let graph = FlowGraph::from_steps(steps)?;
+        let index = steps
+            .iter()
+            .position(|s| s.id == step.id)
+            .ok_or_else(|| format!("Step {} is not a step of the flow", step.id))?;
+        let depends_on: Vec<&String> = graph.dependencies[index]
+            .iter()
+            .map(|dep| &graph.labels[*dep])
+            .collect();
+
+        let copy = flow_step::Model {
+            id: "<copy>".to_string(),
+            name,
+            step_order: steps.iter().map(|s| s.step_order).max().unwrap_or(0) + 1,
+            depends_on: Some(serde_json::json!(depends_on)),
+            ..step.clone()
+        };
+        let mut with_copy = steps.to_vec();
+        with_copy.push(copy.clone());
+        Self::check_handlers(&FlowGraph::from_steps(&with_copy)?, &with_copy)?;
+        Ok(copy)
+    }
+
+    // Rejects a step order already used by another step of the flow: the order decides
+    // the implicit dependencies and must not be ambiguous.
+    fn check_step_order(
+        steps: &[flow_step::Model],
+        step_id: Option<&str>,
         step_order: i32,
-        config: Option<serde_json::Value>,
-    ) -> Result<flow_step::Model, DbErr> {
-        FlowStepRepository::create(db, flow_id, task_id, step_order, config).await
+    ) -> Result<(), String> {
+        match steps
+            .iter()
+            .find(|s| s.step_order == step_order && Some(s.id.as_str()) != step_id)
+        {
+            Some(other) => Err(format!(
+                "step_order {} is already used by step {}",
+                step_order,
+                other.name.as_deref().unwrap_or(&other.id)
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Rejects an error handler or a route target that is no longer a step depending on
+    // its step, after another step changed
+    fn check_handlers(graph: &FlowGraph, steps: &[flow_step::Model]) -> Result<(), String> {
+        for (index, step) in steps.iter().enumerate() {
+            if step.step_type == STEP_TYPE_ROUTER {
+                if let Ok(router) = RouterConfig::from_config(step.config.as_ref()) {
+                    router.check_targets(graph, index)?;
+                }
+            } else if let Ok(Some(policy)) = ErrorPolicy::from_config(step.config.as_ref()) {
+                policy.handler_index(graph, index)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Rejects a step running or falling back on a task that does not exist
+    async fn check_tasks(db: &DatabaseConnection, step: &flow_step::Model) -> Result<(), String> {
+        let fallback = ErrorPolicy::from_config(step.config.as_ref())
+            .ok()
+            .flatten()
+            .and_then(|policy| policy.fallback_task_id);
+        for task_id in step.task_id.iter().chain(&fallback) {
+            match AgentTaskRepository::find_by_id(db, task_id.clone()).await {
+                Ok(Some(_)) => {}
+                Ok(None) => return Err(format!("Task {} not found", task_id)),
+                Err(e) => return Err(format!("Database error fetching tasks: {}", e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects a step whose type does not match its task and config, whose sub-flow
+    /// is unknown or would create a cycle, or whose error handler or route targets are
+    /// not steps depending on it. `index` is the node of the step in `graph`. Its task and
+    /// fallback task are not looked up: an import may create them along with the step.
+    pub async fn check_step(
+        db: &DatabaseConnection,
+        flow_id: &str,
+        graph: &FlowGraph,
+        index: usize,
+        step_type: &str,
+        has_task: bool,
+        config: Option<&serde_json::Value>,
+    ) -> Result<(), String> {
+        match step_type {
+            STEP_TYPE_TASK => {
+                if !has_task {
+                    return Err("Task steps require a task_id".to_string());
+                }
+            }
+            STEP_TYPE_ROUTER => {
+                RouterConfig::from_config(config)?.check_targets(graph, index)?;
+            }
+            STEP_TYPE_MAP => {
+                let config = MapConfig::from_config(config, has_task)?;
+                if let Some(sub_flow) = &config.flow_id {
+                    SubFlowService::check_reference(db, flow_id, sub_flow).await?;
+                }
+            }
+            STEP_TYPE_FLOW => {
+                if has_task {
+                    return Err("Sub-flow steps run a flow, not a task_id".to_string());
+                }
+                let config = SubFlowConfig::from_config(config)?;
+                SubFlowService::check_reference(db, flow_id, &config.flow_id).await?;
+            }
+            STEP_TYPE_APPROVAL => {
+                if has_task {
+                    return Err("Approval steps do not run a task_id".to_string());
+                }
+                ApprovalConfig::from_config(config)?;
+            }
+            other => return Err(format!("Unknown step type {}", other)),
+        }
+
+        if let Some(policy) = ErrorPolicy::from_config(config)? {
+            if matches!(step_type, STEP_TYPE_ROUTER | STEP_TYPE_APPROVAL) {
+                return Err(format!("{} steps do not take an error policy", step_type));
+            }
+            if policy.fallback_task_id.is_some() && step_type != STEP_TYPE_TASK {
+                return Err("Only task steps run a fallback task".to_string());
+            }
+            policy.handler_index(graph, index)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the contracts of adjacent steps are compatible, without running the flow.
+    /// Returns `None` when the flow does not exist.
+    pub async fn check_flow(
+        db: &DatabaseConnection,
+        flow_id: String,
+    ) -> Result<Option<FlowCheck>, String> {
+        if FlowRepository::find_by_id(db, flow_id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Ok(None);
+        }
+
+        let steps = FlowStepRepository::get_steps_for_flow(db, flow_id)
+            .await
+            .map_err(|e| format!("Failed to fetch flow steps: {}", e))?;
+        let task_ids: Vec<String> = steps.iter().filter_map(|s| s.task_id.clone()).collect();
+        let tasks: HashMap<String, agent_task::Model> = agent_task::Entity::find()
+            .filter(agent_task::Column::Id.is_in(task_ids))
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error fetching tasks: {}", e))?
+            .into_iter()
+            .map(|task| (task.id.clone(), task))
+            .collect();
+
+        contract::check_flow(&steps, &tasks).map(Some)
+    }
+
+    pub async fn get_execution(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_execution::Model>, DbErr> {
+        FlowExecutionRepository::find_by_id(db, id).await
+    }
+
+    /// Lists the step records of an execution in start order, `None` when the execution
+    /// does not exist.
+    pub async fn get_step_executions(
+        db: &DatabaseConnection,
+        execution_id: String,
+    ) -> Result<Option<Vec<flow_step_execution::Model>>, DbErr> {
+        if FlowExecutionRepository::find_by_id(db, execution_id.clone())
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        FlowStepExecutionRepository::find_by_execution(db, execution_id)
+            .await
+            .map(Some)
+    }
+
+    /// Lists the sub-flow executions started by an execution, `None` when the execution
+    /// does not exist.
+    pub async fn get_child_executions(
+        db: &DatabaseConnection,
+        execution_id: String,
+    ) -> Result<Option<Vec<flow_execution::Model>>, DbErr> {
+        if FlowExecutionRepository::find_by_id(db, execution_id.clone())
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        FlowExecutionRepository::find_children(db, execution_id)
+            .await
+            .map(Some)
     }
 
     pub async fn get_flow_steps(
@@ -85,3 +675,104 @@ impl Service {
         FlowStepRepository::get_steps_for_flow(db, flow_id).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn step(id: &str, step_order: i32, depends_on: Option<serde_json::Value>) -> flow_step::Model {
+        flow_step::Model {
+            depends_on,
+            ..flow_step::Model::fixture(id)
+                .with_order(step_order)
+                .with_config(json!({ "template": "{{input}}" }))
+        }
+    }
+
+    fn with_handler(mut step: flow_step::Model, handler: &str) -> flow_step::Model {
+        step.config = Some(json!({ "error_policy": { "handler": handler } }));
+        step
+    }
+
+    #[test]
+    fn patches_keep_omitted_fields_and_clear_nulls() {
+        let mut patched = step("b", 2, Some(json!(["a"])));
+        let payload: UpdateFlowStepPayload =
+            serde_json::from_value(json!({ "name": null, "step_order": 2 })).unwrap();
+        assert_eq!(Service::apply_step_changes(&mut patched, payload), None);
+        assert_eq!(patched.name, None);
+        assert_eq!(patched.task_id.as_deref(), Some("task"));
+        assert_eq!(patched.depends_on, Some(json!(["a"])));
+
+        let payload: UpdateFlowStepPayload =
+            serde_json::from_value(json!({ "depends_on": null, "step_order": 5 })).unwrap();
+        assert_eq!(Service::apply_step_changes(&mut patched, payload), Some(5));
+        assert_eq!(patched.depends_on, None);
+        assert!(patched.config.is_some());
+    }
+
+    #[test]
+    fn reorders_every_step_once() {
+        let steps = vec![step("a", 1, None), step("b", 2, Some(json!([])))];
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        let reordered = Service::reordered_steps(&steps, &ids(&["b", "a"])).unwrap();
+        assert_eq!(reordered[0].id, "b");
+        assert_eq!(reordered[1].step_order, 2);
+        assert!(Service::reordered_steps(&steps, &ids(&["a"])).is_err());
+        assert!(Service::reordered_steps(&steps, &ids(&["a", "a"])).is_err());
+        assert!(Service::reordered_steps(&steps, &ids(&["a", "c"])).is_err());
+
+        // The handler depends on its step through the step order only
+        let handled = vec![with_handler(step("a", 1, None), "b"), step("b", 2, None)];
+        assert!(Service::reordered_steps(&handled, &ids(&["b", "a"])).is_err());
+    }
+
+    #[test]
+    fn rejects_removing_upstream_steps_and_handlers() {
+        let mut steps = vec![step("a", 1, None), step("b", 2, Some(json!(["a"])))];
+        assert!(Service::check_removal(&mut steps.clone(), 0).is_err());
+        assert!(Service::check_removal(&mut steps, 1).is_ok());
+        assert_eq!(steps.len(), 1);
+
+        let mut handled = vec![
+            with_handler(step("a", 1, None), "notify"),
+            step("notify", 2, Some(json!(["a"]))),
+        ];
+        let err = Service::check_removal(&mut handled, 1).unwrap_err();
+        assert!(err.contains("error handler of step a"));
+
+        let mut routed = vec![
+            step("route", 1, None)
+                .with_type(STEP_TYPE_ROUTER)
+                .with_config(json!({ "routes": [], "default": "b" })),
+            step("a", 2, Some(json!(["route"]))),
+            step("b", 3, Some(json!(["route"]))),
+        ];
+        assert!(Service::check_removal(&mut routed.clone(), 1).is_ok());
+        let err = Service::check_removal(&mut routed, 2).unwrap_err();
+        assert!(err.contains("route target of step route"));
+    }
+
+    #[test]
+    fn copies_resolve_implicit_dependencies() {
+        let steps = vec![
+            step("a", 1, None),
+            step("b", 2, None),
+            step("c", 3, Some(json!([]))),
+        ];
+        let copy = Service::copy_step(&steps, &steps[1]).unwrap();
+        assert_eq!(copy.name.as_deref(), Some("b-copy"));
+        assert_eq!(copy.step_order, 4);
+        assert_eq!(copy.depends_on, Some(json!(["a"])));
+        let root = Service::copy_step(&steps, &steps[0]).unwrap();
+        assert_eq!(root.depends_on, Some(json!([])));
+
+        let handled = vec![
+            with_handler(step("a", 1, None), "notify"),
+            step("notify", 2, Some(json!(["a"]))),
+        ];
+        assert!(Service::copy_step(&handled, &handled[0]).is_err());
+    }
+}
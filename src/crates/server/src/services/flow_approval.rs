@@ -0,0 +1,380 @@
+use crate::models::flow_approval::{
+    self, DecideApprovalPayload, APPROVAL_APPROVED, APPROVAL_PENDING, APPROVAL_REJECTED,
+    APPROVAL_TIMED_OUT,
+};
+use crate::models::flow_execution::{StepProgress, STEP_COMPLETED, STEP_FAILED};
+use crate::models::flow_step;
+use crate::repositories::{
+    flow_approval::Repository as FlowApprovalRepository,
+    flow_execution::Repository as FlowExecutionRepository,
+    flow_step_execution::Repository as FlowStepExecutionRepository,
+};
+use crate::services::flow_template::TemplateContext;
+use sea_orm::{DatabaseConnection, DbErr};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Expired approvals are rejected, failing their execution (default).
+pub const ON_TIMEOUT_REJECT: &str = "reject";
+/// Expired approvals pass the submitted data on unchanged.
+pub const ON_TIMEOUT_APPROVE: &str = "approve";
+
+/*
+ * Config of an approval step, e.g.
+ * { "message": "Review the reply to {{ flow.input.customer }}", "timeout_secs": 3600,
+ *   "on_timeout": "reject" }
+ * The step input is submitted for review, the approved (possibly edited) data becomes the
+ * step output. Without `timeout_secs` the approval waits until a reviewer decides.
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApprovalConfig {
+    /* Instructions for the reviewer, a template rendered like step prompts */
+    pub message: Option<String>,
+
+    pub timeout_secs: Option<i64>,
+
+    /* "reject" (default) or "approve" */
+    #[serde(default = "default_on_timeout")]
+    pub on_timeout: String,
+}
+
+fn default_on_timeout() -> String {
+    ON_TIMEOUT_REJECT.to_string()
+}
+
+impl ApprovalConfig {
+    pub fn from_config(config: Option<&Value>) -> Result<Self, String> {
+        let parsed: Self = match config {
+            Some(config) if !config.is_null() => serde_json::from_value(config.clone())
+                .map_err(|e| format!("Invalid approval config: {}", e))?,
+            _ => Self {
+                message: None,
+                timeout_secs: None,
+                on_timeout: default_on_timeout(),
+            },
+        };
+
+        if parsed.timeout_secs.is_some_and(|secs| secs <= 0) {
+            return Err("timeout_secs must be greater than zero".to_string());
+        }
+        if ![ON_TIMEOUT_REJECT, ON_TIMEOUT_APPROVE].contains(&parsed.on_timeout.as_str()) {
+            return Err(format!(
+                "Unknown approval timeout policy {}",
+                parsed.on_timeout
+            ));
+        }
+        Ok(parsed)
+    }
+}
+
+pub struct Service;
+
+impl Service {
+    /// Submits the input of an approval step for review, linked to the record of the step.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn request(
+        db: &DatabaseConnection,
+        execution_id: &str,
+        flow_id: &str,
+        step: &flow_step::Model,
+        step_name: &str,
+        step_execution_id: Option<String>,
+        input: &Value,
+        context: &TemplateContext,
+    ) -> Result<flow_approval::Model, String> {
+        let config = ApprovalConfig::from_config(step.config.as_ref())?;
+        let message = match &config.message {
+            Some(message) => Some(context.render(message, input)?),
+            None => None,
+        };
+        let expires_at = config
+            .timeout_secs
+            .map(|secs| (chrono::Utc::now() + chrono::Duration::seconds(secs)).into());
+
+        FlowApprovalRepository::create(
+            db,
+            execution_id.to_string(),
+            flow_id.to_string(),
+            step.id.clone(),
+            step_name.to_string(),
+            step_execution_id,
+            message,
+            input.clone(),
+            config.on_timeout,
+            expires_at,
+        )
+        .await
+        .map_err(|e| format!("Failed to request approval: {}", e))
+    }
+
+    pub async fn list_pending(db: &DatabaseConnection) -> Result<Vec<flow_approval::Model>, DbErr> {
+        FlowApprovalRepository::find_pending(db).await
+    }
+
+    pub async fn get_approval(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_approval::Model>, DbErr> {
+        FlowApprovalRepository::find_by_id(db, id).await
+    }
+
+    /// Approves a Pending approval, passing on the edited data when given, the submitted
+    /// data otherwise. The execution is queued again once none of its approvals is left
+    /// waiting. Returns `None` when the approval does not exist.
+    pub async fn approve(
+        db: &DatabaseConnection,
+        id: String,
+        payload: DecideApprovalPayload,
+    ) -> Result<Option<flow_approval::Model>, String> {
+        let approval = match Self::pending(db, id.clone()).await? {
+            Some(approval) => approval,
+            None => return Ok(None),
+        };
+        let response = payload.data.or(approval.data);
+        Self::decide(
+            db,
+            id,
+            APPROVAL_APPROVED,
+            response,
+            payload.reviewer,
+            payload.comment,
+        )
+        .await
+        .map(Some)
+    }
+
+    /// Rejects a Pending approval, failing its execution. Returns `None` when the
+    /// approval does not exist.
+    pub async fn reject(
+        db: &DatabaseConnection,
+        id: String,
+        payload: DecideApprovalPayload,
+    ) -> Result<Option<flow_approval::Model>, String> {
+        if Self::pending(db, id.clone()).await?.is_none() {
+            return Ok(None);
+        }
+        Self::decide(
+            db,
+            id,
+            APPROVAL_REJECTED,
+            None,
+            payload.reviewer,
+            payload.comment,
+        )
+        .await
+        .map(Some)
+    }
+
+    /// Applies the timeout policy of the approvals whose timeout is reached.
+    /// Returns the number of approvals settled.
+    pub async fn expire(db: &DatabaseConnection) -> Result<usize, String> {
+        let expired = FlowApprovalRepository::find_expired(db)
+            .await
+            .map_err(|e| format!("Failed to fetch expired approvals: {}", e))?;
+
+        let mut settled = 0;
+        for approval in expired {
+            let decided = if approval.on_timeout == ON_TIMEOUT_APPROVE {
+                Self::decide(
+                    db,
+                    approval.id,
+                    APPROVAL_APPROVED,
+                    approval.data,
+                    None,
+                    Some("Approved on timeout".to_string()),
+                )
+                .await
+            } else {
+                Self::decide(
+                    db,
+                    approval.id,
+                    APPROVAL_TIMED_OUT,
+                    None,
+                    None,
+                    Some("Approval timed out".to_string()),
+                )
+                .await
+            };
+            // Approvals decided by a reviewer in the meantime are left as they are
+            if decided.is_ok() {
+                settled += 1;
+            }
+        }
+        Ok(settled)
+    }
+
+    /// Moves an execution paused on approvals on: Failed after a rejection, queued again
+    /// once none of its approvals is left waiting. Executions still running their other
+    /// branches are left alone, they settle themselves once they pause.
+    pub async fn settle(
+        db: &DatabaseConnection,
+        execution_id: &str,
+        approvals: &[flow_approval::Model],
+    ) -> Result<(), DbErr> {
+        if let Some(rejected) = approvals
+            .iter()
+            .find(|a| a.status == APPROVAL_REJECTED || a.status == APPROVAL_TIMED_OUT)
+        {
+            FlowExecutionRepository::transition(
+                db,
+                execution_id.to_string(),
+                "WaitingForApproval",
+                "Failed",
+                Some(json!({
+                    "error": Self::rejection(rejected),
+                    "step_id": rejected.step_id,
+                    "approval_id": rejected.id,
+                })),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        if approvals.iter().all(|a| a.status != APPROVAL_PENDING)
+            && FlowApprovalRepository::count_pending(db, execution_id.to_string()).await? == 0
+        {
+            FlowExecutionRepository::transition(
+                db,
+                execution_id.to_string(),
+                "WaitingForApproval",
+                "Pending",
+                None,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn pending(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_approval::Model>, String> {
+        match FlowApprovalRepository::find_by_id(db, id)
+            .await
+            .map_err(|e| format!("Database error fetching approval: {}", e))?
+        {
+            Some(approval) if approval.status != APPROVAL_PENDING => Err(format!(
+                "Approval {} is already {}",
+                approval.id, approval.status
+            )),
+            approval => Ok(approval),
+        }
+    }
+
+    // Settles an approval once, then its step record and its execution.
+    async fn decide(
+        db: &DatabaseConnection,
+        id: String,
+        status: &str,
+        response: Option<Value>,
+        reviewer: Option<String>,
+        comment: Option<String>,
+    ) -> Result<flow_approval::Model, String> {
+        let approval =
+            FlowApprovalRepository::decide(db, id.clone(), status, response, reviewer, comment)
+                .await
+                .map_err(|e| format!("Failed to decide approval: {}", e))?
+                .ok_or_else(|| format!("Approval {} was already decided", id))?;
+
+        let approved = approval.status == APPROVAL_APPROVED;
+        let (step_status, error) = if approved {
+            (STEP_COMPLETED, None)
+        } else {
+            (STEP_FAILED, Some(Self::rejection(&approval)))
+        };
+        if let Some(record_id) = &approval.step_execution_id {
+            if let Ok(Some(record)) =
+                FlowStepExecutionRepository::find_by_id(db, record_id.clone()).await
+            {
+                let _ = FlowStepExecutionRepository::finish(
+                    db,
+                    record,
+                    step_status,
+                    approval.response.clone(),
+                    error.clone(),
+                    0,
+                    None,
+                    None,
+                )
+                .await;
+            }
+        }
+        Self::update_progress(db, &approval, step_status, error).await;
+
+        if !approved {
+            let _ = FlowApprovalRepository::cancel_pending(
+                db,
+                approval.execution_id.clone(),
+                "Another approval of the execution was rejected",
+            )
+            .await;
+        }
+        Self::settle(db, &approval.execution_id, std::slice::from_ref(&approval))
+            .await
+            .map_err(|e| format!("Failed to update execution: {}", e))?;
+        Ok(approval)
+    }
+
+    fn rejection(approval: &flow_approval::Model) -> String {
+        let reason = if approval.status == APPROVAL_TIMED_OUT {
+            "Approval timed out"
+        } else {
+            "Rejected by reviewer"
+        };
+        match &approval.comment {
+            Some(comment) if approval.status == APPROVAL_REJECTED => {
+                format!("{}: {}", reason, comment)
+            }
+            _ => reason.to_string(),
+        }
+    }
+
+    // Reflects the decision in the step progress of the execution.
+    async fn update_progress(
+        db: &DatabaseConnection,
+        approval: &flow_approval::Model,
+        status: &str,
+        error: Option<String>,
+    ) {
+        let execution =
+            match FlowExecutionRepository::find_by_id(db, approval.execution_id.clone()).await {
+                Ok(Some(execution)) => execution,
+                _ => return,
+            };
+        let mut steps: Vec<StepProgress> = match execution
+            .steps
+            .and_then(|steps| serde_json::from_value(steps).ok())
+        {
+            Some(steps) => steps,
+            None => return,
+        };
+        for step in steps.iter_mut().filter(|s| s.step_id == approval.step_id) {
+            step.status = status.to_string();
+            step.error = error.clone();
+            step.completed_at = Some(chrono::Utc::now().to_rfc3339());
+        }
+        let steps = serde_json::to_value(&steps).unwrap_or_default();
+        let _ = FlowExecutionRepository::update_steps(db, execution.id, steps).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_defaults_to_rejecting_on_timeout() {
+        let config = ApprovalConfig::from_config(None).unwrap();
+        assert_eq!(config.on_timeout, ON_TIMEOUT_REJECT);
+        assert!(config.timeout_secs.is_none());
+
+        let config = ApprovalConfig::from_config(Some(
+            &json!({ "timeout_secs": 60, "on_timeout": "approve" }),
+        ))
+        .unwrap();
+        assert_eq!(config.timeout_secs, Some(60));
+
+        assert!(ApprovalConfig::from_config(Some(&json!({ "timeout_secs": 0 }))).is_err());
+        assert!(ApprovalConfig::from_config(Some(&json!({ "on_timeout": "skip" }))).is_err());
+    }
+}
@@ -0,0 +1,1024 @@
+use crate::models::flow_document::{
+    AgentDefinition, FlowDefinition, FlowDocument, FlowImportReport, StepDefinition,
+    TaskDefinition, DOCUMENT_FORMAT,
+};
+use crate::models::flow_step::STEP_TYPE_ROUTER;
+use crate::models::flow_version::FieldChange;
+use crate::models::{agent, agent_task, flow, flow_step};
+use crate::repositories::{
+    agent::Repository as AgentRepository, agent_task::Repository as AgentTaskRepository,
+    flow::Repository as FlowRepository, flow_step::Repository as FlowStepRepository,
+};
+use crate::services::agentic::{self, Service as AgenticService};
+use crate::services::contract;
+use crate::services::fallback::FallbackPolicy;
+use crate::services::flow::Service as FlowService;
+use crate::services::flow_graph::FlowGraph;
+use crate::services::flow_subflow::sub_flow_of;
+use crate::services::flow_version::{self, Service as FlowVersionService};
+use crate::services::response_cache::CachePolicy;
+use regex::Regex;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
+    Set, TransactionTrait,
+};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Parses a flow document, YAML or JSON (JSON being valid YAML).
+pub fn parse_document(text: &str) -> Result<FlowDocument, String> {
+    let document: FlowDocument =
+        serde_yaml::from_str(text).map_err(|e| format!("Invalid flow document: {}", e))?;
+    if document.format != DOCUMENT_FORMAT {
+        return Err(format!(
+            "Unsupported document format {}, expected {}",
+            document.format, DOCUMENT_FORMAT
+        ));
+    }
+    Ok(document)
+}
+
+/// Key of a task in documents: `<agent slug>/<task name>`.
+pub fn task_key(agent_slug: &str, task_name: &str) -> String {
+    format!("{}/{}", agent_slug, task_name)
+}
+
+// Replaces the references to other steps (dependencies, router targets, error handler and
+// `steps.<id>` template variables) found in `aliases`
+fn map_references(
+    step_type: &str,
+    config: &mut Option<Value>,
+    depends_on: &mut Option<Vec<String>>,
+    aliases: &HashMap<String, String>,
+) {
+    let alias = |reference: &mut String| {
+        if let Some(target) = aliases.get(reference.as_str()) {
+            *reference = target.clone();
+        }
+    };
+    let alias_targets = |targets: &mut Value| match targets {
+        Value::String(reference) => alias(reference),
+        Value::Array(references) => {
+            for reference in references {
+                if let Value::String(reference) = reference {
+                    alias(reference);
+                }
+            }
+        }
+        _ => {}
+    };
+    if let Some(depends_on) = depends_on {
+        depends_on.iter_mut().for_each(alias);
+    }
+    let config = match config.as_mut() {
+        Some(config) => config,
+        None => return,
+    };
+    alias_templates(config, aliases);
+    if let Some(Value::String(handler)) = config
+        .get_mut("error_policy")
+        .and_then(|policy| policy.get_mut("handler"))
+    {
+        alias(handler);
+    }
+    if step_type != STEP_TYPE_ROUTER {
+        return;
+    }
+
+    if let Some(Value::Array(routes)) = config.get_mut("routes") {
+        routes
+            .iter_mut()
+            .filter_map(|route| route.get_mut("next"))
+            .for_each(alias_targets);
+    }
+    if let Some(default) = config.get_mut("default") {
+        alias_targets(default);
+    }
+}
+
+// Replaces the steps found in `aliases` in the `steps.<id>` and `steps["<id>"]` variables
+// of every template of a step config
+fn alias_templates(value: &mut Value, aliases: &HashMap<String, String>) {
+    match value {
+        Value::String(template) if template.contains("steps") => {
+            let variable =
+                Regex::new(r#"(^|[^\w.])steps(?:\.([A-Za-z_]\w*)|\[\s*["']([^"']+)["']\s*\])"#)
+                    .expect("valid step variable pattern");
+            let identifier = Regex::new(r"^[A-Za-z_]\w*$").expect("valid identifier pattern");
+            let aliased = variable.replace_all(template, |captures: &regex::Captures| {
+                let step = captures
+                    .get(2)
+                    .or(captures.get(3))
+                    .map_or("", |m| m.as_str());
+                match aliases.get(step) {
+                    Some(target) if captures.get(2).is_some() && identifier.is_match(target) => {
+                        format!("{}steps.{}", &captures[1], target)
+                    }
+                    Some(target) => format!("{}steps[\"{}\"]", &captures[1], target),
+                    None => captures[0].to_string(),
+                }
+            });
+            *template = aliased.into_owned();
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| alias_templates(value, aliases)),
+        Value::Object(values) => values
+            .values_mut()
+            .for_each(|value| alias_templates(value, aliases)),
+        _ => {}
+    }
+}
+
+// Fallback task of the error policy of a step config
+fn policy_task_of(config: Option<&Value>) -> Option<&str> {
+    config
+        .and_then(|config| config.get("error_policy"))
+        .and_then(|policy| policy.get("fallback_task_id"))
+        .and_then(Value::as_str)
+}
+
+// Replaces the fallback task of the error policy of a step config through `tasks`
+fn map_policy_task(
+    config: &mut Option<Value>,
+    tasks: &HashMap<String, String>,
+) -> Result<(), String> {
+    if let Some(Value::String(task)) = config
+        .as_mut()
+        .and_then(|config| config.get_mut("error_policy"))
+        .and_then(|policy| policy.get_mut("fallback_task_id"))
+    {
+        *task = tasks
+            .get(task.as_str())
+            .cloned()
+            .ok_or_else(|| format!("Unknown fallback task {}", task))?;
+    }
+    Ok(())
+}
+
+// Calls `task` on every task id and `agent` on every agent id referenced by task settings:
+// the tools of agentic tasks and the fallback targets
+fn visit_settings(
+    settings: &mut Value,
+    task: &mut impl FnMut(&mut String),
+    agent: &mut impl FnMut(&mut String),
+) {
+    if let Some(Value::Array(tools)) = settings.get_mut("tools") {
+        for tool in tools {
+            if let Value::String(id) = tool {
+                task(id);
+            }
+        }
+    }
+    if let Some(Value::Array(targets)) = settings
+        .get_mut("fallback")
+        .and_then(|fallback| fallback.get_mut("targets"))
+    {
+        for target in targets {
+            if let Some(Value::String(id)) = target.get_mut("task_id") {
+                task(id);
+            }
+            if let Some(Value::String(id)) = target.get_mut("agent_id") {
+                agent(id);
+            }
+        }
+    }
+}
+
+// Tasks and agents referenced by task settings, as written in the settings
+fn settings_references(settings: Option<&Value>) -> (Vec<String>, Vec<String>) {
+    let (mut tasks, mut agents) = (Vec::new(), Vec::new());
+    if let Some(mut settings) = settings.cloned() {
+        visit_settings(&mut settings, &mut |id| tasks.push(id.clone()), &mut |id| {
+            agents.push(id.clone())
+        });
+    }
+    (tasks, agents)
+}
+
+// Task settings with their task and agent references replaced through `tasks` and `agents`:
+// ids by document keys and agent slugs on export, the other way round on import
+fn map_settings(
+    settings: Option<&Value>,
+    tasks: &HashMap<String, String>,
+    agents: &HashMap<String, String>,
+) -> Result<Option<Value>, String> {
+    let mut settings = settings.cloned();
+    let (mut unknown_task, mut unknown_agent) = (None, None);
+    if let Some(settings) = settings.as_mut() {
+        visit_settings(
+            settings,
+            &mut |id| match tasks.get(id.as_str()) {
+                Some(target) => *id = target.clone(),
+                None => unknown_task = Some(id.clone()),
+            },
+            &mut |id| match agents.get(id.as_str()) {
+                Some(target) => *id = target.clone(),
+                None => unknown_agent = Some(id.clone()),
+            },
+        );
+    }
+    if let Some(task) = unknown_task {
+        return Err(format!("Unknown task {}", task));
+    }
+    if let Some(agent) = unknown_agent {
+        return Err(format!("Unknown agent {}", agent));
+    }
+    Ok(settings)
+}
+
+/// Document steps of flow steps, given the document key of every task id (step and fallback
+/// tasks) and the name of every sub-flow id. References to named steps use their name
+/// instead of their id.
+pub fn export_steps(
+    steps: &[flow_step::Model],
+    tasks: &HashMap<String, String>,
+    flows: &HashMap<String, String>,
+) -> Result<Vec<StepDefinition>, String> {
+    let names: HashMap<String, String> = steps
+        .iter()
+        .filter_map(|s| s.name.clone().map(|name| (s.id.clone(), name)))
+        .collect();
+
+    steps
+        .iter()
+        .map(|step| {
+            let task =
+                match &step.task_id {
+                    Some(task_id) => Some(tasks.get(task_id).cloned().ok_or_else(|| {
+                        format!("Task {} of step {} not found", task_id, step.id)
+                    })?),
+                    None => None,
+                };
+            let mut config = step.config.clone();
+            let flow = match sub_flow_of(step) {
+                Some(flow_id) => {
+                    if let Some(Value::Object(config)) = config.as_mut() {
+                        config.remove("flow_id");
+                    }
+                    Some(flows.get(&flow_id).cloned().ok_or_else(|| {
+                        format!("Sub-flow {} of step {} not found", flow_id, step.id)
+                    })?)
+                }
+                None => None,
+            };
+            let mut depends_on = match &step.depends_on {
+                Some(value) if !value.is_null() => Some(
+                    serde_json::from_value::<Vec<String>>(value.clone()).map_err(|_| {
+                        format!(
+                            "depends_on of step {} must be an array of step ids",
+                            step.id
+                        )
+                    })?,
+                ),
+                _ => None,
+            };
+            map_references(&step.step_type, &mut config, &mut depends_on, &names);
+            map_policy_task(&mut config, tasks).map_err(|e| format!("Step {}: {}", step.id, e))?;
+
+            Ok(StepDefinition {
+                id: step.name.is_none().then(|| step.id.clone()),
+                name: step.name.clone(),
+                step_type: step.step_type.clone(),
+                step_order: step.step_order,
+                task,
+                flow,
+                config: config.filter(|c| c.as_object().is_none_or(|c| !c.is_empty())),
+                depends_on,
+            })
+        })
+        .collect()
+}
+
+/// Flow steps of document steps, reusing the ids of the `existing` steps they match
+/// (named steps by name, unnamed ones by id) and resolving tasks (step and fallback tasks)
+/// by document key and sub-flows by name.
+pub fn import_steps(
+    flow_id: &str,
+    definitions: &[StepDefinition],
+    existing: &[flow_step::Model],
+    tasks: &HashMap<String, String>,
+    flows: &HashMap<String, String>,
+) -> Result<Vec<flow_step::Model>, String> {
+    let mut keys = HashSet::new();
+    let mut orders = HashSet::new();
+    let mut aliases = HashMap::new();
+    let mut ids = Vec::new();
+    for definition in definitions {
+        if !orders.insert(definition.step_order) {
+            return Err(format!(
+                "step_order {} is used by several steps",
+                definition.step_order
+            ));
+        }
+        let (key, matched) = match (&definition.name, &definition.id) {
+            (Some(name), _) => (
+                name,
+                existing.iter().find(|s| s.name.as_ref() == Some(name)),
+            ),
+            (None, Some(id)) => (id, existing.iter().find(|s| &s.id == id)),
+            (None, None) => return Err("Every step needs a name or an id".to_string()),
+        };
+        if !keys.insert(key.clone()) {
+            return Err(format!("Several steps are identified as {}", key));
+        }
+        let id = matched
+            .map(|s| s.id.clone())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        if definition.name.is_none() {
+            aliases.insert(key.clone(), id.clone());
+        }
+        ids.push((id, matched));
+    }
+
+    let mut steps = definitions
+        .iter()
+        .zip(ids)
+        .map(|(definition, (id, matched))| {
+            let task_id = match &definition.task {
+                Some(key) => Some(
+                    tasks
+                        .get(key)
+                        .cloned()
+                        .ok_or_else(|| format!("Unknown task {}", key))?,
+                ),
+                None => None,
+            };
+            let mut config = definition.config.clone();
+            if let Some(name) = &definition.flow {
+                let sub_flow = flows
+                    .get(name)
+                    .ok_or_else(|| format!("Unknown sub-flow {}", name))?;
+                match config.get_or_insert_with(|| json!({})) {
+                    Value::Object(config) => {
+                        config.insert("flow_id".to_string(), json!(sub_flow));
+                    }
+                    _ => return Err(format!("Config of step {} must be an object", id)),
+                }
+            }
+            let mut depends_on = definition.depends_on.clone();
+            map_references(
+                &definition.step_type,
+                &mut config,
+                &mut depends_on,
+                &aliases,
+            );
+            map_policy_task(&mut config, tasks).map_err(|e| format!("Step {}: {}", id, e))?;
+
+            Ok(flow_step::Model {
+                id,
+                flow_id: flow_id.to_string(),
+                task_id,
+                step_type: definition.step_type.clone(),
+                step_order: definition.step_order,
+                config,
+                name: definition.name.clone(),
+                depends_on: depends_on.map(|deps| json!(deps)),
+                created_at: matched.and_then(|s| s.created_at),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    steps.sort_by_key(|s| s.step_order);
+    Ok(steps)
+}
+
+// Task with the fields of its definition, its settings referencing tasks and agents by id
+fn defined_task(
+    id: String,
+    agent_id: String,
+    definition: &TaskDefinition,
+    settings: Option<Value>,
+    created_at: Option<sea_orm::prelude::DateTimeWithTimeZone>,
+) -> agent_task::Model {
+    agent_task::Model {
+        id,
+        agent_id,
+        name: definition.name.clone(),
+        description: definition.description.clone(),
+        task_type: definition.task_type.clone(),
+        path: definition.path.clone(),
+        method: definition.method.clone(),
+        input_contract: definition.input_contract.clone(),
+        output_contract: definition.output_contract.clone(),
+        settings,
+        created_at,
+    }
+}
+
+// Rejects a created or updated task as the creation of a task does, its tools being looked
+// up among the other `pending` tasks of the import first
+async fn check_task(
+    db: &DatabaseConnection,
+    task: &agent_task::Model,
+    pending: &HashMap<String, agent_task::Model>,
+) -> Result<(), String> {
+    if task.task_type == agentic::TASK_TYPE {
+        AgenticService::validate_with(db, task.settings.as_ref(), pending).await?;
+    }
+    FallbackPolicy::from_settings(task.settings.as_ref())?;
+    CachePolicy::from_settings(task.settings.as_ref())?;
+    for schema in [&task.input_contract, &task.output_contract]
+        .into_iter()
+        .flatten()
+    {
+        contract::validate_schema(schema)?;
+    }
+    Ok(())
+}
+
+pub struct Service;
+
+impl Service {
+    /// Document of a flow with its draft steps, or the steps of a published version.
+    /// Returns `None` when the flow or the version does not exist.
+    pub async fn export(
+        db: &DatabaseConnection,
+        flow_id: String,
+        version: Option<i32>,
+    ) -> Result<Option<FlowDocument>, String> {
+        let flow = match FlowRepository::find_by_id(db, flow_id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            Some(flow) => flow,
+            None => return Ok(None),
+        };
+        let steps = match version {
+            Some(version) => {
+                match FlowVersionService::get_version(db, flow_id, version)
+                    .await
+                    .map_err(|e| e.to_string())?
+                {
+                    Some(version) => flow_version::snapshot_steps(&version)?,
+                    None => return Ok(None),
+                }
+            }
+            None => FlowStepRepository::get_steps_for_flow(db, flow_id)
+                .await
+                .map_err(|e| format!("Failed to fetch flow steps: {}", e))?,
+        };
+
+        // Tasks of the steps, then the tasks their settings reference, until none is missing
+        let mut tasks: Vec<agent_task::Model> = Vec::new();
+        let mut task_ids: Vec<String> = steps
+            .iter()
+            .flat_map(|s| [s.task_id.as_deref(), policy_task_of(s.config.as_ref())])
+            .flatten()
+            .map(str::to_string)
+            .collect();
+        let mut agent_ids = Vec::new();
+        while !task_ids.is_empty() {
+            let found = agent_task::Entity::find()
+                .filter(agent_task::Column::Id.is_in(task_ids))
+                .all(db)
+                .await
+                .map_err(|e| format!("Database error fetching tasks: {}", e))?;
+            tasks.extend(found.iter().cloned());
+            task_ids = Vec::new();
+            for task in &found {
+                let (referenced, agents) = settings_references(task.settings.as_ref());
+                task_ids.extend(
+                    referenced
+                        .into_iter()
+                        .filter(|id| !tasks.iter().any(|t| &t.id == id)),
+                );
+                agent_ids.extend(agents);
+                agent_ids.push(task.agent_id.clone());
+            }
+            task_ids.sort();
+            task_ids.dedup();
+        }
+        let agents: HashMap<String, agent::Model> = agent::Entity::find()
+            .filter(agent::Column::Id.is_in(agent_ids))
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error fetching agents: {}", e))?
+            .into_iter()
+            .map(|agent| (agent.id.clone(), agent))
+            .collect();
+        let sub_flow_ids: Vec<String> = steps.iter().filter_map(sub_flow_of).collect();
+        let flows: HashMap<String, String> = flow::Entity::find()
+            .filter(flow::Column::Id.is_in(sub_flow_ids))
+            .all(db)
+            .await
+            .map_err(|e| format!("Database error fetching flows: {}", e))?
+            .into_iter()
+            .map(|flow| (flow.id, flow.name))
+            .collect();
+
+        let mut task_keys = HashMap::new();
+        for task in &tasks {
+            let agent = agents
+                .get(&task.agent_id)
+                .ok_or_else(|| format!("Agent {} of task {} not found", task.agent_id, task.id))?;
+            task_keys.insert(task.id.clone(), task_key(&agent.slug, &task.name));
+        }
+        let agent_slugs: HashMap<String, String> = agents
+            .values()
+            .map(|agent| (agent.id.clone(), agent.slug.clone()))
+            .collect();
+
+        // Sorted by key for stable documents
+        let mut task_definitions = BTreeMap::new();
+        for task in tasks {
+            let key = task_keys[&task.id].clone();
+            let settings = map_settings(task.settings.as_ref(), &task_keys, &agent_slugs)
+                .map_err(|e| format!("Task {}: {}", key, e))?;
+            task_definitions.insert(
+                key,
+                TaskDefinition {
+                    agent: agent_slugs[&task.agent_id].clone(),
+                    name: task.name,
+                    description: task.description,
+                    task_type: task.task_type,
+                    path: task.path,
+                    method: task.method,
+                    input_contract: task.input_contract,
+                    output_contract: task.output_contract,
+                    settings,
+                },
+            );
+        }
+        let agent_definitions: BTreeMap<String, AgentDefinition> = agents
+            .into_values()
+            .map(|agent| {
+                (
+                    agent.slug.clone(),
+                    AgentDefinition {
+                        slug: agent.slug,
+                        endpoint: agent.endpoint,
+                        source: agent.source,
+                    },
+                )
+            })
+            .collect();
+
+        Ok(Some(FlowDocument {
+            format: DOCUMENT_FORMAT,
+            flow: FlowDefinition {
+                name: flow.name,
+                description: flow.description,
+            },
+            agents: agent_definitions.into_values().collect(),
+            tasks: task_definitions.into_values().collect(),
+            steps: export_steps(&steps, &task_keys, &flows)?,
+        }))
+    }
+
+    /// Creates or updates a flow from a document: registers its missing agents, creates or
+    /// updates its tasks (checked like tasks created through the API) and replaces the draft
+    /// steps, all at once. The flow is `flow_id`, otherwise the flow of the same name, created
+    /// when there is none. A dry run only reports the changes. Returns `None` when `flow_id`
+    /// does not exist.
+    pub async fn import(
+        db: &DatabaseConnection,
+        document: FlowDocument,
+        flow_id: Option<String>,
+        dry_run: bool,
+    ) -> Result<Option<FlowImportReport>, String> {
+        let target = match flow_id {
+            Some(flow_id) => match FlowRepository::find_by_id(db, flow_id)
+                .await
+                .map_err(|e| e.to_string())?
+            {
+                Some(flow) => Some(flow),
+                None => return Ok(None),
+            },
+            None => {
+                let mut flows = FlowRepository::find_by_name(db, document.flow.name.clone())
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if flows.len() > 1 {
+                    return Err(format!(
+                        "Several flows are named {}, pass the flow_id to update",
+                        document.flow.name
+                    ));
+                }
+                flows.pop()
+            }
+        };
+        let flow_id = target
+            .as_ref()
+            .map(|f| f.id.clone())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let now = chrono::Utc::now();
+
+        let mut flow_changes = Vec::new();
+        if let Some(flow) = &target {
+            if flow.name != document.flow.name {
+                flow_changes.push(FieldChange {
+                    field: "name".to_string(),
+                    from: json!(flow.name),
+                    to: json!(document.flow.name),
+                });
+            }
+            if flow.description != document.flow.description {
+                flow_changes.push(FieldChange {
+                    field: "description".to_string(),
+                    from: json!(flow.description),
+                    to: json!(document.flow.description),
+                });
+            }
+        }
+
+        // Tasks referenced by the steps and by the settings of the defined tasks
+        let mut references: Vec<&str> = document
+            .steps
+            .iter()
+            .flat_map(|s| [s.task.as_deref(), policy_task_of(s.config.as_ref())])
+            .flatten()
+            .collect();
+        let mut slugs: Vec<String> = document.tasks.iter().map(|t| t.agent.clone()).collect();
+        let mut settings_tasks = Vec::new();
+        for definition in &document.tasks {
+            let (tasks, agents) = settings_references(definition.settings.as_ref());
+            settings_tasks.extend(tasks);
+            slugs.extend(agents);
+        }
+        references.extend(settings_tasks.iter().map(String::as_str));
+        references.sort();
+        references.dedup();
+
+        // Agents of the tasks, registered or to register
+        for key in &references {
+            let (slug, _) = key
+                .split_once('/')
+                .ok_or_else(|| format!("Task {} must be written <agent slug>/<task name>", key))?;
+            slugs.push(slug.to_string());
+        }
+        slugs.sort();
+        slugs.dedup();
+        let mut agent_ids = HashMap::new();
+        let mut new_agents = Vec::new();
+        let mut agents_created = Vec::new();
+        for slug in slugs {
+            match AgentRepository::find_by_slug(db, slug.clone())
+                .await
+                .map_err(|e| e.to_string())?
+            {
+                Some(agent) => {
+                    agent_ids.insert(slug, (agent.id, true));
+                }
+                None => {
+                    let definition =
+                        document
+                            .agents
+                            .iter()
+                            .find(|a| a.slug == slug)
+                            .ok_or_else(|| {
+                                format!(
+                                    "Agent {} is neither registered nor defined in the document",
+                                    slug
+                                )
+                            })?;
+                    let id = uuid::Uuid::new_v4().to_string();
+                    agents_created.push(slug.clone());
+                    agent_ids.insert(slug, (id.clone(), false));
+                    new_agents.push(agent::ActiveModel {
+                        id: Set(id),
+                        slug: Set(definition.slug.clone()),
+                        endpoint: Set(definition.endpoint.clone()),
+                        status: Set(agent::AgentStatus::Pending),
+                        source: Set(definition.source.clone()),
+                        rate_limit: Set(None),
+                    });
+                }
+            }
+        }
+
+        // Tasks defined in the document are created or updated, the others must exist
+        let mut task_ids = HashMap::new();
+        let mut defined = Vec::new();
+        let mut registered: HashMap<String, Vec<agent_task::Model>> = HashMap::new();
+        for (agent_id, _) in agent_ids.values().filter(|(_, registered)| *registered) {
+            let tasks = AgentTaskRepository::find_by_agent_id(db, agent_id.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+            registered.insert(agent_id.clone(), tasks);
+        }
+        for definition in &document.tasks {
+            let key = task_key(&definition.agent, &definition.name);
+            if task_ids.contains_key(&key) {
+                return Err(format!("Task {} is defined several times", key));
+            }
+            let (agent_id, _) = &agent_ids[&definition.agent];
+            let existing = registered
+                .get(agent_id)
+                .and_then(|tasks| tasks.iter().find(|t| t.name == definition.name));
+            let id = existing
+                .map(|task| task.id.clone())
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            task_ids.insert(key.clone(), id);
+            defined.push((key, agent_id.clone(), definition, existing));
+        }
+        for key in references {
+            if task_ids.contains_key(key) {
+                continue;
+            }
+            let (slug, name) = key.split_once('/').unwrap_or_default();
+            let task = agent_ids
+                .get(slug)
+                .and_then(|(agent_id, _)| registered.get(agent_id))
+                .and_then(|tasks| tasks.iter().find(|t| t.name == name))
+                .ok_or_else(|| {
+                    format!(
+                        "Task {} is neither registered nor defined in the document",
+                        key
+                    )
+                })?;
+            task_ids.insert(key.to_string(), task.id.clone());
+        }
+
+        // Settings of the defined tasks reference tasks and agents by id once resolved
+        let agent_map: HashMap<String, String> = agent_ids
+            .iter()
+            .map(|(slug, (id, _))| (slug.clone(), id.clone()))
+            .collect();
+        let mut new_tasks = Vec::new();
+        let mut updated_tasks = Vec::new();
+        for (key, agent_id, definition, existing) in defined {
+            let settings = map_settings(definition.settings.as_ref(), &task_ids, &agent_map)
+                .map_err(|e| format!("Task {}: {}", key, e))?;
+            let created_at = existing.map_or(Some(now.into()), |task| task.created_at);
+            let task = defined_task(
+                task_ids[&key].clone(),
+                agent_id,
+                definition,
+                settings,
+                created_at,
+            );
+            match existing {
+                Some(existing) if *existing == task => {}
+                Some(_) => updated_tasks.push((key, task)),
+                None => new_tasks.push((key, task)),
+            }
+        }
+        let pending: HashMap<String, agent_task::Model> = new_tasks
+            .iter()
+            .chain(&updated_tasks)
+            .map(|(_, task)| (task.id.clone(), task.clone()))
+            .collect();
+        for (key, task) in new_tasks.iter().chain(&updated_tasks) {
+            check_task(db, task, &pending)
+                .await
+                .map_err(|e| format!("Task {}: {}", key, e))?;
+        }
+
+        let mut flows = HashMap::new();
+        for name in document.steps.iter().filter_map(|s| s.flow.as_ref()) {
+            let matches = FlowRepository::find_by_name(db, name.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+            match matches.as_slice() {
+                [sub_flow] => {
+                    flows.insert(name.clone(), sub_flow.id.clone());
+                }
+                [] => return Err(format!("Sub-flow {} not found", name)),
+                _ => return Err(format!("Several flows are named {}", name)),
+            }
+        }
+
+        let existing = match &target {
+            Some(_) => FlowStepRepository::get_steps_for_flow(db, flow_id.clone())
+                .await
+                .map_err(|e| format!("Failed to fetch flow steps: {}", e))?,
+            None => Vec::new(),
+        };
+        let steps = import_steps(&flow_id, &document.steps, &existing, &task_ids, &flows)?;
+        let graph = FlowGraph::from_steps(&steps)?;
+        for (index, step) in steps.iter().enumerate() {
+            FlowService::check_step(
+                db,
+                &flow_id,
+                &graph,
+                index,
+                &step.step_type,
+                step.task_id.is_some(),
+                step.config.as_ref(),
+            )
+            .await
+            .map_err(|e| format!("Step {}: {}", step.name.as_ref().unwrap_or(&step.id), e))?;
+        }
+        let diff = flow_version::diff_steps("draft", &existing, "import", &steps);
+
+        let report = FlowImportReport {
+            dry_run,
+            flow_id: (target.is_some() || !dry_run).then(|| flow_id.clone()),
+            created: target.is_none(),
+            flow_changes,
+            agents_created,
+            tasks_created: new_tasks.iter().map(|(key, _)| key.clone()).collect(),
+            tasks_updated: updated_tasks.iter().map(|(key, _)| key.clone()).collect(),
+            steps: diff,
+        };
+        if dry_run {
+            return Ok(Some(report));
+        }
+
+        let txn = db.begin().await.map_err(|e| e.to_string())?;
+        match target {
+            Some(flow) => {
+                let mut active = flow.into_active_model();
+                active.name = Set(document.flow.name);
+                active.description = Set(document.flow.description);
+                active.updated_at = Set(Some(now.into()));
+                active.update(&txn).await
+            }
+            None => {
+                flow::ActiveModel {
+                    id: Set(flow_id.clone()),
+                    name: Set(document.flow.name),
+                    description: Set(document.flow.description),
+                    created_at: Set(Some(now.into())),
+                    updated_at: Set(Some(now.into())),
+                }
+                .insert(&txn)
+                .await
+            }
+        }
+        .map_err(|e| format!("Failed to save the flow: {}", e))?;
+        for agent in new_agents {
+            agent
+                .insert(&txn)
+                .await
+                .map_err(|e| format!("Failed to register an agent: {}", e))?;
+        }
+        for (_, task) in new_tasks {
+            task.into_active_model()
+                .reset_all()
+                .insert(&txn)
+                .await
+                .map_err(|e| format!("Failed to create a task: {}", e))?;
+        }
+        for (_, task) in updated_tasks {
+            task.into_active_model()
+                .reset_all()
+                .update(&txn)
+                .await
+                .map_err(|e| format!("Failed to update a task: {}", e))?;
+        }
+        let removed: Vec<String> = report.steps.removed.iter().map(|s| s.id.clone()).collect();
+        flow_step::Entity::delete_many()
+            .filter(flow_step::Column::Id.is_in(removed))
+            .exec(&txn)
+            .await
+            .map_err(|e| format!("Failed to remove steps: {}", e))?;
+        let changed: HashSet<&str> = report
+            .steps
+            .changed
+            .iter()
+            .map(|c| c.step_id.as_str())
+            .collect();
+        for step in steps {
+            if changed.contains(step.id.as_str()) {
+                step.into_active_model()
+                    .reset_all()
+                    .update(&txn)
+                    .await
+                    .map_err(|e| format!("Failed to update a step: {}", e))?;
+            } else if report.steps.added.iter().any(|s| s.id == step.id) {
+                let mut active = step.into_active_model().reset_all();
+                active.created_at = Set(Some(now.into()));
+                active
+                    .insert(&txn)
+                    .await
+                    .map_err(|e| format!("Failed to add a step: {}", e))?;
+            }
+        }
+        txn.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(Some(report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(id: &str, name: Option<&str>, depends_on: Option<Value>) -> flow_step::Model {
+        flow_step::Model {
+            task_id: Some("t1".to_string()),
+            name: name.map(|n| n.to_string()),
+            depends_on,
+            ..flow_step::Model::fixture(id).with_order(1)
+        }
+    }
+
+    #[test]
+    fn exports_and_imports_steps_by_key() {
+        let first = step("s1", Some("classify"), None).with_config(json!({
+            "prompt": "{{ steps.s0.text }}",
+            "error_policy": { "fallback_task_id": "t1", "handler": "s3" }
+        }));
+        let mut router = step("r1", None, Some(json!(["s1"])))
+            .with_order(2)
+            .with_type(STEP_TYPE_ROUTER)
+            .with_config(json!({
+                "routes": [{ "when": { "label": "yes" }, "next": ["s3"] }],
+                "default": "s3"
+            }));
+        router.task_id = None;
+        let last = step("s3", None, Some(json!(["r1"])))
+            .with_order(3)
+            .with_config(json!({ "prompt": "{{ steps.s1.label }} {{ steps['r1'].next }}" }));
+        let steps = vec![first, router, last];
+
+        let tasks = HashMap::from([("t1".to_string(), "bot/classify".to_string())]);
+        let exported = export_steps(&steps, &tasks, &HashMap::new()).unwrap();
+        assert_eq!(exported[0].id, None);
+        assert_eq!(exported[0].task.as_deref(), Some("bot/classify"));
+        assert_eq!(exported[1].depends_on, Some(vec!["classify".to_string()]));
+        let policy = &exported[0].config.as_ref().unwrap()["error_policy"];
+        assert_eq!(policy["fallback_task_id"], json!("bot/classify"));
+        assert_eq!(
+            exported[2].config.as_ref().unwrap()["prompt"],
+            json!("{{ steps.classify.label }} {{ steps['r1'].next }}")
+        );
+
+        let document = serde_yaml::to_string(&exported).unwrap();
+        let parsed: Vec<StepDefinition> = serde_yaml::from_str(&document).unwrap();
+        assert_eq!(parsed, exported);
+
+        // Into an environment where the task has another id and only the named step exists
+        let tasks = HashMap::from([("bot/classify".to_string(), "t9".to_string())]);
+        let existing = vec![step("x1", Some("classify"), None)];
+        let imported = import_steps("flow", &parsed, &existing, &tasks, &HashMap::new()).unwrap();
+        assert_eq!(imported[0].id, "x1");
+        assert_eq!(imported[0].task_id.as_deref(), Some("t9"));
+        let (router, last) = (&imported[1], &imported[2]);
+        assert_ne!(router.id, "r1");
+        assert_eq!(last.depends_on, Some(json!([router.id])));
+        let config = router.config.as_ref().unwrap();
+        assert_eq!(config["routes"][0]["next"], json!([last.id]));
+        assert_eq!(config["default"], json!(last.id));
+        assert_eq!(router.depends_on, Some(json!(["classify"])));
+        let config = imported[0].config.as_ref().unwrap();
+        assert_eq!(config["error_policy"]["fallback_task_id"], json!("t9"));
+        assert_eq!(config["error_policy"]["handler"], json!(last.id));
+        assert_eq!(config["prompt"], json!("{{ steps.s0.text }}"));
+        assert_eq!(
+            last.config.as_ref().unwrap()["prompt"],
+            json!(format!(
+                "{{{{ steps.classify.label }}}} {{{{ steps[\"{}\"].next }}}}",
+                router.id
+            ))
+        );
+
+        let unknown = import_steps("flow", &parsed, &existing, &HashMap::new(), &HashMap::new());
+        assert!(unknown.is_err());
+
+        let mut same_order = parsed.clone();
+        same_order[2].step_order = 2;
+        let ambiguous = import_steps("flow", &same_order, &existing, &tasks, &HashMap::new());
+        assert!(ambiguous.unwrap_err().contains("step_order 2"));
+    }
+
+    #[test]
+    fn maps_task_and_agent_references_of_settings() {
+        let settings = json!({
+            "tools": ["t1", "t2"],
+            "fallback": { "targets": [{ "task_id": "t2" }, { "agent_id": "a1", "model": "m" }] }
+        });
+        let tasks = HashMap::from([
+            ("t1".to_string(), "bot/search".to_string()),
+            ("t2".to_string(), "bot/answer".to_string()),
+        ]);
+        let agents = HashMap::from([("a1".to_string(), "backup".to_string())]);
+        assert_eq!(
+            settings_references(Some(&settings)),
+            (
+                vec!["t1".to_string(), "t2".to_string(), "t2".to_string()],
+                vec!["a1".to_string()]
+            )
+        );
+
+        let exported = map_settings(Some(&settings), &tasks, &agents)
+            .unwrap()
+            .unwrap();
+        assert_eq!(exported["tools"], json!(["bot/search", "bot/answer"]));
+        assert_eq!(
+            exported["fallback"]["targets"][0]["task_id"],
+            json!("bot/answer")
+        );
+        assert_eq!(
+            exported["fallback"]["targets"][1]["agent_id"],
+            json!("backup")
+        );
+
+        let unknown = map_settings(Some(&settings), &tasks, &HashMap::new());
+        assert_eq!(unknown.unwrap_err(), "Unknown agent a1");
+        assert_eq!(map_settings(None, &tasks, &agents), Ok(None));
+    }
+
+    #[test]
+    fn parses_yaml_and_json_documents() {
+        let yaml = "format: 1\nflow:\n  name: triage\nsteps:\n  - name: a\n    step_type: task\n    step_order: 1\n    task: bot/classify\n";
+        let document = parse_document(yaml).unwrap();
+        assert_eq!(document.flow.name, "triage");
+        assert_eq!(document.steps[0].task.as_deref(), Some("bot/classify"));
+
+        let json = r#"{"format": 1, "flow": {"name": "triage"}, "steps": []}"#;
+        assert!(parse_document(json).unwrap().steps.is_empty());
+        assert!(parse_document(r#"{"format": 2, "flow": {"name": "x"}, "steps": []}"#).is_err());
+    }
+}
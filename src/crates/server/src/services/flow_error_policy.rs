@@ -0,0 +1,301 @@
+use crate::services::agent_client::{AgentCallError, ErrorClass};
+use crate::services::fallback::default_error_classes;
+use crate::services::flow_graph::FlowGraph;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// The step succeeded after failed attempts.
+pub const RECOVERY_RETRIED: &str = "retried";
+/// The fallback task answered after the step task failed.
+pub const RECOVERY_FALLBACK: &str = "fallback";
+/// The step failed and the flow went on with its default output.
+pub const RECOVERY_CONTINUED: &str = "continued";
+/// The step failed and its error handler step runs in place of its downstream steps.
+pub const RECOVERY_HANDLED: &str = "handled";
+
+const MAX_RETRIES: u32 = 10;
+const MAX_BACKOFF_MS: u64 = 60_000;
+
+/*
+ * Error policy of a step, read from `config.error_policy`, e.g.
+ * { "error_policy": { "retries": 2, "backoff_ms": 500, "retry_on": ["timeout"],
+ *                     "fallback_task_id": "...", "fallback_on": ["server_error"],
+ *                     "continue_on_error": true,
+ *                     "default_output": { "label": "unknown" } } }
+ * Retries and the fallback task apply to task steps, continuing or handling the error to
+ * task, map and sub-flow steps. `continue_on_error` and `handler` are exclusive.
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct ErrorPolicy {
+    /* Attempts of the step task after the first one, on top of the agent call retries */
+    #[serde(default)]
+    pub retries: u32,
+
+    /* Delay before the first retry, doubled at every attempt */
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+
+    /* Error classes retried */
+    #[serde(default = "default_error_classes")]
+    pub retry_on: Vec<ErrorClass>,
+
+    /* Task run once with the same payload when the attempts are exhausted */
+    pub fallback_task_id: Option<String>,
+
+    /* Error classes answered by the fallback task */
+    #[serde(default = "default_error_classes")]
+    pub fallback_on: Vec<ErrorClass>,
+
+    /* Settle the failed step as completed, with `default_output` as its output */
+    #[serde(default)]
+    pub continue_on_error: bool,
+
+    /* Output of a failed step continuing the flow, `{"error": ...}` otherwise */
+    pub default_output: Option<Value>,
+
+    /* Step (name or id) depending on this one, run with the error as input when it fails
+     * and skipped otherwise */
+    pub handler: Option<String>,
+}
+
+fn default_backoff_ms() -> u64 {
+    1000
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/*
+ * How the failures of a step were dealt with, stored in `flow_step_executions.recovery`.
+ */
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Recovery {
+    /* "retried", "fallback", "continued" or "handled", unset when the step still failed */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<String>,
+
+    /* Attempts of the step task started by the policy, apart from the agent call retries
+     * counted on the step record */
+    #[serde(skip_serializing_if = "is_zero")]
+    pub retries: u32,
+
+    /* Failed attempts of the step task, oldest first */
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attempts: Vec<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_task_id: Option<String>,
+
+    /* Error handler step run in place of the downstream steps */
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handler: Option<String>,
+}
+
+impl ErrorPolicy {
+    /// Parses the error policy of a step config, `None` when the step declares none.
+    pub fn from_config(config: Option<&Value>) -> Result<Option<Self>, String> {
+        let raw = match config.and_then(|c| c.get("error_policy")) {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        let policy: Self = serde_json::from_value(raw.clone())
+            .map_err(|e| format!("Invalid error policy: {}", e))?;
+
+        if policy.retries > MAX_RETRIES {
+            return Err(format!("retries must not exceed {}", MAX_RETRIES));
+        }
+        if policy.continue_on_error && policy.handler.is_some() {
+            return Err("An error policy either continues on error or has a handler".to_string());
+        }
+        if policy.default_output.is_some() && !policy.continue_on_error {
+            return Err("default_output requires continue_on_error".to_string());
+        }
+        Ok(Some(policy))
+    }
+
+    /// Whether a failed attempt (numbered from 0) is tried again.
+    pub fn retries(&self, attempt: u32, error: &AgentCallError) -> bool {
+        attempt < self.retries && self.retry_on.contains(&error.class)
+    }
+
+    /// Fallback task answering an error once the attempts are exhausted.
+    pub fn fallback(&self, error: &AgentCallError) -> Option<&str> {
+        self.fallback_task_id
+            .as_deref()
+            .filter(|_| self.fallback_on.contains(&error.class))
+    }
+
+    /// Delay before the retry following the failed attempt `attempt` (numbered from 0).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.backoff_ms.saturating_mul(1 << attempt.min(16));
+        Duration::from_millis(delay.min(MAX_BACKOFF_MS))
+    }
+
+    /// Node of the error handler of the step at `index`, which must depend on the step.
+    pub fn handler_index(&self, graph: &FlowGraph, index: usize) -> Result<Option<usize>, String> {
+        let handler = match &self.handler {
+            Some(handler) => handler,
+            None => return Ok(None),
+        };
+        match graph.find(handler) {
+            Some(h) if graph.dependencies[h].contains(&index) => Ok(Some(h)),
+            _ => Err(format!(
+                "Error handler {} of step {} must be a step depending on it",
+                handler, graph.labels[index]
+            )),
+        }
+    }
+
+    /// Output of the failed step when the policy recovers from its error, recording how.
+    pub fn recover(
+        &self,
+        label: &str,
+        error: &AgentCallError,
+        recovery: &mut Recovery,
+    ) -> Option<Value> {
+        if let Some(handler) = &self.handler {
+            recovery.outcome = Some(RECOVERY_HANDLED.to_string());
+            recovery.handler = Some(handler.clone());
+            return Some(error_output(label, error));
+        }
+        if self.continue_on_error {
+            recovery.outcome = Some(RECOVERY_CONTINUED.to_string());
+            return Some(
+                self.default_output
+                    .clone()
+                    .unwrap_or_else(|| error_output(label, error)),
+            );
+        }
+        None
+    }
+}
+
+impl Recovery {
+    pub fn attempt(&mut self, error: &AgentCallError) {
+        self.attempts.push(json!({
+            "attempt": self.attempts.len() + 1,
+            "class": error.class,
+            "error": error.message,
+        }));
+    }
+
+    /// JSON stored on the step record, `None` when nothing was recovered nor retried.
+    pub fn to_value(&self) -> Option<Value> {
+        if self.outcome.is_none()
+            && self.retries == 0
+            && self.attempts.is_empty()
+            && self.fallback_task_id.is_none()
+        {
+            return None;
+        }
+        serde_json::to_value(self).ok()
+    }
+
+    /// Whether the step failed and its error handler ran, from a stored recovery.
+    pub fn handled(recovery: Option<&Value>) -> bool {
+        recovery
+            .and_then(|r| r.get("outcome"))
+            .is_some_and(|outcome| outcome == RECOVERY_HANDLED)
+    }
+}
+
+/// Output of a failed step handed to its error handler: `{"error", "class", "step"}`.
+pub fn error_output(label: &str, error: &AgentCallError) -> Value {
+    json!({ "error": error.message, "class": error.class, "step": label })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::flow_graph::GraphNode;
+
+    fn policy(raw: Value) -> Result<Option<ErrorPolicy>, String> {
+        ErrorPolicy::from_config(Some(&json!({ "template": "x", "error_policy": raw })))
+    }
+
+    #[test]
+    fn parses_and_validates_policies() {
+        assert!(ErrorPolicy::from_config(Some(&json!({ "template": "x" })))
+            .unwrap()
+            .is_none());
+        let parsed = policy(json!({ "retries": 2 })).unwrap().unwrap();
+        assert_eq!(parsed.backoff_ms, 1000);
+        assert!(parsed.retry_on.contains(&ErrorClass::Timeout));
+        assert!(!parsed.retry_on.contains(&ErrorClass::ClientError));
+
+        assert!(policy(json!({ "retries": 50 })).is_err());
+        assert!(policy(json!({ "continue_on_error": true, "handler": "notify" })).is_err());
+        assert!(policy(json!({ "default_output": {} })).is_err());
+    }
+
+    #[test]
+    fn retries_with_backoff_then_recovers() {
+        let parsed = policy(json!({ "retries": 1, "backoff_ms": 100, "continue_on_error": true }))
+            .unwrap()
+            .unwrap();
+        let timeout = AgentCallError::new(ErrorClass::Timeout, "too slow", 2);
+        assert!(parsed.retries(0, &timeout));
+        assert!(!parsed.retries(1, &timeout));
+        assert!(!parsed.retries(0, &AgentCallError::new(ErrorClass::ClientError, "bad", 0)));
+        assert_eq!(parsed.backoff(0), Duration::from_millis(100));
+        assert_eq!(parsed.backoff(2), Duration::from_millis(400));
+
+        let mut recovery = Recovery::default();
+        recovery.attempt(&timeout);
+        let output = parsed.recover("classify", &timeout, &mut recovery).unwrap();
+        assert_eq!(output["step"], json!("classify"));
+        assert_eq!(output["class"], json!("timeout"));
+        let stored = recovery.to_value().unwrap();
+        assert_eq!(stored["outcome"], json!(RECOVERY_CONTINUED));
+        assert_eq!(stored["attempts"][0]["error"], json!("too slow"));
+        assert!(!Recovery::handled(Some(&stored)));
+    }
+
+    #[test]
+    fn falls_back_on_its_own_error_classes() {
+        let parsed = policy(json!({
+            "retry_on": ["timeout"],
+            "fallback_task_id": "backup",
+            "fallback_on": ["server_error"]
+        }))
+        .unwrap()
+        .unwrap();
+        let server_error = AgentCallError::new(ErrorClass::ServerError, "down", 0);
+        assert!(!parsed.retries(0, &server_error));
+        assert_eq!(parsed.fallback(&server_error), Some("backup"));
+        let timeout = AgentCallError::new(ErrorClass::Timeout, "too slow", 0);
+        assert_eq!(parsed.fallback(&timeout), None);
+
+        let mut recovery = Recovery::default();
+        recovery.attempt(&timeout);
+        recovery.retries += 1;
+        let stored = recovery.to_value().unwrap();
+        assert_eq!(stored["retries"], json!(1));
+    }
+
+    #[test]
+    fn requires_a_dependent_handler() {
+        let node = |id: &str, depends_on: &[&str]| GraphNode {
+            id: id.to_string(),
+            name: None,
+            depends_on: Some(depends_on.iter().map(|d| d.to_string()).collect()),
+        };
+        let graph = FlowGraph::build(&[
+            node("classify", &[]),
+            node("notify", &["classify"]),
+            node("other", &[]),
+        ])
+        .unwrap();
+        let handled = |handler: &str| {
+            policy(json!({ "handler": handler }))
+                .unwrap()
+                .unwrap()
+                .handler_index(&graph, 0)
+        };
+        assert_eq!(handled("notify"), Ok(Some(1)));
+        assert!(handled("other").is_err());
+        assert!(handled("missing").is_err());
+    }
+}
@@ -1,219 +1,924 @@
+use crate::models::flow_execution::{
+    self, StepProgress, STEP_CANCELLED, STEP_COMPLETED, STEP_FAILED, STEP_PENDING, STEP_RUNNING,
+    STEP_SKIPPED, STEP_WAITING,
+};
+use crate::models::flow_step::{
+    self, STEP_TYPE_APPROVAL, STEP_TYPE_FLOW, STEP_TYPE_MAP, STEP_TYPE_ROUTER,
+};
+use crate::models::{agent_task, flow_step_execution, session_message};
 use crate::repositories::{
+    flow_approval::Repository as FlowApprovalRepository,
     flow_execution::Repository as FlowExecutionRepository,
-    flow_step::Repository as FlowStepRepository,
+    flow_step_execution::Repository as FlowStepExecutionRepository,
 };
 use crate::services::{
-    agent::Service as AgentService, agent_client::Service as AgentClient,
-    agent_log::Service as AgentLogService,
+    agent_client::AgentCallError,
+    contract::ContractViolation,
+    flow_approval::Service as FlowApprovalService,
+    flow_error_policy::{ErrorPolicy, Recovery, RECOVERY_FALLBACK, RECOVERY_RETRIED},
+    flow_graph::FlowGraph,
+    flow_map::{MapConfig, Service as FlowMapService},
+    flow_router::RouterConfig,
+    flow_subflow::{Lineage, Service as SubFlowService, SubFlowConfig},
+    flow_template::TemplateContext,
+    flow_version::{Revision, Service as FlowVersionService},
+    flow_worker::Heartbeat,
+    session::Service as SessionService,
+    task_runner::Service as TaskRunner,
 };
-use reqwest::Client;
-use sea_orm::{DatabaseConnection, EntityTrait};
+use crate::state::AppState;
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{FuturesUnordered, StreamExt};
+use sea_orm::{DatabaseConnection, EntityTrait, TransactionTrait};
+use std::collections::HashMap;
+
+/// Failure of a flow run. A broken task contract keeps its details so that the API
+/// can answer with a structured 422.
+#[derive(Debug)]
+pub struct FlowError {
+    pub message: String,
+    pub contract: Option<Box<ContractViolation>>,
+    /// Execution paused on approval steps rather than failed, resumed once they are decided
+    pub paused: Option<String>,
+}
+
+impl FlowError {
+    pub fn new(message: String) -> Self {
+        Self {
+            message,
+            contract: None,
+            paused: None,
+        }
+    }
+}
+
+impl From<String> for FlowError {
+    fn from(message: String) -> Self {
+        Self::new(message)
+    }
+}
+
+// Per-step progress of an execution, saved on the execution at every transition, and
+// the record of every step in `flow_step_executions`.
+struct Progress {
+    execution_id: String,
+    steps: Vec<StepProgress>,
+    records: Vec<Option<flow_step_execution::Model>>,
+}
+
+// Outcome of a step, stored in its record.
+#[derive(Default)]
+struct StepOutcome {
+    response: Option<serde_json::Value>,
+    error: Option<String>,
+    retries: i32,
+    agent_log_id: Option<String>,
+    recovery: Option<serde_json::Value>,
+}
+
+// Result of a task, map or sub-flow step run, tagged with the step index.
+struct StepRun {
+    index: usize,
+    result: Result<serde_json::Value, AgentCallError>,
+    retries: i32,
+    agent_log_id: Option<String>,
+    recovery: Recovery,
+}
+
+impl Progress {
+    fn new(execution_id: &str, steps: &[flow_step::Model], graph: &FlowGraph) -> Self {
+        Self {
+            execution_id: execution_id.to_string(),
+            steps: steps
+                .iter()
+                .zip(&graph.labels)
+                .map(|(step, label)| StepProgress {
+                    step_id: step.id.clone(),
+                    name: label.clone(),
+                    status: STEP_PENDING.to_string(),
+                    started_at: None,
+                    completed_at: None,
+                    error: None,
+                })
+                .collect(),
+            records: vec![None; steps.len()],
+        }
+    }
+
+    /// Marks a step as Running and records the payload it was started with.
+    async fn start(&mut self, db: &DatabaseConnection, index: usize, payload: &serde_json::Value) {
+        self.set(db, index, STEP_RUNNING, None).await;
+        self.records[index] = self
+            .create_record(db, index, STEP_RUNNING, Some(payload.clone()))
+            .await;
+    }
+
+    /// Marks a step as waiting for approval, recording the data submitted for review.
+    /// Returns the id of its record.
+    async fn wait(
+        &mut self,
+        db: &DatabaseConnection,
+        index: usize,
+        payload: &serde_json::Value,
+    ) -> Option<String> {
+        self.set(db, index, STEP_WAITING, None).await;
+        self.records[index] = self
+            .create_record(db, index, STEP_WAITING, Some(payload.clone()))
+            .await;
+        self.records[index].as_ref().map(|record| record.id.clone())
+    }
+
+    /// Settles a step (Completed, Failed or Skipped) and stores its outcome.
+    async fn finish(
+        &mut self,
+        db: &DatabaseConnection,
+        index: usize,
+        status: &str,
+        outcome: StepOutcome,
+    ) {
+        self.set(db, index, status, outcome.error.as_deref()).await;
+        let record = match self.records[index].take() {
+            Some(record) => Some(record),
+            None => self.create_record(db, index, status, None).await,
+        };
+        if let Some(record) = record {
+            let _ = FlowStepExecutionRepository::finish(
+                db,
+                record,
+                status,
+                outcome.response,
+                outcome.error,
+                outcome.retries,
+                outcome.agent_log_id,
+                outcome.recovery,
+            )
+            .await;
+        }
+    }
+
+    /// Marks a step completed by an earlier run, without recording it again.
+    fn restore(&mut self, index: usize, record: &flow_step_execution::Model) {
+        let step = &mut self.steps[index];
+        step.status = STEP_COMPLETED.to_string();
+        step.started_at = record.started_at.map(|t| t.to_rfc3339());
+        step.completed_at = record.completed_at.map(|t| t.to_rfc3339());
+        step.error = None;
+    }
+
+    async fn create_record(
+        &self,
+        db: &DatabaseConnection,
+        index: usize,
+        status: &str,
+        payload: Option<serde_json::Value>,
+    ) -> Option<flow_step_execution::Model> {
+        let step = &self.steps[index];
+        FlowStepExecutionRepository::create(
+            db,
+            self.execution_id.clone(),
+            step.step_id.clone(),
+            step.name.clone(),
+            status,
+            payload,
+        )
+        .await
+        .map_err(|e| tracing::warn!("Failed to record step {}: {}", step.name, e))
+        .ok()
+    }
+
+    async fn set(
+        &mut self,
+        db: &DatabaseConnection,
+        index: usize,
+        status: &str,
+        error: Option<&str>,
+    ) {
+        self.mark(index, status, error);
+        self.save(db).await;
+    }
+
+    // Moves a step to `status`: Running restarts its clock, settled statuses stop it and
+    // steps skipped without running keep no start time.
+    fn mark(&mut self, index: usize, status: &str, error: Option<&str>) {
+        let now = chrono::Utc::now().to_rfc3339();
+        let step = &mut self.steps[index];
+        step.status = status.to_string();
+        step.error = error.map(|e| e.to_string());
+        if status == STEP_RUNNING || (step.started_at.is_none() && status != STEP_SKIPPED) {
+            step.started_at = Some(now.clone());
+        }
+        if status != STEP_RUNNING && status != STEP_WAITING {
+            step.completed_at = Some(now);
+        }
+    }
+
+    async fn save(&self, db: &DatabaseConnection) {
+        let steps = serde_json::to_value(&self.steps).unwrap_or_default();
+        let _ = FlowExecutionRepository::update_steps(db, self.execution_id.clone(), steps).await;
+    }
+}
 
 pub struct Service;
 
 impl Service {
-    /// Coordinates the execution pipeline by passing the output of one agent
-    /// as the input of the next. Manages flow state and logs via respective services.
+    /// Runs a revision of a flow to completion and returns its output.
     pub async fn execute_flow(
-        db: &DatabaseConnection,
-        http_client: &Client,
+        state: &AppState,
         flow_id: String,
         initial_input: serde_json::Value,
-    ) -> Result<serde_json::Value, String> {
+        session_id: Option<String>,
+        revision: Revision,
+    ) -> Result<serde_json::Value, FlowError> {
+        let history = match &session_id {
+            Some(id) => SessionService::get_history(&state.db, id.clone()).await?,
+            None => Vec::new(),
+        };
+        let version = FlowVersionService::resolve(&state.db, &flow_id, revision).await?;
+
         // 1. Create a execution record
-        let execution =
-            FlowExecutionRepository::create(db, flow_id.clone(), Some(initial_input.clone()))
-                .await
-                .map_err(|e| format!("Failed to create flow execution: {}", e))?;
+        let execution = FlowExecutionRepository::create(
+            &state.db,
+            flow_id,
+            version.map(|v| v.id),
+            Some(initial_input.clone()),
+            session_id.clone(),
+            "Running",
+            None,
+        )
+        .await
+        .map_err(|e| format!("Failed to create flow execution: {}", e))?;
 
-        let execution_id = execution.id.clone();
+        let lineage = Lineage::root(&execution.flow_id);
+        Self::run_execution(
+            state,
+            execution.id,
+            execution.flow_id,
+            initial_input,
+            history,
+            session_id,
+            HashMap::new(),
+            lineage,
+        )
+        .await
+    }
 
-        // 2. Fetch the ordered sequence of steps to process
-        let steps = FlowStepRepository::get_steps_for_flow(db, flow_id.clone())
+    /// Runs the flow of `lineage` as a sub-flow, in a child execution linked to the
+    /// execution and step of its parent. Boxed since the sub-flow goes through the flow
+    /// executor again.
+    pub fn execute_sub_flow(
+        state: &AppState,
+        input: serde_json::Value,
+        lineage: Lineage,
+    ) -> BoxFuture<'_, Result<serde_json::Value, FlowError>> {
+        async move {
+            let (parent_execution_id, parent_step_id) = match (
+                lineage.parent_execution_id.clone(),
+                lineage.parent_step_id.clone(),
+            ) {
+                (Some(execution_id), Some(step_id)) => (execution_id, step_id),
+                _ => {
+                    return Err(FlowError::new(
+                        "Sub-flows need a parent execution".to_string(),
+                    ))
+                }
+            };
+            // Sub-flows run the latest published version of their flow
+            let version =
+                FlowVersionService::resolve(&state.db, lineage.flow_id(), Revision::Latest).await?;
+            let execution = FlowExecutionRepository::create_child(
+                &state.db,
+                lineage.flow_id().to_string(),
+                version.map(|v| v.id),
+                Some(input.clone()),
+                parent_execution_id,
+                parent_step_id,
+                lineage.depth() as i32,
+            )
             .await
-            .map_err(|e| format!("Failed to fetch flow steps: {}", e))?;
+            .map_err(|e| format!("Failed to create sub-flow execution: {}", e))?;
 
-        if steps.is_empty() {
-            let _ =
-                FlowExecutionRepository::update_status(db, execution_id.clone(), "Failed", None)
-                    .await;
-            return Err("Flow has no steps defined".to_string());
+            Self::run_execution(
+                state,
+                execution.id,
+                execution.flow_id,
+                input,
+                Vec::new(),
+                None,
+                HashMap::new(),
+                lineage,
+            )
+            .await
         }
+        .boxed()
+    }
 
-        let mut current_data = initial_input;
+    /// Queues a Pending execution of a revision of a flow, run in the background by the
+    /// first free flow worker. The execution (status, step progress and output) is then
+    /// polled through its id, or posted to `callback_url` once it settles.
+    pub async fn start_flow(
+        state: &AppState,
+        flow_id: String,
+        initial_input: serde_json::Value,
+        session_id: Option<String>,
+        callback_url: Option<String>,
+        revision: Revision,
+    ) -> Result<flow_execution::Model, FlowError> {
+        // Unknown sessions are rejected before queueing
+        if let Some(id) = &session_id {
+            SessionService::get_history(&state.db, id.clone()).await?;
+        }
+        let version = FlowVersionService::resolve(&state.db, &flow_id, revision).await?;
 
-        // 3. Sequential operation (Pass the Torch)
-        for step in steps {
-            // Find the task in charge of the step
-            let task_opt = crate::models::agent_task::Entity::find_by_id(step.task_id.clone())
-                .one(db)
-                .await
-                .map_err(|e| format!("Database error fetching task: {}", e))?;
+        let execution = FlowExecutionRepository::create(
+            &state.db,
+            flow_id,
+            version.map(|v| v.id),
+            Some(initial_input),
+            session_id,
+            "Pending",
+            callback_url,
+        )
+        .await
+        .map_err(|e| format!("Failed to create flow execution: {}", e))?;
+        Ok(execution)
+    }
 
-            let task = match task_opt {
-                Some(t) => t,
-                None => {
-                    let err = format!("Task {} not found for step", step.task_id);
-                    let _ = FlowExecutionRepository::update_status(
+    /// Requests the cancellation of a Pending, Running or WaitingForApproval execution: the
+    /// worker running it (on this instance right away, on another one at its next heartbeat)
+    /// drops the steps in flight and marks it Cancelled. Returns `None` when the execution does not exist.
+    pub async fn cancel_execution(
+        state: &AppState,
+        execution_id: String,
+    ) -> Result<Option<flow_execution::Model>, String> {
+        let db = &state.db;
+        match FlowExecutionRepository::request_cancel(db, execution_id.clone())
+            .await
+            .map_err(|e| format!("Failed to cancel execution: {}", e))?
+        {
+            Some(execution) => {
+                state.executions.cancel(&execution_id);
+                if execution.status == "Cancelled" {
+                    let _ = FlowApprovalRepository::cancel_pending(
                         db,
                         execution_id.clone(),
-                        "Failed",
-                        Some(serde_json::json!({ "error": err })),
+                        "Execution cancelled",
                     )
                     .await;
-                    return Err(err);
+                    let _ = FlowStepExecutionRepository::cancel_waiting(db, execution_id).await;
                 }
-            };
+                Ok(Some(execution))
+            }
+            None => match FlowExecutionRepository::find_by_id(db, execution_id)
+                .await
+                .map_err(|e| format!("Database error fetching execution: {}", e))?
+            {
+                Some(execution) => Err(format!(
+                    "Execution {} is already {}",
+                    execution.id, execution.status
+                )),
+                None => Ok(None),
+            },
+        }
+    }
 
-            let agent_opt = AgentService::get_agent_by_id(db, task.agent_id.clone())
+    /// Queues a Failed or Cancelled execution again: steps completed by earlier runs keep their
+    /// recorded output, the failed and remaining steps run again. With `step` (id or
+    /// name), that step and everything downstream of it are rerun instead, on any settled
+    /// execution. Returns `None` when the execution does not exist.
+    pub async fn resume_execution(
+        state: &AppState,
+        execution_id: String,
+        step: Option<String>,
+    ) -> Result<Option<flow_execution::Model>, String> {
+        let db = &state.db;
+        let execution = match FlowExecutionRepository::find_by_id(db, execution_id.clone())
+            .await
+            .map_err(|e| format!("Database error fetching execution: {}", e))?
+        {
+            Some(execution) => execution,
+            None => return Ok(None),
+        };
+
+        let settled = match step {
+            None => execution.status == "Failed" || execution.status == "Cancelled",
+            Some(_) => ["Failed", "Completed", "Cancelled"].contains(&execution.status.as_str()),
+        };
+        if !settled {
+            return Err(format!(
+                "Execution {} is {} and cannot be resumed",
+                execution.id, execution.status
+            ));
+        }
+
+        // The outputs of the rerun steps must not be reused by the next run
+        let mut rerun = Vec::new();
+        if let Some(reference) = &step {
+            let steps = FlowVersionService::steps_of(
+                db,
+                execution.flow_id.clone(),
+                execution.version_id.clone(),
+            )
+            .await?;
+            let graph = FlowGraph::from_steps(&steps)?;
+            let index = graph
+                .find(reference)
+                .ok_or_else(|| format!("Unknown step {}", reference))?;
+            rerun = graph
+                .downstream(index)
+                .into_iter()
+                .map(|i| steps[i].id.clone())
+                .collect();
+        }
+
+        // The execution is queued only from the settled status read above, so concurrent
+        // resumes queue it once, and its records are superseded before a worker claims it
+        let txn = db.begin().await.map_err(|e| e.to_string())?;
+        let queued = FlowExecutionRepository::transition(
+            &txn,
+            execution_id.clone(),
+            &execution.status,
+            "Pending",
+            None,
+        )
+        .await
+        .map_err(|e| format!("Failed to update execution: {}", e))?;
+        if !queued {
+            return Err(format!(
+                "Execution {} changed while being resumed",
+                execution_id
+            ));
+        }
+        if !rerun.is_empty() {
+            FlowStepExecutionRepository::supersede(&txn, execution_id.clone(), rerun)
                 .await
-                .map_err(|e| format!("Database error fetching agent: {}", e))?;
+                .map_err(|e| format!("Failed to update step records: {}", e))?;
+        }
+        txn.commit().await.map_err(|e| e.to_string())?;
 
-            let agent = match agent_opt {
-                Some(a) => a,
-                None => {
-                    let err = format!("Agent {} not found for task", task.agent_id);
-                    let _ = FlowExecutionRepository::update_status(
-                        db,
-                        execution_id.clone(),
-                        "Failed",
-                        Some(serde_json::json!({ "error": err })),
-                    )
+        FlowExecutionRepository::find_by_id(db, execution_id)
+            .await
+            .map_err(|e| format!("Database error fetching execution: {}", e))
+    }
+
+    /// Runs an execution claimed by a flow worker, reusing the output of the steps an
+    /// earlier run (before a failure or a restart) already completed.
+    pub async fn run_claimed(
+        state: &AppState,
+        execution: flow_execution::Model,
+    ) -> Result<serde_json::Value, FlowError> {
+        let db = &state.db;
+
+        // Records left Running were interrupted with their previous worker
+        let _ = FlowStepExecutionRepository::fail_running(
+            db,
+            execution.id.clone(),
+            "Interrupted before completion",
+        )
+        .await;
+
+        let mut completed = HashMap::new();
+        for record in FlowStepExecutionRepository::find_by_execution(db, execution.id.clone())
+            .await
+            .map_err(|e| format!("Failed to fetch step records: {}", e))?
+        {
+            if record.status == STEP_COMPLETED {
+                completed.insert(record.step_id.clone(), record);
+            }
+        }
+
+        let history = match &execution.session_id {
+            Some(id) => SessionService::get_history(db, id.clone())
+                .await
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let lineage = SubFlowService::lineage_of(db, &execution).await?;
+
+        Self::run_execution(
+            state,
+            execution.id,
+            execution.flow_id,
+            execution.input_data.unwrap_or_default(),
+            history,
+            execution.session_id,
+            completed,
+            lineage,
+        )
+        .await
+    }
+
+    /// Coordinates the execution pipeline by passing the output of every step to the
+    /// steps depending on it, running independent branches concurrently. Manages flow
+    /// state and logs via respective services. When a session is given, its history is
+    /// prepended to the payload of the root steps. Steps found in `completed` are not run
+    /// again, the output of their record is reused. Sub-flow steps run their flow in a
+    /// child execution, `lineage` guards against cycles and unbounded nesting.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_execution(
+        state: &AppState,
+        execution_id: String,
+        flow_id: String,
+        initial_input: serde_json::Value,
+        history: Vec<session_message::Model>,
+        session_id: Option<String>,
+        completed: HashMap<String, flow_step_execution::Model>,
+        lineage: Lineage,
+    ) -> Result<serde_json::Value, FlowError> {
+        let db = &state.db;
+        let registration = state.executions.register(&execution_id);
+        let cancelled = registration.token.clone();
+        let _heartbeat = Heartbeat::start(db.clone(), execution_id.clone(), cancelled.clone());
+
+        // 2. Fetch the steps of the revision run, sorted by step order
+        let version_id = FlowExecutionRepository::find_by_id(db, execution_id.clone())
+            .await
+            .map_err(|e| format!("Database error fetching execution: {}", e))?
+            .and_then(|execution| execution.version_id);
+        let steps = FlowVersionService::steps_of(db, flow_id.clone(), version_id).await?;
+
+        if steps.is_empty() {
+            let _ =
+                FlowExecutionRepository::update_status(db, execution_id.clone(), "Failed", None)
                     .await;
-                    return Err(err);
+            return Err("Flow has no steps defined".to_string().into());
+        }
+
+        let graph = match FlowGraph::from_steps(&steps) {
+            Ok(graph) => graph,
+            Err(e) => {
+                Self::mark_failed(db, &execution_id, serde_json::json!({ "error": e })).await;
+                return Err(e.into());
+            }
+        };
+        let (policies, handlers) = match Self::error_policies(&graph, &steps) {
+            Ok(policies) => policies,
+            Err(e) => {
+                Self::mark_failed(db, &execution_id, serde_json::json!({ "error": e })).await;
+                return Err(e.into());
+            }
+        };
+
+        // 3. Graph traversal: every step whose upstream steps are settled is started,
+        // independent branches run concurrently. Steps left without any active upstream
+        // step (all skipped, or not selected by a router) are skipped.
+        let mut outputs: Vec<Option<serde_json::Value>> = vec![None; steps.len()];
+        let mut started = vec![false; steps.len()];
+        let mut skipped = vec![false; steps.len()];
+        let mut selected: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut names = vec![String::new(); steps.len()];
+        let mut branches = Vec::new();
+        let mut context = TemplateContext::new(initial_input.clone());
+        let mut progress = Progress::new(&execution_id, &steps, &graph);
+        progress.save(db).await;
+        let mut running = FuturesUnordered::new();
+        let mut approvals = Vec::new();
+
+        loop {
+            if cancelled.is_cancelled() {
+                drop(running);
+                return Err(Self::cancel(db, &execution_id, &mut progress).await);
+            }
+
+            // The topological order settles routers and skips in a single pass
+            for &index in &graph.order {
+                let deps = &graph.dependencies[index];
+                let settled = deps
+                    .iter()
+                    .all(|dep| outputs[*dep].is_some() || skipped[*dep]);
+                if started[index] || !settled {
+                    continue;
                 }
-            };
+                started[index] = true;
+                let step = &steps[index];
 
-            // Prepare payload by dynamically acting as a template engine
-            let mut payload = current_data.clone();
+                let active: Vec<usize> = deps
+                    .iter()
+                    .copied()
+                    .filter(|dep| outputs[*dep].is_some())
+                    .filter(|dep| selected.get(dep).is_none_or(|next| next.contains(&index)))
+                    .collect();
+                if !deps.is_empty() && active.is_empty() {
+                    skipped[index] = true;
+                    progress
+                        .finish(db, index, STEP_SKIPPED, StepOutcome::default())
+                        .await;
+                    continue;
+                }
+                let input = Self::step_input(&graph, index, &active, &outputs, &initial_input);
 
-            // Build the URL
-            let base_url = agent.endpoint.trim_end_matches('/');
-            let endpoint = match &task.path {
-                Some(p) => {
-                    if p.starts_with('/') {
-                        format!("{}{}", base_url, p)
-                    } else {
-                        format!("{}/{}", base_url, p)
+                // Steps completed by an earlier run of the execution keep their output
+                if let Some(record) = completed.get(&step.id) {
+                    let (output, selection, decision) =
+                        Self::reuse(&graph, index, step, record, input, handlers[index]);
+                    if let Some(next) = selection {
+                        selected.insert(index, next);
                     }
+                    branches.extend(decision);
+                    context.add_step(&step.id, step.name.as_deref(), &output);
+                    outputs[index] = Some(output);
+                    progress.restore(index, record);
+                    continue;
                 }
-                None => base_url.to_string(),
-            };
 
-            if let Some(config) = step.config {
-                if let Some(config_obj) = config.as_object() {
-                    let mut new_payload = serde_json::Map::new();
-
-                    // Extract a clean string representation of the current data (e.g. previous step output)
-                    let input_str = match &current_data {
-                        serde_json::Value::String(s) => s.clone(),
-                        serde_json::Value::Object(obj) => {
-                            if let Some(content) = obj.get("response").and_then(|v| v.as_str()) {
-                                content.to_string()
-                            } else if let Some(content) = obj
-                                .get("message")
-                                .and_then(|v| v.get("content"))
-                                .and_then(|v| v.as_str())
-                            {
-                                content.to_string()
-                            } else {
-                                current_data.to_string()
-                            }
+                // Routers pass their input through and select the downstream steps that run
+                if step.step_type == STEP_TYPE_ROUTER {
+                    progress.start(db, index, &input).await;
+                    match Self::route(&graph, index, step, &input) {
+                        Ok((next, decision)) => {
+                            selected.insert(index, next);
+                            branches.push(decision.clone());
+                            let _ = FlowExecutionRepository::update_branches(
+                                db,
+                                execution_id.clone(),
+                                serde_json::Value::Array(branches.clone()),
+                            )
+                            .await;
+                            context.add_step(&step.id, step.name.as_deref(), &input);
+                            outputs[index] = Some(input);
+                            let outcome = StepOutcome {
+                                response: Some(decision),
+                                ..Default::default()
+                            };
+                            progress.finish(db, index, STEP_COMPLETED, outcome).await;
+                            continue;
                         }
-                        v => v.to_string(),
-                    };
+                        Err(e) => {
+                            let outcome = StepOutcome {
+                                error: Some(e.clone()),
+                                ..Default::default()
+                            };
+                            progress.finish(db, index, STEP_FAILED, outcome).await;
+                            Self::mark_failed(
+                                db,
+                                &execution_id,
+                                serde_json::json!({ "error": e, "step": step.step_order, "step_id": step.id }),
+                            )
+                            .await;
+                            return Err(FlowError::new(format!(
+                                "Flow failed at router step {}: {}",
+                                step.step_order, e
+                            )));
+                        }
+                    }
+                }
 
-                    // 1. Template Interpolation
-                    if let Some(template_val) = config_obj.get("template").and_then(|v| v.as_str())
-                    {
-                        let interpolated = template_val.replace("{{input}}", &input_str);
-                        new_payload.insert("prompt".to_string(), serde_json::json!(interpolated));
-                    } else if let Some(prompt_val) =
-                        config_obj.get("prompt").and_then(|v| v.as_str())
-                    {
-                        // Fallback: If there's a prompt, also try to interpolate it
-                        let interpolated = prompt_val.replace("{{input}}", &input_str);
-                        new_payload.insert("prompt".to_string(), serde_json::json!(interpolated));
+                // Approval steps submit their input for review, the execution pauses once
+                // nothing else runs
+                if step.step_type == STEP_TYPE_APPROVAL {
+                    let record_id = progress.wait(db, index, &input).await;
+                    let requested = if lineage.depth() > 0 {
+                        Err("Approval steps cannot run inside sub-flows".to_string())
                     } else {
-                        // If no template, inject current_data as raw format
-                        new_payload.insert("prompt".to_string(), serde_json::json!(input_str));
+                        FlowApprovalService::request(
+                            db,
+                            &execution_id,
+                            &flow_id,
+                            step,
+                            &graph.labels[index],
+                            record_id,
+                            &input,
+                            &context,
+                        )
+                        .await
+                    };
+                    match requested {
+                        Ok(approval) => {
+                            approvals.push(approval.id);
+                            continue;
+                        }
+                        Err(e) => {
+                            let outcome = StepOutcome {
+                                error: Some(e.clone()),
+                                ..Default::default()
+                            };
+                            progress.finish(db, index, STEP_FAILED, outcome).await;
+                            Self::mark_failed(
+                                db,
+                                &execution_id,
+                                serde_json::json!({ "error": e, "step": step.step_order, "step_id": step.id }),
+                            )
+                            .await;
+                            return Err(FlowError::new(format!(
+                                "Flow failed at approval step {}: {}",
+                                step.step_order, e
+                            )));
+                        }
                     }
+                }
 
-                    // 2. Extract control parameters and map them for Ollama payload construction
-                    if let Some(system_prompt) = config_obj.get("system_prompt") {
-                        new_payload.insert("system".to_string(), system_prompt.clone());
-                    }
-                    if let Some(temperature) = config_obj.get("temperature") {
-                        // Put inside options for standard Ollama? Or root? We'll put root, standard Ollama API accepts temperature at root
-                        new_payload.insert("temperature".to_string(), temperature.clone());
-                    }
-                    if let Some(model) = config_obj.get("model") {
-                        new_payload.insert("model".to_string(), model.clone());
-                    }
+                if step.step_type == STEP_TYPE_FLOW {
+                    progress.start(db, index, &input).await;
+                    running.push(
+                        Self::run_sub_flow(
+                            state,
+                            index,
+                            step,
+                            input,
+                            &lineage,
+                            execution_id.clone(),
+                        )
+                        .boxed(),
+                    );
+                    continue;
+                }
 
-                    // 3. Keep moving existing objects if they are not the mapped ones
-                    for (k, v) in config_obj {
-                        if k != "template"
-                            && k != "prompt"
-                            && k != "system_prompt"
-                            && k != "temperature"
-                            && k != "model"
-                        {
-                            new_payload.insert(k.clone(), v.clone());
-                        }
+                // Find the task in charge of the step (map steps may run a sub-flow instead)
+                let task = match Self::find_task(db, step).await {
+                    Ok(task) => task,
+                    Err(err) => {
+                        let outcome = StepOutcome {
+                            error: Some(err.clone()),
+                            ..Default::default()
+                        };
+                        progress.finish(db, index, STEP_FAILED, outcome).await;
+                        Self::mark_failed(db, &execution_id, serde_json::json!({ "error": err }))
+                            .await;
+                        return Err(err.into());
                     }
+                };
+                names[index] = task
+                    .as_ref()
+                    .map(|t| t.name.clone())
+                    .unwrap_or_else(|| step.step_type.clone());
 
-                    // Only map stream: false to avoid streaming chunks response parsing
-                    new_payload.insert("stream".to_string(), serde_json::json!(false));
+                if step.step_type == STEP_TYPE_MAP {
+                    progress.start(db, index, &input).await;
+                    let sub_flow = match &task {
+                        Some(_) => None,
+                        None => Some((lineage.clone(), execution_id.clone())),
+                    };
+                    running.push(
+                        Self::run_map(state, index, step, task, sub_flow, input, context.clone())
+                            .boxed(),
+                    );
+                    continue;
+                }
+                let task = match task {
+                    Some(task) => task,
+                    None => {
+                        let err = format!("Step {} has no task", step.id);
+                        let outcome = StepOutcome {
+                            error: Some(err.clone()),
+                            ..Default::default()
+                        };
+                        progress.finish(db, index, STEP_FAILED, outcome).await;
+                        Self::mark_failed(db, &execution_id, serde_json::json!({ "error": err }))
+                            .await;
+                        return Err(err.into());
+                    }
+                };
 
-                    payload = serde_json::Value::Object(new_payload);
+                // Render the step config against the flow input and the earlier step outputs
+                let mut payload = match &step.config {
+                    Some(config) => match Self::build_payload(config, &input, &context) {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            let outcome = StepOutcome {
+                                error: Some(e.clone()),
+                                ..Default::default()
+                            };
+                            progress.finish(db, index, STEP_FAILED, outcome).await;
+                            Self::mark_failed(
+                                db,
+                                &execution_id,
+                                serde_json::json!({ "error": e, "step": step.step_order, "step_id": step.id }),
+                            )
+                            .await;
+                            return Err(FlowError::new(format!(
+                                "Flow failed at step {}, task {}: {}",
+                                step.step_order, names[index], e
+                            )));
+                        }
+                    },
+                    None => input,
+                };
+                if deps.is_empty() {
+                    payload = SessionService::apply_history(&history, &payload);
                 }
-            }
 
-            // Execute the agent and handle retry resilience
-            let result = AgentClient::execute_task(http_client, &endpoint, &payload).await;
+                progress.start(db, index, &payload).await;
+                running.push(
+                    Self::run_step(state, index, task, payload, policies[index].clone()).boxed(),
+                );
+            }
 
-            let (response_json, retries_used) = match &result {
-                Ok((res, retries)) => (res.clone(), *retries),
-                Err((err_msg, retries)) => (serde_json::json!({ "error": err_msg }), *retries),
+            // Execute the tasks through the gateway path (retry resilience and logging included)
+            let StepRun {
+                index,
+                result,
+                retries,
+                agent_log_id,
+                mut recovery,
+            } = match tokio::select! {
+                finished = running.next() => finished,
+                // Dropping the running steps aborts their in-flight agent calls
+                _ = cancelled.cancelled() => {
+                    drop(running);
+                    return Err(Self::cancel(db, &execution_id, &mut progress).await);
+                }
+            } {
+                Some(finished) => finished,
+                None => break,
             };
 
-            let ping_db = db.clone();
-            let log_agent_id = agent.id.clone();
-            let log_payload = payload.clone();
-            let log_response = response_json.clone();
-            tokio::spawn(async move {
-                let _ = AgentLogService::create(
-                    &ping_db,
-                    log_agent_id,
-                    log_payload,
-                    log_response,
-                    retries_used,
-                )
-                .await;
-            });
-
-            // If the execution failed, stop the flow and mark as Failed
+            // If a step failed and its error policy does not recover, stop the flow (dropping
+            // the branches still running) and mark as Failed
             match result {
-                Ok((resp, _)) => {
-                    current_data = resp;
+                Ok(resp) => {
+                    let step = &steps[index];
+                    if let Some(handler) = handlers[index] {
+                        selected.insert(
+                            index,
+                            Self::handler_selection(&graph, index, handler, false),
+                        );
+                    }
+                    context.add_step(&step.id, step.name.as_deref(), &resp);
+                    let outcome = StepOutcome {
+                        response: Some(resp.clone()),
+                        error: None,
+                        retries,
+                        agent_log_id,
+                        recovery: recovery.to_value(),
+                    };
+                    progress.finish(db, index, STEP_COMPLETED, outcome).await;
+                    outputs[index] = Some(resp);
                 }
-                Err((e, _)) => {
-                    let _ = FlowExecutionRepository::update_status(
+                Err(e) => {
+                    let step = &steps[index];
+                    let recovered = policies[index]
+                        .as_ref()
+                        .and_then(|policy| policy.recover(&graph.labels[index], &e, &mut recovery));
+                    if let Some(output) = recovered {
+                        // The failed step settles as completed, keeping its error
+                        if let Some(handler) = handlers[index] {
+                            selected.insert(
+                                index,
+                                Self::handler_selection(&graph, index, handler, true),
+                            );
+                        }
+                        context.add_step(&step.id, step.name.as_deref(), &output);
+                        let outcome = StepOutcome {
+                            response: Some(output.clone()),
+                            error: Some(e.message),
+                            retries,
+                            agent_log_id,
+                            recovery: recovery.to_value(),
+                        };
+                        progress.finish(db, index, STEP_COMPLETED, outcome).await;
+                        outputs[index] = Some(output);
+                        continue;
+                    }
+                    let outcome = StepOutcome {
+                        response: None,
+                        error: Some(e.message.clone()),
+                        retries,
+                        agent_log_id,
+                        recovery: recovery.to_value(),
+                    };
+                    progress.finish(db, index, STEP_FAILED, outcome).await;
+                    Self::mark_failed(
                         db,
-                        execution_id.clone(),
-                        "Failed",
-                        Some(serde_json::json!({ "error": e, "step": step.step_order })),
+                        &execution_id,
+                        serde_json::json!({
+                            "error": e.message,
+                            "class": e.class,
+                            "contract": e.contract,
+                            "step": step.step_order,
+                            "step_id": step.id
+                        }),
                     )
                     .await;
-                    return Err(format!(
-                        "Flow failed at step {}, agent {}: {}",
-                        step.step_order, agent.slug, e
-                    ));
+                    return Err(FlowError {
+                        message: format!(
+                            "Flow failed at step {}, task {}: {}",
+                            step.step_order, names[index], e.message
+                        ),
+                        contract: e.contract,
+                        paused: None,
+                    });
                 }
             }
         }
 
+        if !approvals.is_empty() {
+            return Err(Self::pause(db, &execution_id, approvals).await);
+        }
+
+        let current_data = Self::flow_output(&graph, &mut outputs);
+
+        if let Some(session_id) = session_id {
+            if let Err(e) = SessionService::record_exchange(
+                state,
+                session_id.clone(),
+                &initial_input,
+                &current_data,
+            )
+            .await
+            {
+                tracing::warn!("Failed to record session {}: {}", session_id, e);
+            }
+        }
+
         // 4. Mark execution as completed and store the final output
         let _ = FlowExecutionRepository::update_status(
             db,
@@ -225,4 +930,611 @@ impl Service {
 
         Ok(current_data)
     }
+
+    // Loads the task of a step, if it has one.
+    async fn find_task(
+        db: &DatabaseConnection,
+        step: &flow_step::Model,
+    ) -> Result<Option<agent_task::Model>, String> {
+        let task_id = match &step.task_id {
+            Some(task_id) => task_id.clone(),
+            None => return Ok(None),
+        };
+        agent_task::Entity::find_by_id(task_id.clone())
+            .one(db)
+            .await
+            .map_err(|e| format!("Database error fetching task: {}", e))?
+            .map(Some)
+            .ok_or_else(|| format!("Task {} not found for step", task_id))
+    }
+
+    // Runs the task of a step, collecting the retries and agent logs of the calls. The
+    // error policy of the step retries the task, then runs its fallback task once.
+    async fn run_step(
+        state: &AppState,
+        index: usize,
+        task: agent_task::Model,
+        payload: serde_json::Value,
+        policy: Option<ErrorPolicy>,
+    ) -> StepRun {
+        let run = async {
+            let mut recovery = Recovery::default();
+            let mut retries = 0;
+            let mut attempt = 0;
+            let error = loop {
+                match TaskRunner::execute(state, &task, &payload).await {
+                    Ok((output, call_retries)) => {
+                        if attempt > 0 {
+                            recovery.outcome = Some(RECOVERY_RETRIED.to_string());
+                        }
+                        return (Ok(output), retries + call_retries, recovery);
+                    }
+                    Err(e) => {
+                        retries += e.retries;
+                        match &policy {
+                            Some(policy) if policy.retries(attempt, &e) => {
+                                recovery.attempt(&e);
+                                recovery.retries += 1;
+                                tokio::time::sleep(policy.backoff(attempt)).await;
+                                attempt += 1;
+                            }
+                            _ => break e,
+                        }
+                    }
+                }
+            };
+
+            let fallback_task_id = match policy.as_ref().and_then(|p| p.fallback(&error)) {
+                Some(task_id) => task_id.to_string(),
+                None => return (Err(error), retries, recovery),
+            };
+            recovery.attempt(&error);
+            recovery.fallback_task_id = Some(fallback_task_id.clone());
+            let fallback = match agent_task::Entity::find_by_id(fallback_task_id.clone())
+                .one(&state.db)
+                .await
+            {
+                Ok(Some(fallback)) => fallback,
+                Ok(None) => {
+                    let e = format!("Fallback task {} not found", fallback_task_id);
+                    return (Err(AgentCallError::internal(e)), retries, recovery);
+                }
+                Err(e) => {
+                    let e = format!("Database error fetching task: {}", e);
+                    return (Err(AgentCallError::internal(e)), retries, recovery);
+                }
+            };
+            match TaskRunner::execute(state, &fallback, &payload).await {
+                Ok((output, call_retries)) => {
+                    recovery.outcome = Some(RECOVERY_FALLBACK.to_string());
+                    (Ok(output), retries + call_retries, recovery)
+                }
+                Err(e) => {
+                    let retries = retries + e.retries;
+                    (Err(e), retries, recovery)
+                }
+            }
+        };
+        let ((result, retries, recovery), logs) = TaskRunner::traced(run).await;
+        StepRun {
+            index,
+            result,
+            retries,
+            agent_log_id: logs.last().cloned(),
+            recovery,
+        }
+    }
+
+    // Runs a map step over the items of its input. Without task, `sub_flow` holds the
+    // lineage and the execution of the step, parents of the child executions.
+    async fn run_map(
+        state: &AppState,
+        index: usize,
+        step: &flow_step::Model,
+        task: Option<agent_task::Model>,
+        sub_flow: Option<(Lineage, String)>,
+        input: serde_json::Value,
+        context: TemplateContext,
+    ) -> StepRun {
+        let run = async {
+            let config = MapConfig::from_config(step.config.as_ref(), task.is_some())?;
+            let child = match (&config.flow_id, &sub_flow) {
+                (Some(flow_id), Some((lineage, execution_id))) => {
+                    Some(lineage.child(execution_id, &step.id, flow_id)?)
+                }
+                _ => None,
+            };
+            FlowMapService::run(
+                state,
+                &config,
+                task.as_ref(),
+                child.as_ref(),
+                &input,
+                &context,
+            )
+            .await
+        };
+        let (result, logs) = TaskRunner::traced(run).await;
+        StepRun {
+            index,
+            result: result.map_err(AgentCallError::internal),
+            retries: 0,
+            agent_log_id: logs.last().cloned(),
+            recovery: Recovery::default(),
+        }
+    }
+
+    // Runs the flow of a sub-flow step in a child execution, its output becomes the step output.
+    async fn run_sub_flow(
+        state: &AppState,
+        index: usize,
+        step: &flow_step::Model,
+        input: serde_json::Value,
+        lineage: &Lineage,
+        execution_id: String,
+    ) -> StepRun {
+        let run = async {
+            let config =
+                SubFlowConfig::from_config(step.config.as_ref()).map_err(FlowError::new)?;
+            let child = lineage
+                .child(&execution_id, &step.id, &config.flow_id)
+                .map_err(FlowError::new)?;
+            Self::execute_sub_flow(state, input, child).await
+        };
+        StepRun {
+            index,
+            result: run.await.map_err(|e| AgentCallError {
+                contract: e.contract,
+                ..AgentCallError::internal(e.message)
+            }),
+            retries: 0,
+            agent_log_id: None,
+            recovery: Recovery::default(),
+        }
+    }
+
+    /// Input of a step: the flow input for root steps, the upstream output for steps
+    /// with a single dependency, and a map of the active upstream outputs keyed by step
+    /// name (or id) for fan-in steps.
+    pub fn step_input(
+        graph: &FlowGraph,
+        index: usize,
+        active: &[usize],
+        outputs: &[Option<serde_json::Value>],
+        initial_input: &serde_json::Value,
+    ) -> serde_json::Value {
+        match graph.dependencies[index].as_slice() {
+            [] => initial_input.clone(),
+            [dep] => outputs[*dep].clone().unwrap_or_default(),
+            _ => {
+                let mut upstream = serde_json::Map::new();
+                for dep in active {
+                    upstream.insert(
+                        graph.labels[*dep].clone(),
+                        outputs[*dep].clone().unwrap_or_default(),
+                    );
+                }
+                serde_json::Value::Object(upstream)
+            }
+        }
+    }
+
+    // Error policies of the steps, and the index of their error handler, which must
+    // depend on the step.
+    #[allow(clippy::type_complexity)]
+    fn error_policies(
+        graph: &FlowGraph,
+        steps: &[flow_step::Model],
+    ) -> Result<(Vec<Option<ErrorPolicy>>, Vec<Option<usize>>), String> {
+        let mut policies = Vec::with_capacity(steps.len());
+        let mut handlers = Vec::with_capacity(steps.len());
+        for (index, step) in steps.iter().enumerate() {
+            let policy = ErrorPolicy::from_config(step.config.as_ref())
+                .map_err(|e| format!("Step {}: {}", graph.labels[index], e))?;
+            let handler = match &policy {
+                Some(policy) => policy.handler_index(graph, index)?,
+                None => None,
+            };
+            policies.push(policy);
+            handlers.push(handler);
+        }
+        Ok((policies, handlers))
+    }
+
+    /// Steps run after a step with an error handler: the handler alone when the step
+    /// failed, its other dependent steps otherwise.
+    pub fn handler_selection(
+        graph: &FlowGraph,
+        index: usize,
+        handler: usize,
+        failed: bool,
+    ) -> Vec<usize> {
+        (0..graph.dependencies.len())
+            .filter(|i| graph.dependencies[*i].contains(&index))
+            .filter(|i| (*i == handler) == failed)
+            .collect()
+    }
+
+    /// Output of a step completed by an earlier run, with the downstream steps it selects
+    /// and its router decision. Routers pass their input through and select the steps of
+    /// their recorded decision, steps with an error handler select the steps their
+    /// recorded recovery ran.
+    fn reuse(
+        graph: &FlowGraph,
+        index: usize,
+        step: &flow_step::Model,
+        record: &flow_step_execution::Model,
+        input: serde_json::Value,
+        handler: Option<usize>,
+    ) -> (
+        serde_json::Value,
+        Option<Vec<usize>>,
+        Option<serde_json::Value>,
+    ) {
+        if step.step_type == STEP_TYPE_ROUTER {
+            let decision = record.response.clone().unwrap_or_default();
+            let next = Self::selected_steps(graph, &decision);
+            return (input, Some(next), Some(decision));
+        }
+        let selection = handler.map(|handler| {
+            let failed = Recovery::handled(record.recovery.as_ref());
+            Self::handler_selection(graph, index, handler, failed)
+        });
+        (record.response.clone().unwrap_or_default(), selection, None)
+    }
+
+    /// Steps selected by a recorded router decision.
+    pub fn selected_steps(graph: &FlowGraph, decision: &serde_json::Value) -> Vec<usize> {
+        decision
+            .get("next")
+            .and_then(|next| next.as_array())
+            .map(|next| {
+                next.iter()
+                    .filter_map(|target| target.as_str().and_then(|t| graph.find(t)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Evaluates a router step and resolves the selected steps, which must depend on it.
+    pub fn route(
+        graph: &FlowGraph,
+        index: usize,
+        step: &flow_step::Model,
+        input: &serde_json::Value,
+    ) -> Result<(Vec<usize>, serde_json::Value), String> {
+        let config = RouterConfig::from_config(step.config.as_ref())?;
+        let decision = config.evaluate(input)?;
+
+        let mut next = Vec::new();
+        for target in &decision.next {
+            next.push(RouterConfig::target_index(graph, index, target)?);
+        }
+
+        let record = serde_json::json!({
+            "step_id": step.id,
+            "step": graph.labels[index],
+            "route": decision.route,
+            "value": decision.value,
+            "next": decision.next,
+        });
+        Ok((next, record))
+    }
+
+    // Marks the execution as Cancelled, recording the steps interrupted while running.
+    async fn cancel(
+        db: &DatabaseConnection,
+        execution_id: &str,
+        progress: &mut Progress,
+    ) -> FlowError {
+        let interrupted: Vec<usize> = (0..progress.steps.len())
+            .filter(|i| [STEP_RUNNING, STEP_WAITING].contains(&progress.steps[*i].status.as_str()))
+            .collect();
+        let mut names = Vec::new();
+        for index in interrupted {
+            names.push(progress.steps[index].name.clone());
+            let outcome = StepOutcome {
+                error: Some("Cancelled while running".to_string()),
+                ..Default::default()
+            };
+            progress.finish(db, index, STEP_CANCELLED, outcome).await;
+        }
+
+        let _ = FlowExecutionRepository::cancel_children(
+            db,
+            execution_id.to_string(),
+            "Parent execution cancelled",
+        )
+        .await;
+        let _ = FlowApprovalRepository::cancel_pending(
+            db,
+            execution_id.to_string(),
+            "Execution cancelled",
+        )
+        .await;
+        let _ = FlowExecutionRepository::update_status(
+            db,
+            execution_id.to_string(),
+            "Cancelled",
+            Some(serde_json::json!({ "error": "Execution cancelled", "interrupted_steps": names })),
+        )
+        .await;
+        FlowError::new(format!("Flow execution {} was cancelled", execution_id))
+    }
+
+    // Pauses the execution until its approvals are decided, releasing its worker.
+    async fn pause(
+        db: &DatabaseConnection,
+        execution_id: &str,
+        approvals: Vec<String>,
+    ) -> FlowError {
+        let _ = FlowExecutionRepository::update_status(
+            db,
+            execution_id.to_string(),
+            "WaitingForApproval",
+            None,
+        )
+        .await;
+
+        // Approvals decided while the other branches were running settle the execution now
+        match FlowApprovalRepository::find_by_ids(db, approvals).await {
+            Ok(approvals) => {
+                if let Err(e) = FlowApprovalService::settle(db, execution_id, &approvals).await {
+                    tracing::warn!("Failed to settle execution {}: {}", execution_id, e);
+                }
+            }
+            Err(e) => tracing::warn!("Failed to fetch approvals of {}: {}", execution_id, e),
+        }
+
+        FlowError {
+            message: format!("Flow execution {} is waiting for approval", execution_id),
+            contract: None,
+            paused: Some(execution_id.to_string()),
+        }
+    }
+
+    // Marks the execution as Failed with the error details as output, cancelling the
+    // sub-flows of the branches it stops.
+    async fn mark_failed(db: &DatabaseConnection, execution_id: &str, error: serde_json::Value) {
+        let _ = FlowExecutionRepository::cancel_children(
+            db,
+            execution_id.to_string(),
+            "Parent execution failed",
+        )
+        .await;
+        let _ = FlowApprovalRepository::cancel_pending(
+            db,
+            execution_id.to_string(),
+            "Execution failed",
+        )
+        .await;
+        let _ = FlowStepExecutionRepository::cancel_waiting(db, execution_id.to_string()).await;
+        let _ = FlowExecutionRepository::update_status(
+            db,
+            execution_id.to_string(),
+            "Failed",
+            Some(error),
+        )
+        .await;
+    }
+
+    /// Output of the flow: the output of its last step, or a map keyed by step name
+    /// (or id) when several branches end the flow. Skipped branches are left out.
+    pub fn flow_output(
+        graph: &FlowGraph,
+        outputs: &mut [Option<serde_json::Value>],
+    ) -> serde_json::Value {
+        let sinks: Vec<usize> = graph
+            .sinks()
+            .into_iter()
+            .filter(|sink| outputs[*sink].is_some())
+            .collect();
+        if let [sink] = sinks.as_slice() {
+            return outputs[*sink].take().unwrap_or_default();
+        }
+
+        let mut result = serde_json::Map::new();
+        for sink in sinks {
+            result.insert(
+                graph.labels[sink].clone(),
+                outputs[sink].take().unwrap_or_default(),
+            );
+        }
+        serde_json::Value::Object(result)
+    }
+
+    /// Maps a step config onto the payload sent to the agent, rendering the template/prompt
+    /// and system prompt with `context` (see `TemplateContext`). `{{input}}` still expands
+    /// to a clean string version of the current data.
+    pub fn build_payload(
+        config: &serde_json::Value,
+        current_data: &serde_json::Value,
+        context: &TemplateContext,
+    ) -> Result<serde_json::Value, String> {
+        let config_obj = match config.as_object() {
+            Some(obj) => obj,
+            None => return Ok(current_data.clone()),
+        };
+
+        let mut new_payload = serde_json::Map::new();
+
+        // Extract a clean string representation of the current data (e.g. previous step output)
+        let input_str = TaskRunner::response_text(current_data);
+
+        // 1. Template Interpolation
+        if let Some(template_val) = config_obj.get("template").and_then(|v| v.as_str()) {
+            let interpolated = context.render(template_val, current_data)?;
+            new_payload.insert("prompt".to_string(), serde_json::json!(interpolated));
+        } else if let Some(prompt_val) = config_obj.get("prompt").and_then(|v| v.as_str()) {
+            // Fallback: If there's a prompt, also try to interpolate it
+            let interpolated = context.render(prompt_val, current_data)?;
+            new_payload.insert("prompt".to_string(), serde_json::json!(interpolated));
+        } else {
+            // If no template, inject current_data as raw format
+            new_payload.insert("prompt".to_string(), serde_json::json!(input_str));
+        }
+
+        // 2. Extract control parameters and map them for Ollama payload construction
+        if let Some(system_prompt) = config_obj.get("system_prompt") {
+            let system = match system_prompt.as_str() {
+                Some(text) => serde_json::json!(context.render(text, current_data)?),
+                None => system_prompt.clone(),
+            };
+            new_payload.insert("system".to_string(), system);
+        }
+        if let Some(temperature) = config_obj.get("temperature") {
+            // Put inside options for standard Ollama? Or root? We'll put root, standard Ollama API accepts temperature at root
+            new_payload.insert("temperature".to_string(), temperature.clone());
+        }
+        if let Some(model) = config_obj.get("model") {
+            new_payload.insert("model".to_string(), model.clone());
+        }
+
+        // 3. Keep moving existing objects if they are not the mapped ones
+        for (k, v) in config_obj {
+            if k != "template"
+                && k != "prompt"
+                && k != "system_prompt"
+                && k != "temperature"
+                && k != "model"
+                && k != "error_policy"
+            {
+                new_payload.insert(k.clone(), v.clone());
+            }
+        }
+
+        // Only map stream: false to avoid streaming chunks response parsing
+        new_payload.insert("stream".to_string(), serde_json::json!(false));
+
+        Ok(serde_json::Value::Object(new_payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::flow_error_policy::RECOVERY_HANDLED;
+    use serde_json::json;
+
+    fn step(id: &str, depends_on: Option<serde_json::Value>) -> flow_step::Model {
+        flow_step::Model {
+            depends_on,
+            ..flow_step::Model::fixture(id)
+        }
+    }
+
+    #[test]
+    fn tracks_step_transitions() {
+        let steps = vec![step("a", None), step("b", None), step("c", None)];
+        let graph = FlowGraph::from_steps(&steps).unwrap();
+        let mut progress = Progress::new("execution", &steps, &graph);
+        assert!(progress.steps.iter().all(|s| s.status == STEP_PENDING));
+
+        progress.mark(0, STEP_RUNNING, None);
+        assert!(progress.steps[0].started_at.is_some());
+        assert!(progress.steps[0].completed_at.is_none());
+        progress.mark(0, STEP_FAILED, Some("boom"));
+        assert_eq!(progress.steps[0].error.as_deref(), Some("boom"));
+        assert!(progress.steps[0].completed_at.is_some());
+        progress.mark(0, STEP_RUNNING, None);
+        assert_eq!(progress.steps[0].error, None);
+
+        progress.mark(1, STEP_SKIPPED, None);
+        assert!(progress.steps[1].started_at.is_none());
+        assert!(progress.steps[1].completed_at.is_some());
+
+        progress.mark(2, STEP_WAITING, None);
+        assert!(progress.steps[2].started_at.is_some());
+        assert!(progress.steps[2].completed_at.is_none());
+        let saved = serde_json::to_value(&progress.steps).unwrap();
+        assert_eq!(saved[2]["status"], json!(STEP_WAITING));
+        assert_eq!(saved[2]["name"], json!("c"));
+    }
+
+    fn record(
+        step_id: &str,
+        response: serde_json::Value,
+        recovery: Option<serde_json::Value>,
+    ) -> flow_step_execution::Model {
+        flow_step_execution::Model {
+            id: format!("record-{}", step_id),
+            execution_id: "execution".to_string(),
+            step_id: step_id.to_string(),
+            step_name: step_id.to_string(),
+            status: STEP_COMPLETED.to_string(),
+            payload: None,
+            response: Some(response),
+            error: None,
+            retries: 0,
+            agent_log_id: None,
+            recovery,
+            started_at: Some(chrono::Utc::now().into()),
+            completed_at: Some(chrono::Utc::now().into()),
+            duration_ms: Some(5),
+        }
+    }
+
+    #[test]
+    fn reuses_completed_steps() {
+        let steps = vec![
+            step("route", None).with_type(STEP_TYPE_ROUTER),
+            step("left", Some(json!(["route"]))),
+            step("right", Some(json!(["route"]))),
+        ];
+        let graph = FlowGraph::from_steps(&steps).unwrap();
+        let input = json!({ "text": "hi" });
+
+        // Routers pass their input through and keep their recorded decision
+        let decision = json!({ "step": "route", "next": ["right"] });
+        let (output, selection, reused) = Service::reuse(
+            &graph,
+            0,
+            &steps[0],
+            &record("route", decision.clone(), None),
+            input.clone(),
+            None,
+        );
+        assert_eq!(output, input);
+        assert_eq!(selection, Some(vec![2]));
+        assert_eq!(reused, Some(decision));
+
+        let (output, selection, reused) = Service::reuse(
+            &graph,
+            1,
+            &steps[1],
+            &record("left", json!({ "label": "ok" }), None),
+            input.clone(),
+            None,
+        );
+        assert_eq!(output, json!({ "label": "ok" }));
+        assert_eq!((selection, reused), (None, None));
+
+        // A handled failure selects the error handler again, a success the other steps
+        let steps = vec![
+            step("classify", None),
+            step("notify", Some(json!(["classify"]))),
+            step("next", Some(json!(["classify"]))),
+        ];
+        let graph = FlowGraph::from_steps(&steps).unwrap();
+        let handled = json!({ "outcome": RECOVERY_HANDLED, "handler": "notify" });
+        let failed = record("classify", json!({ "error": "boom" }), Some(handled));
+        let (_, selection, _) =
+            Service::reuse(&graph, 0, &steps[0], &failed, input.clone(), Some(1));
+        assert_eq!(selection, Some(vec![1]));
+        let succeeded = record("classify", json!("ok"), None);
+        let (_, selection, _) = Service::reuse(&graph, 0, &steps[0], &succeeded, input, Some(1));
+        assert_eq!(selection, Some(vec![2]));
+    }
+
+    #[test]
+    fn restores_completed_steps() {
+        let steps = vec![step("a", None)];
+        let graph = FlowGraph::from_steps(&steps).unwrap();
+        let mut progress = Progress::new("execution", &steps, &graph);
+        progress.mark(0, STEP_FAILED, Some("boom"));
+        progress.restore(0, &record("a", json!("done"), None));
+        assert_eq!(progress.steps[0].status, STEP_COMPLETED);
+        assert_eq!(progress.steps[0].error, None);
+        assert!(progress.steps[0].started_at.is_some());
+    }
 }
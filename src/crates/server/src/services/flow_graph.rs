@@ -0,0 +1,221 @@
+use crate::models::flow_step;
+use std::collections::{BTreeSet, HashMap};
+
+/// Minimal view of a step needed to resolve the dependency graph of a flow.
+#[derive(Debug, Clone)]
+pub struct GraphNode {
+    pub id: String,
+    pub name: Option<String>,
+    /* Ids or names of the upstream steps, `None` means "the previous step by order" */
+    pub depends_on: Option<Vec<String>>,
+}
+
+impl GraphNode {
+    pub fn from_step(step: &flow_step::Model) -> Result<Self, String> {
+        let depends_on = match &step.depends_on {
+            Some(value) if !value.is_null() => Some(
+                serde_json::from_value::<Vec<String>>(value.clone()).map_err(|_| {
+                    format!(
+                        "depends_on of step {} must be an array of step ids",
+                        step.id
+                    )
+                })?,
+            ),
+            _ => None,
+        };
+
+        Ok(Self {
+            id: step.id.clone(),
+            name: step.name.clone(),
+            depends_on,
+        })
+    }
+}
+
+/// Dependency graph of a flow. Nodes keep the index they had in the `step_order` sorted input.
+#[derive(Debug)]
+pub struct FlowGraph {
+    /* Upstream nodes of every node */
+    pub dependencies: Vec<Vec<usize>>,
+
+    /* Topological order, ties broken by step order */
+    pub order: Vec<usize>,
+
+    /* Key of every node in fan-in maps and multi-sink outputs: its name, or its id */
+    pub labels: Vec<String>,
+
+    /* Node of every step id and name */
+    references: HashMap<String, usize>,
+}
+
+impl FlowGraph {
+    /// Builds the graph of flow steps sorted by `step_order`.
+    pub fn from_steps(steps: &[flow_step::Model]) -> Result<Self, String> {
+        let nodes = steps
+            .iter()
+            .map(GraphNode::from_step)
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::build(&nodes)
+    }
+
+    /// Resolves the dependencies of the nodes and rejects unknown references and cycles.
+    pub fn build(nodes: &[GraphNode]) -> Result<Self, String> {
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for (i, node) in nodes.iter().enumerate() {
+            index.insert(node.id.as_str(), i);
+        }
+        for (i, node) in nodes.iter().enumerate() {
+            if let Some(name) = &node.name {
+                if index
+                    .insert(name.as_str(), i)
+                    .is_some_and(|other| other != i)
+                {
+                    return Err(format!("Step name {} is not unique in the flow", name));
+                }
+            }
+        }
+
+        let mut dependencies = Vec::with_capacity(nodes.len());
+        for (i, node) in nodes.iter().enumerate() {
+            let deps = match &node.depends_on {
+                None if i == 0 => Vec::new(),
+                None => vec![i - 1],
+                Some(refs) => {
+                    let mut deps = Vec::new();
+                    for reference in refs {
+                        let dep = *index.get(reference.as_str()).ok_or_else(|| {
+                            format!("Step {} depends on unknown step {}", node.id, reference)
+                        })?;
+                        if dep == i {
+                            return Err(format!("Step {} cannot depend on itself", node.id));
+                        }
+                        if !deps.contains(&dep) {
+                            deps.push(dep);
+                        }
+                    }
+                    deps
+                }
+            };
+            dependencies.push(deps);
+        }
+
+        // Kahn's algorithm, always picking the lowest step order among the ready nodes
+        let mut pending: Vec<usize> = dependencies.iter().map(|deps| deps.len()).collect();
+        let mut ready: BTreeSet<usize> = (0..nodes.len()).filter(|i| pending[*i] == 0).collect();
+        let mut order = Vec::with_capacity(nodes.len());
+        while let Some(next) = ready.pop_first() {
+            order.push(next);
+            for (i, deps) in dependencies.iter().enumerate() {
+                if deps.contains(&next) {
+                    pending[i] -= 1;
+                    if pending[i] == 0 {
+                        ready.insert(i);
+                    }
+                }
+            }
+        }
+
+        let labels: Vec<String> = nodes
+            .iter()
+            .map(|node| node.name.clone().unwrap_or_else(|| node.id.clone()))
+            .collect();
+
+        if order.len() < nodes.len() {
+            let cycle: Vec<&str> = (0..nodes.len())
+                .filter(|i| pending[*i] > 0)
+                .map(|i| labels[i].as_str())
+                .collect();
+            return Err(format!("Flow steps form a cycle: {}", cycle.join(", ")));
+        }
+
+        let references = index
+            .into_iter()
+            .map(|(reference, i)| (reference.to_string(), i))
+            .collect();
+
+        Ok(Self {
+            dependencies,
+            order,
+            labels,
+            references,
+        })
+    }
+
+    /// Finds a node by step id or name.
+    pub fn find(&self, reference: &str) -> Option<usize> {
+        self.references.get(reference).copied()
+    }
+
+    /// The node and every node depending on it, directly or not, in topological order.
+    pub fn downstream(&self, index: usize) -> Vec<usize> {
+        let mut reached = vec![false; self.dependencies.len()];
+        reached[index] = true;
+        for &node in &self.order {
+            if self.dependencies[node].iter().any(|dep| reached[*dep]) {
+                reached[node] = true;
+            }
+        }
+        self.order.iter().copied().filter(|i| reached[*i]).collect()
+    }
+
+    /// Nodes no other node depends on, their outputs make up the flow output.
+    pub fn sinks(&self) -> Vec<usize> {
+        (0..self.dependencies.len())
+            .filter(|i| !self.dependencies.iter().any(|deps| deps.contains(i)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: &str, depends_on: Option<&[&str]>) -> GraphNode {
+        GraphNode {
+            id: id.to_string(),
+            name: None,
+            depends_on: depends_on.map(|deps| deps.iter().map(|d| d.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn steps_without_dependencies_form_a_chain() {
+        let graph = FlowGraph::build(&[node("a", None), node("b", None), node("c", None)]).unwrap();
+        assert_eq!(graph.dependencies, vec![vec![], vec![0], vec![1]]);
+        assert_eq!(graph.order, vec![0, 1, 2]);
+        assert_eq!(graph.sinks(), vec![2]);
+    }
+
+    #[test]
+    fn branches_fan_in_by_name() {
+        let mut join = node("d", Some(&["left", "c"]));
+        join.name = Some("join".to_string());
+        let mut left = node("b", Some(&["a"]));
+        left.name = Some("left".to_string());
+
+        let graph =
+            FlowGraph::build(&[node("a", None), left, node("c", Some(&["a"])), join]).unwrap();
+        assert_eq!(graph.dependencies[3], vec![1, 2]);
+        assert_eq!(graph.order, vec![0, 1, 2, 3]);
+        assert_eq!(graph.labels[3], "join");
+        assert_eq!(graph.sinks(), vec![3]);
+        assert_eq!(graph.downstream(1), vec![1, 3]);
+        assert_eq!(graph.downstream(0), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn cycles_and_unknown_steps_are_rejected() {
+        let cycle = FlowGraph::build(&[
+            node("a", Some(&[])),
+            node("b", Some(&["c"])),
+            node("c", Some(&["b"])),
+        ]);
+        assert!(cycle.unwrap_err().contains("cycle"));
+
+        let unknown = FlowGraph::build(&[node("a", Some(&["missing"]))]);
+        assert!(unknown.unwrap_err().contains("unknown step"));
+
+        let itself = FlowGraph::build(&[node("a", Some(&["a"]))]);
+        assert!(itself.is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::models::agent_task;
+use crate::services::{
+    flow_executor::Service as FlowExecutor, flow_subflow::Lineage, flow_template::TemplateContext,
+    json_path, task_runner::Service as TaskRunner,
+};
+use crate::state::AppState;
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// The first failed item fails the step (and the flow).
+pub const ON_ERROR_FAIL_FAST: &str = "fail_fast";
+/// Failed items are left out of the results and listed in `errors`.
+pub const ON_ERROR_SKIP: &str = "skip";
+/// Failed items keep their position in the results as `{"error": ...}`.
+pub const ON_ERROR_COLLECT: &str = "collect";
+
+/*
+ * Config of a map step, e.g.
+ * { "items": "$.documents", "concurrency": 4, "on_error": "skip",
+ *   "item_config": { "template": "Summarize: {{input}}" } }
+ * Every item runs the task of the step, or the sub-flow `flow_id` when the step has no task
+ * (one child execution per item).
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct MapConfig {
+    /* JSONPath of the array in the step input */
+    pub items: String,
+
+    /* Items processed at the same time */
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+
+    /* "fail_fast" (default), "skip" or "collect" */
+    #[serde(default = "default_on_error")]
+    pub on_error: String,
+
+    /* Step config applied to every item to build its payload, the raw item otherwise */
+    pub item_config: Option<Value>,
+
+    /* Flow run for every item instead of a task */
+    pub flow_id: Option<String>,
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+fn default_on_error() -> String {
+    ON_ERROR_FAIL_FAST.to_string()
+}
+
+impl MapConfig {
+    /// Parses the config of a map step, which needs exactly one of a task or a sub-flow.
+    pub fn from_config(config: Option<&Value>, has_task: bool) -> Result<Self, String> {
+        let config = config.ok_or_else(|| "Map steps require a config".to_string())?;
+        let parsed: Self = serde_json::from_value(config.clone())
+            .map_err(|e| format!("Invalid map config: {}", e))?;
+
+        json_path::select(&Value::Null, &parsed.items)?;
+        if parsed.concurrency == 0 {
+            return Err("concurrency must be greater than zero".to_string());
+        }
+        if ![ON_ERROR_FAIL_FAST, ON_ERROR_SKIP, ON_ERROR_COLLECT]
+            .contains(&parsed.on_error.as_str())
+        {
+            return Err(format!("Unknown map error policy {}", parsed.on_error));
+        }
+        if has_task == parsed.flow_id.is_some() {
+            return Err("Map steps run either a task_id or a config flow_id".to_string());
+        }
+        Ok(parsed)
+    }
+}
+
+pub struct Service;
+
+impl Service {
+    /// Runs the task (or sub-flow) over every item of the selected array, at most
+    /// `concurrency` at a time, and returns `{"results": [...], "errors": [...]}` with
+    /// the results in item order. Item templates see the item as `input`/`previous` and
+    /// the outputs of the steps completed before the map step. `sub_flow` is the lineage
+    /// of the child executions when the items run a sub-flow.
+    pub async fn run(
+        state: &AppState,
+        config: &MapConfig,
+        task: Option<&agent_task::Model>,
+        sub_flow: Option<&Lineage>,
+        input: &Value,
+        context: &TemplateContext,
+    ) -> Result<Value, String> {
+        let items = match json_path::select(input, &config.items)? {
+            Some(Value::Array(items)) => items.clone(),
+            Some(_) => return Err(format!("{} is not an array", config.items)),
+            None => return Err(format!("No items found at {}", config.items)),
+        };
+
+        // `buffered` keeps the results in the order of the items
+        let total = items.len();
+        let mut outcomes = stream::iter(items.into_iter().enumerate())
+            .map(|(index, item)| async move {
+                (
+                    index,
+                    Self::run_item(state, config, task, sub_flow, context, item).await,
+                )
+            })
+            .buffered(config.concurrency);
+
+        let mut results = Vec::with_capacity(total);
+        let mut errors = Vec::new();
+        while let Some((index, outcome)) = outcomes.next().await {
+            match outcome {
+                Ok(output) => results.push(output),
+                Err(e) if config.on_error == ON_ERROR_FAIL_FAST => {
+                    return Err(format!("Item {} failed: {}", index, e));
+                }
+                Err(e) => {
+                    if config.on_error == ON_ERROR_COLLECT {
+                        results.push(json!({ "error": e }));
+                    }
+                    errors.push(json!({ "index": index, "error": e }));
+                }
+            }
+        }
+
+        Ok(json!({ "results": results, "errors": errors }))
+    }
+
+    async fn run_item(
+        state: &AppState,
+        config: &MapConfig,
+        task: Option<&agent_task::Model>,
+        sub_flow: Option<&Lineage>,
+        context: &TemplateContext,
+        item: Value,
+    ) -> Result<Value, String> {
+        let payload = match &config.item_config {
+            Some(item_config) => FlowExecutor::build_payload(item_config, &item, context)?,
+            None => item,
+        };
+
+        match (task, sub_flow) {
+            (Some(task), _) => TaskRunner::execute(state, task, &payload)
+                .await
+                .map(|(output, _)| output)
+                .map_err(|e| e.message),
+            (None, Some(lineage)) => {
+                FlowExecutor::execute_sub_flow(state, payload, lineage.clone())
+                    .await
+                    .map_err(|e| e.message)
+            }
+            (None, None) => Err("Map step has neither a task nor a flow".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_needs_exactly_one_target() {
+        let with_flow = json!({ "items": "$.documents", "flow_id": "summarize" });
+        assert!(MapConfig::from_config(Some(&with_flow), false).is_ok());
+        assert!(MapConfig::from_config(Some(&with_flow), true).is_err());
+
+        let task_only = json!({ "items": "$.documents", "on_error": "collect" });
+        let config = MapConfig::from_config(Some(&task_only), true).unwrap();
+        assert_eq!(config.concurrency, 4);
+        assert!(MapConfig::from_config(Some(&task_only), false).is_err());
+
+        let bad_policy = json!({ "items": "$.documents", "on_error": "ignore" });
+        assert!(MapConfig::from_config(Some(&bad_policy), true).is_err());
+    }
+}
@@ -0,0 +1,357 @@
+use crate::services::{flow_graph::FlowGraph, json_path, task_runner::Service as TaskRunner};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+/*
+ * Config of a router step, e.g.
+ * { "routes": [
+ *     { "when": { "label": "NEGATIVE" }, "next": "escalate" },
+ *     { "when": { "path": "$.score", "op": "gte", "value": 0.8 }, "next": ["publish", "notify"] }
+ *   ],
+ *   "default": "review" }
+ * The first matching route selects the downstream steps (by name or id) that run,
+ * the other steps depending on the router are skipped.
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct RouterConfig {
+    pub routes: Vec<Route>,
+
+    /* Steps run when no route matches, none when omitted */
+    pub default: Option<Targets>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Route {
+    pub when: Condition,
+    pub next: Targets,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Targets {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Targets {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            Targets::One(target) => vec![target.clone()],
+            Targets::Many(targets) => targets.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Condition {
+    /* Classification label of the input (`label`/`sentiment` field or plain text), case insensitive */
+    Label {
+        label: String,
+    },
+
+    /* Comparison of the value selected by a JSONPath */
+    Path {
+        path: String,
+        #[serde(default = "default_op")]
+        op: String,
+        value: Option<Value>,
+    },
+}
+
+fn default_op() -> String {
+    "eq".to_string()
+}
+
+const OPERATORS: [&str; 11] = [
+    "eq",
+    "ne",
+    "gt",
+    "gte",
+    "lt",
+    "lte",
+    "contains",
+    "in",
+    "exists",
+    "not_exists",
+    "matches",
+];
+
+/// Outcome of a router evaluation, recorded on the flow execution.
+#[derive(Debug, Clone)]
+pub struct Decision {
+    /* Index of the matching route, `None` for the default branch */
+    pub route: Option<usize>,
+    pub next: Vec<String>,
+    /* Value the matching condition looked at */
+    pub value: Option<Value>,
+}
+
+impl RouterConfig {
+    /// Parses and validates the config of a router step.
+    pub fn from_config(config: Option<&Value>) -> Result<Self, String> {
+        let config = config.ok_or_else(|| "Router steps require a config".to_string())?;
+        let parsed: Self = serde_json::from_value(config.clone())
+            .map_err(|e| format!("Invalid router config: {}", e))?;
+
+        for route in &parsed.routes {
+            if let Condition::Path { path, op, value } = &route.when {
+                json_path::select(&Value::Null, path)?;
+                if !OPERATORS.contains(&op.as_str()) {
+                    return Err(format!("Unknown router operator {}", op));
+                }
+                if value.is_none() && op != "exists" && op != "not_exists" {
+                    return Err(format!("Router operator {} requires a value", op));
+                }
+                if op == "matches" {
+                    let pattern = value.as_ref().and_then(|v| v.as_str()).unwrap_or_default();
+                    Regex::new(pattern).map_err(|e| format!("Invalid router pattern: {}", e))?;
+                }
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Steps named by the routes and the default branch.
+    pub fn targets(&self) -> Vec<String> {
+        let defaults = self.default.iter().flat_map(Targets::to_vec);
+        self.routes
+            .iter()
+            .flat_map(|route| route.next.to_vec())
+            .chain(defaults)
+            .collect()
+    }
+
+    /// Rejects a route or default target that is not a step depending on the router,
+    /// `index` being the node of the router in `graph`.
+    pub fn check_targets(&self, graph: &FlowGraph, index: usize) -> Result<(), String> {
+        for target in self.targets() {
+            Self::target_index(graph, index, &target)?;
+        }
+        Ok(())
+    }
+
+    /// Node of a target of the router at `index`, which must depend on it.
+    pub fn target_index(graph: &FlowGraph, index: usize, target: &str) -> Result<usize, String> {
+        graph
+            .find(target)
+            .filter(|i| graph.dependencies[*i].contains(&index))
+            .ok_or_else(|| {
+                format!(
+                    "Route target {} of step {} must be a step depending on it",
+                    target, graph.labels[index]
+                )
+            })
+    }
+
+    /// Picks the branch of the first matching route, or the default one.
+    pub fn evaluate(&self, input: &Value) -> Result<Decision, String> {
+        for (index, route) in self.routes.iter().enumerate() {
+            let (matched, value) = route.when.evaluate(input)?;
+            if matched {
+                return Ok(Decision {
+                    route: Some(index),
+                    next: route.next.to_vec(),
+                    value,
+                });
+            }
+        }
+
+        Ok(Decision {
+            route: None,
+            next: self
+                .default
+                .as_ref()
+                .map(Targets::to_vec)
+                .unwrap_or_default(),
+            value: None,
+        })
+    }
+}
+
+impl Condition {
+    fn evaluate(&self, input: &Value) -> Result<(bool, Option<Value>), String> {
+        match self {
+            Condition::Label { label } => {
+                let actual = Self::label_of(input);
+                Ok((
+                    actual.eq_ignore_ascii_case(label.trim()),
+                    Some(Value::String(actual)),
+                ))
+            }
+            Condition::Path { path, op, value } => {
+                let selected = json_path::select(input, path)?.filter(|v| !v.is_null());
+                let expected = value.as_ref().unwrap_or(&Value::Null);
+                let matched = match (op.as_str(), selected) {
+                    ("exists", selected) => selected.is_some(),
+                    ("not_exists", selected) => selected.is_none(),
+                    ("ne", selected) => !selected.is_some_and(|v| Self::equals(v, expected)),
+                    (_, None) => false,
+                    ("eq", Some(v)) => Self::equals(v, expected),
+                    ("gt", Some(v)) => Self::compare(v, expected).is_some_and(|o| o.is_gt()),
+                    ("gte", Some(v)) => Self::compare(v, expected).is_some_and(|o| o.is_ge()),
+                    ("lt", Some(v)) => Self::compare(v, expected).is_some_and(|o| o.is_lt()),
+                    ("lte", Some(v)) => Self::compare(v, expected).is_some_and(|o| o.is_le()),
+                    ("contains", Some(v)) => match (v, expected) {
+                        (Value::String(s), Value::String(needle)) => s.contains(needle.as_str()),
+                        (Value::Array(items), needle) => {
+                            items.iter().any(|item| Self::equals(item, needle))
+                        }
+                        (Value::Object(obj), Value::String(key)) => obj.contains_key(key),
+                        _ => false,
+                    },
+                    ("in", Some(v)) => expected
+                        .as_array()
+                        .is_some_and(|items| items.iter().any(|item| Self::equals(v, item))),
+                    ("matches", Some(v)) => {
+                        let pattern = expected.as_str().unwrap_or_default();
+                        let regex = Regex::new(pattern)
+                            .map_err(|e| format!("Invalid router pattern: {}", e))?;
+                        regex.is_match(&TaskRunner::response_text(v))
+                    }
+                    (other, _) => return Err(format!("Unknown router operator {}", other)),
+                };
+                Ok((matched, selected.cloned()))
+            }
+        }
+    }
+
+    /// Numbers are compared by value so that `1` equals `1.0`.
+    fn equals(a: &Value, b: &Value) -> bool {
+        match (a.as_f64(), b.as_f64()) {
+            (Some(x), Some(y)) => x == y,
+            _ => a == b,
+        }
+    }
+
+    fn compare(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+        match (a, b) {
+            (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+            _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        }
+    }
+
+    /// Extracts a classification label such as the sentiment agent `POSITIVE`/`NEGATIVE`
+    /// answer: `label` or `sentiment` fields (also in a first array element), else the text.
+    fn label_of(input: &Value) -> String {
+        let candidate = match input {
+            Value::Array(items) => items.first().unwrap_or(input),
+            other => other,
+        };
+        let text = ["label", "sentiment"]
+            .iter()
+            .find_map(|key| candidate.get(key).and_then(|v| v.as_str()))
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| TaskRunner::response_text(candidate));
+
+        text.trim()
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::flow_graph::GraphNode;
+    use serde_json::json;
+
+    fn router(config: Value) -> RouterConfig {
+        RouterConfig::from_config(Some(&config)).unwrap()
+    }
+
+    #[test]
+    fn routes_on_sentiment_label() {
+        let config = router(json!({
+            "routes": [
+                { "when": { "label": "POSITIVE" }, "next": "thank" },
+                { "when": { "label": "negative" }, "next": ["escalate", "notify"] }
+            ],
+            "default": "review"
+        }));
+
+        let decision = config
+            .evaluate(&json!({ "success": true, "sentiment": "NEGATIVE." }))
+            .unwrap();
+        assert_eq!(decision.route, Some(1));
+        assert_eq!(decision.next, vec!["escalate", "notify"]);
+
+        let decision = config.evaluate(&json!("Positive")).unwrap();
+        assert_eq!(decision.next, vec!["thank"]);
+
+        let decision = config.evaluate(&json!({ "sentiment": "NEUTRAL" })).unwrap();
+        assert_eq!(decision.route, None);
+        assert_eq!(decision.next, vec!["review"]);
+    }
+
+    #[test]
+    fn routes_on_json_path_comparisons() {
+        let config = router(json!({
+            "routes": [
+                { "when": { "path": "$.score", "op": "gte", "value": 0.8 }, "next": "high" },
+                { "when": { "path": "$.tags", "op": "contains", "value": "urgent" }, "next": "urgent" },
+                { "when": { "path": "$.text", "op": "matches", "value": "^re:" }, "next": "reply" }
+            ]
+        }));
+
+        assert_eq!(
+            config.evaluate(&json!({ "score": 0.9 })).unwrap().next,
+            vec!["high"]
+        );
+        assert_eq!(
+            config
+                .evaluate(&json!({ "score": 0.1, "tags": ["urgent"] }))
+                .unwrap()
+                .next,
+            vec!["urgent"]
+        );
+        assert_eq!(
+            config.evaluate(&json!({ "text": "re: hi" })).unwrap().next,
+            vec!["reply"]
+        );
+        assert!(config
+            .evaluate(&json!({ "score": "n/a" }))
+            .unwrap()
+            .next
+            .is_empty());
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let unknown_op = json!({ "routes": [{ "when": { "path": "$.a", "op": "like", "value": 1 }, "next": "x" }] });
+        assert!(RouterConfig::from_config(Some(&unknown_op)).is_err());
+
+        let missing_value =
+            json!({ "routes": [{ "when": { "path": "$.a", "op": "gt" }, "next": "x" }] });
+        assert!(RouterConfig::from_config(Some(&missing_value)).is_err());
+
+        assert!(RouterConfig::from_config(None).is_err());
+    }
+
+    #[test]
+    fn targets_must_depend_on_the_router() {
+        let node = |id: &str, depends_on: Option<Vec<&str>>| GraphNode {
+            id: id.to_string(),
+            name: None,
+            depends_on: depends_on.map(|deps| deps.iter().map(|d| d.to_string()).collect()),
+        };
+        let graph = FlowGraph::build(&[
+            node("route", None),
+            node("a", Some(vec!["route"])),
+            node("b", Some(vec!["route"])),
+            node("c", Some(vec!["a"])),
+        ])
+        .unwrap();
+
+        let config = |default: &str| {
+            router(json!({
+                "routes": [{ "when": { "label": "x" }, "next": ["a", "b"] }],
+                "default": default
+            }))
+        };
+        assert!(config("b").check_targets(&graph, 0).is_ok());
+        assert!(config("c").check_targets(&graph, 0).is_err());
+        assert!(config("unknown").check_targets(&graph, 0).is_err());
+    }
+}
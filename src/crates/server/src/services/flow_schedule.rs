@@ -0,0 +1,436 @@
+use crate::models::flow_schedule::{
+    self, CreateFlowSchedulePayload, UpdateFlowSchedulePayload, OVERLAP_ALLOW,
+    OVERLAP_CANCEL_PREVIOUS, OVERLAP_SKIP,
+};
+use crate::models::flow_schedule_run::{self, RUN_FAILED, RUN_SKIPPED, RUN_TRIGGERED};
+use crate::repositories::{
+    flow::Repository as FlowRepository, flow_schedule::Repository as FlowScheduleRepository,
+    flow_schedule_run::Repository as FlowScheduleRunRepository,
+};
+use crate::services::flow_executor::Service as FlowExecutor;
+use crate::services::flow_version::Revision;
+use crate::state::AppState;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use sea_orm::{DatabaseConnection, DbErr, Set};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::info;
+
+/// Interval at which the scheduler looks for due schedules.
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+/// Runs returned by the history of a schedule.
+const HISTORY_LIMIT: u64 = 100;
+
+/// Cron expression evaluated in a timezone.
+pub struct CronSchedule {
+    schedule: cron::Schedule,
+    timezone: Tz,
+}
+
+impl CronSchedule {
+    /// Parses a 5 field Unix expression (minute precision, days of week 0-7 from Sunday),
+    /// or a 6/7 field one starting with seconds (days of week 1-7 from Sunday), and an
+    /// IANA timezone name.
+    pub fn parse(expression: &str, timezone: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let expression = match fields.as_slice() {
+            [minute, hour, day, month, weekday] => format!(
+                "0 {} {} {} {} {}",
+                minute,
+                hour,
+                day,
+                month,
+                unix_weekdays(weekday)?
+            ),
+            _ => expression.trim().to_string(),
+        };
+        let schedule = cron::Schedule::from_str(&expression)
+            .map_err(|e| format!("Invalid cron expression: {}", e))?;
+        let timezone =
+            Tz::from_str(timezone).map_err(|_| format!("Unknown timezone {}", timezone))?;
+        Ok(Self { schedule, timezone })
+    }
+
+    /// First occurrence strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedule
+            .after(&after.with_timezone(&self.timezone))
+            .next()
+            .map(|next| next.with_timezone(&Utc))
+    }
+}
+
+// Translates the day of week field of a Unix expression (Sunday is 0 or 7) to the
+// numbering of the cron crate (Sunday is 1). Names are kept as they are.
+fn unix_weekdays(field: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid day of week {}", field);
+    let number = |value: &str| -> Result<u32, String> {
+        value
+            .parse::<u32>()
+            .ok()
+            .filter(|day| *day <= 7)
+            .ok_or_else(invalid)
+    };
+
+    let mut days: Vec<u32> = Vec::new();
+    let mut parts: Vec<String> = Vec::new();
+    for part in field.split(',') {
+        if !part.chars().any(|c| c.is_ascii_digit()) {
+            parts.push(part.to_string());
+            continue;
+        }
+        let (base, step) = match part.split_once('/') {
+            Some((base, step)) => (
+                base,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(invalid)?,
+            ),
+            None => (part, 1),
+        };
+        let (first, last) = match base.split_once('-') {
+            _ if base == "*" => (0, 6),
+            Some((first, last)) => (number(first)?, number(last)?),
+            None if step > 1 => (number(base)?, 6),
+            None => (number(base)?, number(base)?),
+        };
+        if first > last {
+            return Err(invalid());
+        }
+        days.extend((first..=last).step_by(step as usize).map(|day| day % 7 + 1));
+    }
+    days.sort_unstable();
+    days.dedup();
+    parts.extend(days.iter().map(|day| day.to_string()));
+    Ok(parts.join(","))
+}
+
+fn validate_overlap_policy(policy: &str) -> Result<(), String> {
+    if [OVERLAP_SKIP, OVERLAP_ALLOW, OVERLAP_CANCEL_PREVIOUS].contains(&policy) {
+        Ok(())
+    } else {
+        Err(format!("Unknown overlap policy {}", policy))
+    }
+}
+
+pub struct Service;
+
+impl Service {
+    /// Spawns the scheduler, starting an execution for every occurrence of the enabled
+    /// schedules. Occurrences missed while no gateway was running are coalesced into a
+    /// single run. Every instance can run a scheduler, each occurrence triggers once.
+    pub fn start(state: AppState) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TICK_INTERVAL);
+            loop {
+                interval.tick().await;
+                match FlowScheduleRepository::find_due(&state.db, Utc::now().into()).await {
+                    Ok(due) => {
+                        for schedule in due {
+                            Self::trigger(&state, schedule).await;
+                        }
+                    }
+                    Err(e) => tracing::warn!("Scheduler failed to fetch due schedules: {}", e),
+                }
+            }
+        });
+    }
+
+    // Claims the due occurrence of a schedule and starts its execution, applying the
+    // overlap policy when the previous execution still runs.
+    async fn trigger(state: &AppState, schedule: flow_schedule::Model) {
+        let db = &state.db;
+        let scheduled_for = match schedule.next_run_at {
+            Some(scheduled_for) => scheduled_for,
+            None => return,
+        };
+        let next_run_at = CronSchedule::parse(&schedule.cron, &schedule.timezone)
+            .ok()
+            .and_then(|cron| cron.next_after(Utc::now()))
+            .map(|next| next.into());
+        match FlowScheduleRepository::advance(db, schedule.id.clone(), scheduled_for, next_run_at)
+            .await
+        {
+            Ok(true) => {}
+            // Triggered by another instance
+            Ok(false) => return,
+            Err(e) => {
+                tracing::warn!("Scheduler failed to claim schedule {}: {}", schedule.id, e);
+                return;
+            }
+        }
+
+        let previous =
+            FlowScheduleRunRepository::find_active_execution(db, schedule.id.clone()).await;
+        if let Ok(Some(previous)) = previous {
+            match schedule.overlap_policy.as_str() {
+                OVERLAP_ALLOW => {}
+                OVERLAP_CANCEL_PREVIOUS => {
+                    if let Err(e) = FlowExecutor::cancel_execution(state, previous.id.clone()).await
+                    {
+                        tracing::warn!("Scheduler failed to cancel {}: {}", previous.id, e);
+                    }
+                }
+                _ => {
+                    let reason = format!("Execution {} is still {}", previous.id, previous.status);
+                    Self::record(
+                        db,
+                        &schedule,
+                        None,
+                        RUN_SKIPPED,
+                        scheduled_for,
+                        Some(reason),
+                    )
+                    .await;
+                    return;
+                }
+            }
+        }
+
+        let input = schedule
+            .input
+            .clone()
+            .unwrap_or_else(|| serde_json::json!({}));
+        match FlowExecutor::start_flow(
+            state,
+            schedule.flow_id.clone(),
+            input,
+            None,
+            None,
+            Revision::Latest,
+        )
+        .await
+        {
+            Ok(execution) => {
+                info!(
+                    "Schedule {} started execution {} of flow {}",
+                    schedule.id, execution.id, schedule.flow_id
+                );
+                Self::record(
+                    db,
+                    &schedule,
+                    Some(execution.id),
+                    RUN_TRIGGERED,
+                    scheduled_for,
+                    None,
+                )
+                .await;
+            }
+            Err(e) => {
+                Self::record(
+                    db,
+                    &schedule,
+                    None,
+                    RUN_FAILED,
+                    scheduled_for,
+                    Some(e.message),
+                )
+                .await;
+            }
+        }
+    }
+
+    async fn record(
+        db: &DatabaseConnection,
+        schedule: &flow_schedule::Model,
+        execution_id: Option<String>,
+        status: &str,
+        scheduled_for: sea_orm::prelude::DateTimeWithTimeZone,
+        error: Option<String>,
+    ) {
+        if let Err(e) = FlowScheduleRunRepository::create(
+            db,
+            schedule.id.clone(),
+            execution_id,
+            status,
+            Some(scheduled_for),
+            error,
+        )
+        .await
+        {
+            tracing::warn!("Failed to record run of schedule {}: {}", schedule.id, e);
+        }
+    }
+
+    pub async fn create_schedule(
+        db: &DatabaseConnection,
+        payload: CreateFlowSchedulePayload,
+    ) -> Result<flow_schedule::Model, String> {
+        if FlowRepository::find_by_id(db, payload.flow_id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Err(format!("Flow {} not found", payload.flow_id));
+        }
+        let timezone = payload.timezone.unwrap_or_else(|| "UTC".to_string());
+        let cron = CronSchedule::parse(&payload.cron, &timezone)?;
+        let overlap_policy = payload
+            .overlap_policy
+            .unwrap_or_else(|| OVERLAP_SKIP.to_string());
+        validate_overlap_policy(&overlap_policy)?;
+
+        let now = Utc::now();
+        let schedule = flow_schedule::ActiveModel {
+            id: Set(uuid::Uuid::new_v4().to_string()),
+            flow_id: Set(payload.flow_id),
+            name: Set(payload.name),
+            cron: Set(payload.cron),
+            timezone: Set(timezone),
+            input: Set(payload.input),
+            enabled: Set(payload.enabled.unwrap_or(true)),
+            overlap_policy: Set(overlap_policy),
+            next_run_at: Set(cron.next_after(now).map(|next| next.into())),
+            last_run_at: Set(None),
+            created_at: Set(Some(now.into())),
+            updated_at: Set(Some(now.into())),
+        };
+        FlowScheduleRepository::create(db, schedule)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn get_all_schedules(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<flow_schedule::Model>, DbErr> {
+        FlowScheduleRepository::find_all(db).await
+    }
+
+    pub async fn get_schedule(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<flow_schedule::Model>, DbErr> {
+        FlowScheduleRepository::find_by_id(db, id).await
+    }
+
+    /// Applies the given changes to a schedule. The next occurrence is computed again
+    /// from now, so re-enabling a schedule does not catch up on the missed ones.
+    pub async fn update_schedule(
+        db: &DatabaseConnection,
+        id: String,
+        payload: UpdateFlowSchedulePayload,
+    ) -> Result<Option<flow_schedule::Model>, String> {
+        let schedule = match FlowScheduleRepository::find_by_id(db, id)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            Some(schedule) => schedule,
+            None => return Ok(None),
+        };
+
+        let expression = payload.cron.unwrap_or_else(|| schedule.cron.clone());
+        let timezone = payload
+            .timezone
+            .unwrap_or_else(|| schedule.timezone.clone());
+        let cron = CronSchedule::parse(&expression, &timezone)?;
+        if let Some(policy) = &payload.overlap_policy {
+            validate_overlap_policy(policy)?;
+        }
+
+        let mut active: flow_schedule::ActiveModel = schedule.into();
+        if let Some(name) = payload.name {
+            active.name = Set(Some(name));
+        }
+        if let Some(input) = payload.input {
+            active.input = Set(Some(input));
+        }
+        if let Some(enabled) = payload.enabled {
+            active.enabled = Set(enabled);
+        }
+        if let Some(policy) = payload.overlap_policy {
+            active.overlap_policy = Set(policy);
+        }
+        active.cron = Set(expression);
+        active.timezone = Set(timezone);
+        active.next_run_at = Set(cron.next_after(Utc::now()).map(|next| next.into()));
+        active.updated_at = Set(Some(Utc::now().into()));
+
+        FlowScheduleRepository::update(db, active)
+            .await
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn delete_schedule(db: &DatabaseConnection, id: String) -> Result<u64, DbErr> {
+        FlowScheduleRepository::delete(db, id).await
+    }
+
+    /// Lists the latest runs of a schedule, `None` when the schedule does not exist.
+    pub async fn get_runs(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<Vec<flow_schedule_run::Model>>, DbErr> {
+        if FlowScheduleRepository::find_by_id(db, id.clone())
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        FlowScheduleRunRepository::find_by_schedule(db, id, HISTORY_LIMIT)
+            .await
+            .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn evaluates_cron_in_its_timezone() {
+        let cron = CronSchedule::parse("0 9 * * *", "Europe/Madrid").unwrap();
+        // 09:00 in Madrid is 08:00 UTC in winter and 07:00 UTC in summer
+        assert_eq!(
+            cron.next_after(at("2026-01-10T10:00:00Z")),
+            Some(at("2026-01-11T08:00:00Z"))
+        );
+        assert_eq!(
+            cron.next_after(at("2026-07-10T06:00:00Z")),
+            Some(at("2026-07-10T07:00:00Z"))
+        );
+
+        let with_seconds = CronSchedule::parse("30 */15 * * * *", "UTC").unwrap();
+        assert_eq!(
+            with_seconds.next_after(at("2026-01-10T10:00:00Z")),
+            Some(at("2026-01-10T10:00:30Z"))
+        );
+    }
+
+    #[test]
+    fn numbers_weekdays_from_sunday_as_unix() {
+        // Friday 2026-01-09 10:00 UTC: the next weekday is Monday
+        let weekdays = CronSchedule::parse("0 9 * * 1-5", "UTC").unwrap();
+        assert_eq!(
+            weekdays.next_after(at("2026-01-09T10:00:00Z")),
+            Some(at("2026-01-12T09:00:00Z"))
+        );
+        for sunday in ["0 9 * * 0", "0 9 * * 7", "0 9 * * SUN", "0 9 * * 6-7"] {
+            let cron = CronSchedule::parse(sunday, "UTC").unwrap();
+            let next = cron.next_after(at("2026-01-09T10:00:00Z")).unwrap();
+            assert!(next <= at("2026-01-11T09:00:00Z"), "{}", sunday);
+        }
+        let fridays = CronSchedule::parse("0 9 * * 5", "UTC").unwrap();
+        assert_eq!(
+            fridays.next_after(at("2026-01-09T10:00:00Z")),
+            Some(at("2026-01-16T09:00:00Z"))
+        );
+        assert_eq!(unix_weekdays("*/2").unwrap(), "1,3,5,7");
+        assert_eq!(unix_weekdays("*").unwrap(), "*");
+        assert!(CronSchedule::parse("0 9 * * 8", "UTC").is_err());
+        assert!(CronSchedule::parse("0 9 * * 5-1", "UTC").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_expressions_and_timezones() {
+        assert!(CronSchedule::parse("every minute", "UTC").is_err());
+        assert!(CronSchedule::parse("0 9 * * *", "Mars/Olympus").is_err());
+    }
+}
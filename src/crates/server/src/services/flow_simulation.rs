@@ -0,0 +1,358 @@
+use crate::models::flow::{
+    FlowSimulation, SimulatedStep, SOURCE_MOCK, SOURCE_PASSTHROUGH, SOURCE_PLACEHOLDER,
+    SOURCE_RECORDED,
+};
+use crate::models::flow_execution::{STEP_COMPLETED, STEP_FAILED, STEP_SKIPPED};
+use crate::models::flow_step::{
+    self, STEP_TYPE_APPROVAL, STEP_TYPE_FLOW, STEP_TYPE_MAP, STEP_TYPE_ROUTER,
+};
+use crate::repositories::{
+    flow::Repository as FlowRepository, flow_execution::Repository as FlowExecutionRepository,
+    flow_step_execution::Repository as FlowStepExecutionRepository,
+};
+use crate::services::flow_error_policy::ErrorPolicy;
+use crate::services::flow_executor::Service as FlowExecutor;
+use crate::services::flow_graph::FlowGraph;
+use crate::services::flow_map::MapConfig;
+use crate::services::flow_template::TemplateContext;
+use crate::services::flow_version::{Revision, Service as FlowVersionService};
+use crate::services::json_path;
+use sea_orm::DatabaseConnection;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/*
+ * Dry run of a flow: steps are visited in the order they would run, routers are evaluated
+ * and the payload of every task is rendered, but no agent is called. The output of a step
+ * is its mock, else its output recorded by an earlier execution, else a placeholder
+ * `{"response": "<simulated output of ...>"}`. Approval steps are considered approved,
+ * steps never fail (error handlers are skipped), sub-flows are not simulated.
+ */
+pub struct Simulation<'a> {
+    pub mocks: &'a HashMap<String, Value>,
+    /* Outputs recorded by an earlier execution, keyed by step id */
+    pub recorded: &'a HashMap<String, Value>,
+}
+
+impl Simulation<'_> {
+    /// Simulates the steps of a flow, sorted by step order, with `input` as flow input.
+    /// The first failing step (an invalid template or route) ends the simulation.
+    pub fn run(&self, steps: &[flow_step::Model], input: &Value) -> FlowSimulation {
+        let mut simulation = FlowSimulation {
+            version: None,
+            steps: Vec::new(),
+            output: None,
+            error: None,
+        };
+        let graph = match FlowGraph::from_steps(steps) {
+            Ok(graph) => graph,
+            Err(e) => {
+                simulation.error = Some(e);
+                return simulation;
+            }
+        };
+
+        let mut outputs: Vec<Option<Value>> = vec![None; steps.len()];
+        let mut selected: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut context = TemplateContext::new(input.clone());
+        for &index in &graph.order {
+            let step = &steps[index];
+            let label = &graph.labels[index];
+            let mut simulated = SimulatedStep {
+                step_id: step.id.clone(),
+                name: label.clone(),
+                step_type: step.step_type.clone(),
+                status: STEP_COMPLETED.to_string(),
+                input: None,
+                payload: None,
+                output: None,
+                output_source: None,
+                decision: None,
+                error: None,
+            };
+
+            let deps = &graph.dependencies[index];
+            let active: Vec<usize> = deps
+                .iter()
+                .copied()
+                .filter(|dep| outputs[*dep].is_some())
+                .filter(|dep| selected.get(dep).is_none_or(|next| next.contains(&index)))
+                .collect();
+            if !deps.is_empty() && active.is_empty() {
+                simulated.status = STEP_SKIPPED.to_string();
+                simulation.steps.push(simulated);
+                continue;
+            }
+            let step_input = FlowExecutor::step_input(&graph, index, &active, &outputs, input);
+
+            match self.step(&graph, index, step, &step_input, &context, &mut simulated) {
+                Ok(output) => {
+                    if let Some(decision) = &simulated.decision {
+                        selected.insert(index, FlowExecutor::selected_steps(&graph, decision));
+                    }
+                    // Simulated steps succeed, their error handler is skipped
+                    let handler = ErrorPolicy::from_config(step.config.as_ref())
+                        .ok()
+                        .flatten()
+                        .and_then(|policy| policy.handler)
+                        .and_then(|handler| graph.find(&handler));
+                    if let Some(handler) = handler {
+                        selected.insert(
+                            index,
+                            FlowExecutor::handler_selection(&graph, index, handler, false),
+                        );
+                    }
+                    context.add_step(&step.id, step.name.as_deref(), &output);
+                    simulated.output = Some(output.clone());
+                    outputs[index] = Some(output);
+                }
+                Err(e) => {
+                    simulated.status = STEP_FAILED.to_string();
+                    simulated.error = Some(e.clone());
+                    simulated.input = Some(step_input);
+                    simulation.steps.push(simulated);
+                    simulation.error = Some(format!("Step {} failed: {}", label, e));
+                    return simulation;
+                }
+            }
+            simulated.input = Some(step_input);
+            simulation.steps.push(simulated);
+        }
+
+        simulation.output = Some(FlowExecutor::flow_output(&graph, &mut outputs));
+        simulation
+    }
+
+    // Mock of a step, by name or by id
+    fn mock(&self, label: &str, step: &flow_step::Model) -> Option<&Value> {
+        self.mocks.get(label).or_else(|| self.mocks.get(&step.id))
+    }
+
+    // Renders the payload of a step and returns its simulated output
+    fn step(
+        &self,
+        graph: &FlowGraph,
+        index: usize,
+        step: &flow_step::Model,
+        input: &Value,
+        context: &TemplateContext,
+        simulated: &mut SimulatedStep,
+    ) -> Result<Value, String> {
+        match step.step_type.as_str() {
+            STEP_TYPE_ROUTER => {
+                let (_, decision) = FlowExecutor::route(graph, index, step, input)?;
+                simulated.decision = Some(decision);
+                simulated.output_source = Some(SOURCE_PASSTHROUGH.to_string());
+                return Ok(input.clone());
+            }
+            STEP_TYPE_APPROVAL => {
+                if let Some(output) = self.mock(&graph.labels[index], step) {
+                    simulated.output_source = Some(SOURCE_MOCK.to_string());
+                    return Ok(output.clone());
+                }
+                simulated.output_source = Some(SOURCE_PASSTHROUGH.to_string());
+                return Ok(input.clone());
+            }
+            STEP_TYPE_FLOW => simulated.payload = Some(input.clone()),
+            STEP_TYPE_MAP => {
+                let config = MapConfig::from_config(step.config.as_ref(), step.task_id.is_some())?;
+                let items = match json_path::select(input, &config.items)? {
+                    Some(Value::Array(items)) => items.clone(),
+                    Some(_) => return Err(format!("{} is not an array", config.items)),
+                    None => return Err(format!("No items found at {}", config.items)),
+                };
+                let payloads = items
+                    .into_iter()
+                    .map(|item| match &config.item_config {
+                        Some(item_config) => {
+                            FlowExecutor::build_payload(item_config, &item, context)
+                        }
+                        None => Ok(item),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                simulated.payload = Some(Value::Array(payloads));
+            }
+            _ => {
+                let payload = match &step.config {
+                    Some(config) => FlowExecutor::build_payload(config, input, context)?,
+                    None => input.clone(),
+                };
+                simulated.payload = Some(payload);
+            }
+        }
+
+        let label = &graph.labels[index];
+        let (output, source) = match (self.mock(label, step), self.recorded.get(&step.id)) {
+            (Some(mock), _) => (mock.clone(), SOURCE_MOCK),
+            (None, Some(recorded)) => (recorded.clone(), SOURCE_RECORDED),
+            (None, None) => {
+                let placeholder = json!({ "response": format!("<simulated output of {}>", label) });
+                match (&step.step_type[..], &simulated.payload) {
+                    (STEP_TYPE_MAP, Some(Value::Array(items))) => (
+                        json!({ "results": vec![placeholder; items.len()], "errors": [] }),
+                        SOURCE_PLACEHOLDER,
+                    ),
+                    _ => (placeholder, SOURCE_PLACEHOLDER),
+                }
+            }
+        };
+        simulated.output_source = Some(source.to_string());
+        Ok(output)
+    }
+}
+
+pub struct Service;
+
+impl Service {
+    /// Simulates a run of a revision of a flow, with the outputs of the steps mocked by
+    /// name (or id) and, for the others, taken from the records of `mock_execution_id`.
+    /// Returns `None` when the flow does not exist.
+    pub async fn simulate(
+        db: &DatabaseConnection,
+        flow_id: String,
+        revision: Revision,
+        input: Value,
+        mocks: HashMap<String, Value>,
+        mock_execution_id: Option<String>,
+    ) -> Result<Option<FlowSimulation>, String> {
+        if FlowRepository::find_by_id(db, flow_id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let version = FlowVersionService::resolve(db, &flow_id, revision).await?;
+        let version_number = version.as_ref().map(|v| v.version);
+        let steps =
+            FlowVersionService::steps_of(db, flow_id.clone(), version.map(|v| v.id)).await?;
+        if steps.is_empty() {
+            return Err("Flow has no steps defined".to_string());
+        }
+
+        let mut recorded = HashMap::new();
+        if let Some(execution_id) = mock_execution_id {
+            let execution = FlowExecutionRepository::find_by_id(db, execution_id.clone())
+                .await
+                .map_err(|e| e.to_string())?
+                .filter(|execution| execution.flow_id == flow_id)
+                .ok_or_else(|| format!("Execution {} of the flow not found", execution_id))?;
+            let records = FlowStepExecutionRepository::find_by_execution(db, execution.id)
+                .await
+                .map_err(|e| e.to_string())?;
+            for record in records {
+                if record.status == STEP_COMPLETED {
+                    if let Some(response) = record.response {
+                        recorded.insert(record.step_id, response);
+                    }
+                }
+            }
+        }
+
+        let simulation = Simulation {
+            mocks: &mocks,
+            recorded: &recorded,
+        };
+        let mut result = simulation.run(&steps, &input);
+        result.version = version_number;
+        Ok(Some(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(id: &str, order: i32, step_type: &str, config: Value) -> flow_step::Model {
+        flow_step::Model::fixture(id)
+            .with_order(order)
+            .with_type(step_type)
+            .with_config(config)
+    }
+
+    #[test]
+    fn renders_payloads_with_mocks_and_routes() {
+        let mut router = step(
+            "route",
+            2,
+            STEP_TYPE_ROUTER,
+            json!({ "routes": [{ "when": { "label": "positive" }, "next": "thank" }], "default": "escalate" }),
+        );
+        router.task_id = None;
+        let mut thank = step(
+            "thank",
+            3,
+            "task",
+            json!({ "template": "Thanks {{ flow.input.user }}" }),
+        );
+        thank.depends_on = Some(json!(["route"]));
+        let mut escalate = step("escalate", 4, "task", json!({ "template": "Escalate" }));
+        escalate.depends_on = Some(json!(["route"]));
+        let steps = vec![
+            step(
+                "classify",
+                1,
+                "task",
+                json!({ "template": "Classify: {{ flow.input.text }}" }),
+            ),
+            router,
+            thank,
+            escalate,
+        ];
+
+        let mocks = HashMap::from([("classify".to_string(), json!({ "label": "positive" }))]);
+        let recorded = HashMap::new();
+        let simulation = Simulation {
+            mocks: &mocks,
+            recorded: &recorded,
+        };
+        let result = simulation.run(&steps, &json!({ "text": "great", "user": "Ada" }));
+        assert_eq!(result.error, None);
+
+        let classify = &result.steps[0];
+        assert_eq!(
+            classify.payload.as_ref().unwrap()["prompt"],
+            json!("Classify: great")
+        );
+        assert_eq!(classify.output_source.as_deref(), Some(SOURCE_MOCK));
+        assert_eq!(
+            result.steps[1].decision.as_ref().unwrap()["next"],
+            json!(["thank"])
+        );
+        let thank = result.steps.iter().find(|s| s.name == "thank").unwrap();
+        assert_eq!(
+            thank.payload.as_ref().unwrap()["prompt"],
+            json!("Thanks Ada")
+        );
+        assert_eq!(thank.output_source.as_deref(), Some(SOURCE_PLACEHOLDER));
+        let escalate = result.steps.iter().find(|s| s.name == "escalate").unwrap();
+        assert_eq!(escalate.status, STEP_SKIPPED);
+        assert_eq!(
+            result.output,
+            Some(json!({ "response": "<simulated output of thank>" }))
+        );
+    }
+
+    #[test]
+    fn stops_at_the_first_failing_template() {
+        let steps = vec![
+            step(
+                "first",
+                1,
+                "task",
+                json!({ "template": "{{ steps.missing.response }}" }),
+            ),
+            step("second", 2, "task", json!({})),
+        ];
+        let (mocks, recorded) = (HashMap::new(), HashMap::new());
+        let simulation = Simulation {
+            mocks: &mocks,
+            recorded: &recorded,
+        };
+        let result = simulation.run(&steps, &json!({}));
+        assert_eq!(result.steps.len(), 1);
+        assert_eq!(result.steps[0].status, STEP_FAILED);
+        assert!(result.error.unwrap().contains("first"));
+        assert_eq!(result.output, None);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::models::flow_execution;
+use crate::models::flow_step::{self, STEP_TYPE_FLOW, STEP_TYPE_MAP};
+use crate::repositories::{
+    flow::Repository as FlowRepository, flow_execution::Repository as FlowExecutionRepository,
+    flow_step::Repository as FlowStepRepository,
+};
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Deepest nesting of sub-flows below a top-level execution.
+pub const MAX_FLOW_DEPTH: usize = 5;
+
+/*
+ * Config of a sub-flow step, e.g. { "flow_id": "..." }.
+ * The sub-flow receives the step input as flow input, its output becomes the step output.
+ */
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubFlowConfig {
+    pub flow_id: String,
+}
+
+impl SubFlowConfig {
+    pub fn from_config(config: Option<&Value>) -> Result<Self, String> {
+        let config = config.ok_or_else(|| "Sub-flow steps require a config".to_string())?;
+        serde_json::from_value(config.clone())
+            .map_err(|e| format!("Invalid sub-flow config: {}", e))
+    }
+}
+
+/// Position of an execution in a tree of sub-flows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lineage {
+    /* Execution and step running the flow, none for top-level executions */
+    pub parent_execution_id: Option<String>,
+    pub parent_step_id: Option<String>,
+    /* Flows from the top-level execution down to this one */
+    pub flows: Vec<String>,
+}
+
+impl Lineage {
+    pub fn root(flow_id: &str) -> Self {
+        Self {
+            parent_execution_id: None,
+            parent_step_id: None,
+            flows: vec![flow_id.to_string()],
+        }
+    }
+
+    /// Flow run by the execution.
+    pub fn flow_id(&self) -> &str {
+        self.flows.last().map(|f| f.as_str()).unwrap_or_default()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.flows.len().saturating_sub(1)
+    }
+
+    /// Lineage of the sub-flow `flow_id` run by `step_id` of `execution_id`, rejecting a
+    /// flow already running above it and nesting deeper than `MAX_FLOW_DEPTH`.
+    pub fn child(&self, execution_id: &str, step_id: &str, flow_id: &str) -> Result<Self, String> {
+        if self.flows.iter().any(|f| f == flow_id) {
+            let mut cycle = self.flows.clone();
+            cycle.push(flow_id.to_string());
+            return Err(format!("Sub-flow cycle: {}", cycle.join(" -> ")));
+        }
+        if self.depth() >= MAX_FLOW_DEPTH {
+            return Err(format!(
+                "Sub-flow {} would nest deeper than {} levels",
+                flow_id, MAX_FLOW_DEPTH
+            ));
+        }
+
+        let mut flows = self.flows.clone();
+        flows.push(flow_id.to_string());
+        Ok(Self {
+            parent_execution_id: Some(execution_id.to_string()),
+            parent_step_id: Some(step_id.to_string()),
+            flows,
+        })
+    }
+}
+
+/// Flow run by a step, for sub-flow steps and map steps running a sub-flow.
+pub fn sub_flow_of(step: &flow_step::Model) -> Option<String> {
+    if step.step_type != STEP_TYPE_FLOW && step.step_type != STEP_TYPE_MAP {
+        return None;
+    }
+    step.config
+        .as_ref()
+        .and_then(|config| config.get("flow_id"))
+        .and_then(|flow_id| flow_id.as_str())
+        .map(|flow_id| flow_id.to_string())
+}
+
+pub struct Service;
+
+impl Service {
+    /// Rejects a sub-flow reference from `flow_id` to an unknown flow, or to a flow that
+    /// already runs `flow_id` (directly or through its own sub-flows).
+    pub async fn check_reference(
+        db: &DatabaseConnection,
+        flow_id: &str,
+        target: &str,
+    ) -> Result<(), String> {
+        if flow_id == target {
+            return Err("A flow cannot run itself as a sub-flow".to_string());
+        }
+        if FlowRepository::find_by_id(db, target.to_string())
+            .await
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Err(format!("Sub-flow {} not found", target));
+        }
+
+        let mut pending = vec![target.to_string()];
+        let mut visited = HashSet::new();
+        while let Some(current) = pending.pop() {
+            if !visited.insert(current.clone()) {
+                continue;
+            }
+            let steps = FlowStepRepository::get_steps_for_flow(db, current.clone())
+                .await
+                .map_err(|e| format!("Failed to fetch flow steps: {}", e))?;
+            for next in steps.iter().filter_map(sub_flow_of) {
+                if next == flow_id {
+                    return Err(format!(
+                        "Flow {} already runs flow {} (through {}), the sub-flow would create a cycle",
+                        target, flow_id, current
+                    ));
+                }
+                pending.push(next);
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the lineage of an execution from its parents, e.g. for a sub-flow
+    /// execution resumed on its own.
+    pub async fn lineage_of(
+        db: &DatabaseConnection,
+        execution: &flow_execution::Model,
+    ) -> Result<Lineage, String> {
+        let mut flows = vec![execution.flow_id.clone()];
+        let mut parent = execution.parent_execution_id.clone();
+        while let Some(id) = parent {
+            let execution = FlowExecutionRepository::find_by_id(db, id.clone())
+                .await
+                .map_err(|e| format!("Database error fetching execution: {}", e))?
+                .ok_or_else(|| format!("Parent execution {} not found", id))?;
+            flows.insert(0, execution.flow_id);
+            parent = execution.parent_execution_id;
+        }
+
+        Ok(Lineage {
+            parent_execution_id: execution.parent_execution_id.clone(),
+            parent_step_id: execution.parent_step_id.clone(),
+            flows,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lineage_rejects_cycles_and_deep_nesting() {
+        let root = Lineage::root("a");
+        let child = root.child("exec-1", "step-1", "b").unwrap();
+        assert_eq!(child.depth(), 1);
+        assert_eq!(child.flow_id(), "b");
+        assert_eq!(child.parent_execution_id.as_deref(), Some("exec-1"));
+
+        let error = child.child("exec-2", "step-2", "a").unwrap_err();
+        assert_eq!(error, "Sub-flow cycle: a -> b -> a");
+
+        let mut deepest = root;
+        for level in 0..MAX_FLOW_DEPTH {
+            deepest = deepest
+                .child("exec", "step", &format!("flow-{}", level))
+                .unwrap();
+        }
+        assert!(deepest.child("exec", "step", "leaf").is_err());
+    }
+}
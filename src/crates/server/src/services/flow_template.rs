@@ -0,0 +1,200 @@
+use crate::services::{json_path, task_runner::Service as TaskRunner};
+use minijinja::{Environment, Error, ErrorKind, UndefinedBehavior};
+use serde_json::{json, Map, Value};
+
+/*
+ * Data available to step templates (minijinja syntax):
+ * - `input`: text of the step input, as the historical `{{input}}` placeholder
+ * - `previous`: the step input as structured data (`{{ previous.items[0].title }}`)
+ * - `flow.input`: the initial flow input
+ * - `steps.<name or id>`: output of every step completed so far
+ * Filters: `json`, `upper`, `truncate(length, end="...")` and `jsonpath("$.path")`.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext {
+    pub flow_input: Value,
+    pub steps: Map<String, Value>,
+}
+
+impl TemplateContext {
+    pub fn new(flow_input: Value) -> Self {
+        Self {
+            flow_input,
+            steps: Map::new(),
+        }
+    }
+
+    /// Stores the output of a step under its id and, if any, its name.
+    pub fn add_step(&mut self, id: &str, name: Option<&str>, output: &Value) {
+        self.steps.insert(id.to_string(), output.clone());
+        if let Some(name) = name {
+            self.steps.insert(name.to_string(), output.clone());
+        }
+    }
+
+    /// Renders a template against this context with `input` as the step input.
+    /// Unknown variables are reported by name instead of rendering as empty strings.
+    pub fn render(&self, template: &str, input: &Value) -> Result<String, String> {
+        let context = json!({
+            "input": TaskRunner::response_text(input),
+            "previous": input,
+            "flow": { "input": self.flow_input },
+            "steps": self.steps,
+        });
+        Self::render_data(template, &context)
+    }
+
+    /// Renders a template against arbitrary data, with the filters of step templates.
+    pub fn render_data(template: &str, context: &Value) -> Result<String, String> {
+        let env = Self::environment();
+        let compiled = env
+            .template_from_str(template)
+            .map_err(|e| format!("Invalid template: {}", Self::describe(&e)))?;
+
+        let mut variables: Vec<String> = compiled.undeclared_variables(true).into_iter().collect();
+        variables.sort();
+        for variable in variables {
+            Self::check_variable(context, &variable)?;
+        }
+
+        compiled
+            .render(context)
+            .map_err(|e| format!("Template error: {}", Self::describe(&e)))
+    }
+
+    /// Rejects a template that does not parse, whatever data it is rendered with later.
+    pub fn check_syntax(template: &str) -> Result<(), String> {
+        Self::environment()
+            .template_from_str(template)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid template: {}", Self::describe(&e)))
+    }
+
+    /// Whether a template renders the same text whatever the step input and the flow state.
+    pub fn is_static(template: &str) -> bool {
+        Self::environment()
+            .template_from_str(template)
+            .is_ok_and(|compiled| compiled.undeclared_variables(true).is_empty())
+    }
+
+    fn environment() -> Environment<'static> {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.add_filter("json", |value: minijinja::Value| -> Result<String, Error> {
+            serde_json::to_string(&value)
+                .map_err(|e| Error::new(ErrorKind::InvalidOperation, e.to_string()))
+        });
+        env.add_filter(
+            "truncate",
+            |value: String, length: Option<usize>, end: Option<String>| -> String {
+                let length = length.unwrap_or(255);
+                if value.chars().count() <= length {
+                    return value;
+                }
+                let mut truncated: String = value.chars().take(length).collect();
+                truncated.push_str(end.as_deref().unwrap_or("..."));
+                truncated
+            },
+        );
+        env.add_filter(
+            "jsonpath",
+            |value: minijinja::Value, path: String| -> Result<minijinja::Value, Error> {
+                let json = serde_json::to_value(&value)
+                    .map_err(|e| Error::new(ErrorKind::InvalidOperation, e.to_string()))?;
+                let selected = json_path::select(&json, &path)
+                    .map_err(|e| Error::new(ErrorKind::InvalidOperation, e))?
+                    .ok_or_else(|| {
+                        Error::new(ErrorKind::UndefinedError, format!("nothing at {}", path))
+                    })?;
+                Ok(minijinja::Value::from_serialize(selected))
+            },
+        );
+        env
+    }
+
+    // Walks a dotted variable (e.g. `steps.summary.response`) through the context.
+    fn check_variable(context: &Value, variable: &str) -> Result<(), String> {
+        let mut current = context;
+        let mut walked = Vec::new();
+        for segment in variable.split('.') {
+            walked.push(segment);
+            match current {
+                Value::Object(obj) => match obj.get(segment) {
+                    Some(next) => current = next,
+                    None => {
+                        let available: Vec<&str> = obj.keys().map(|k| k.as_str()).collect();
+                        return Err(format!(
+                            "Unknown template variable {} (available: {})",
+                            walked.join("."),
+                            available.join(", ")
+                        ));
+                    }
+                },
+                // Attributes of lists and scalars are left to the renderer
+                _ => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    fn describe(error: &Error) -> String {
+        match error.detail() {
+            Some(detail) => format!("{} ({})", error.kind(), detail),
+            None => error.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> TemplateContext {
+        let mut context = TemplateContext::new(json!({ "topic": "rust" }));
+        context.add_step(
+            "3f2a",
+            Some("summary"),
+            &json!({ "response": "Short summary", "tags": ["a", "b"] }),
+        );
+        context
+    }
+
+    #[test]
+    fn renders_inputs_and_step_outputs() {
+        let rendered = context()
+            .render(
+                "{{ flow.input.topic | upper }}: {{ steps.summary.response }} / {{ input }}",
+                &json!({ "response": "previous text" }),
+            )
+            .unwrap();
+        assert_eq!(rendered, "RUST: Short summary / previous text");
+
+        let rendered = context()
+            .render("{{ steps['3f2a'].tags | json }}", &json!(null))
+            .unwrap();
+        assert_eq!(rendered, r#"["a","b"]"#);
+    }
+
+    #[test]
+    fn supports_jsonpath_and_truncate() {
+        let rendered = context()
+            .render(
+                "{{ previous | jsonpath('$.items[1].title') | truncate(3) }}",
+                &json!({ "items": [{ "title": "first" }, { "title": "second" }] }),
+            )
+            .unwrap();
+        assert_eq!(rendered, "sec...");
+    }
+
+    #[test]
+    fn reports_missing_variables() {
+        let error = context()
+            .render("{{ steps.translate.response }}", &json!(null))
+            .unwrap_err();
+        assert!(error.contains("steps.translate"), "{}", error);
+        assert!(error.contains("summary"), "{}", error);
+
+        let error = context().render("{{ unknown }}", &json!(null)).unwrap_err();
+        assert!(error.contains("unknown"), "{}", error);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::models::flow_step;
+use crate::models::flow_version::{
+    self, FieldChange, FlowVersionDiff, PublishFlowPayload, StepChange,
+};
+use crate::repositories::{
+    flow::Repository as FlowRepository, flow_step::Repository as FlowStepRepository,
+    flow_version::Repository as FlowVersionRepository,
+};
+use crate::services::flow_graph::FlowGraph;
+use sea_orm::{DatabaseConnection, DbErr};
+use serde_json::Value;
+
+/// Revision of a flow run by an execution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Revision {
+    /// Latest published version, the draft for flows never published (default)
+    Latest,
+    Draft,
+    Version(i32),
+}
+
+impl Revision {
+    pub fn from_request(version: Option<i32>, draft: bool) -> Result<Self, String> {
+        match (version, draft) {
+            (Some(_), true) => Err("Pass either a version or draft, not both".to_string()),
+            (Some(version), false) => Ok(Self::Version(version)),
+            (None, true) => Ok(Self::Draft),
+            (None, false) => Ok(Self::Latest),
+        }
+    }
+}
+
+// Fields of a step compared between revisions
+const COMPARED_FIELDS: [&str; 6] = [
+    "task_id",
+    "step_type",
+    "step_order",
+    "config",
+    "name",
+    "depends_on",
+];
+
+/// Differences between two revisions of a flow, steps being matched by id.
+pub fn diff_steps(
+    from_label: &str,
+    from: &[flow_step::Model],
+    to_label: &str,
+    to: &[flow_step::Model],
+) -> FlowVersionDiff {
+    let added = to
+        .iter()
+        .filter(|step| !from.iter().any(|s| s.id == step.id))
+        .cloned()
+        .collect();
+    let removed = from
+        .iter()
+        .filter(|step| !to.iter().any(|s| s.id == step.id))
+        .cloned()
+        .collect();
+
+    let mut changed = Vec::new();
+    for step in to {
+        let previous = match from.iter().find(|s| s.id == step.id) {
+            Some(previous) => previous,
+            None => continue,
+        };
+        let (old, new) = (
+            serde_json::to_value(previous).unwrap_or_default(),
+            serde_json::to_value(step).unwrap_or_default(),
+        );
+        let changes: Vec<FieldChange> = COMPARED_FIELDS
+            .iter()
+            .filter(|field| old.get(**field) != new.get(**field))
+            .map(|field| FieldChange {
+                field: field.to_string(),
+                from: old.get(*field).cloned().unwrap_or(Value::Null),
+                to: new.get(*field).cloned().unwrap_or(Value::Null),
+            })
+            .collect();
+        if !changes.is_empty() {
+            changed.push(StepChange {
+                step_id: step.id.clone(),
+                name: step.name.clone().unwrap_or_else(|| step.id.clone()),
+                changes,
+            });
+        }
+    }
+
+    FlowVersionDiff {
+        from: from_label.to_string(),
+        to: to_label.to_string(),
+        added,
+        removed,
+        changed,
+    }
+}
+
+fn is_empty(diff: &FlowVersionDiff) -> bool {
+    diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty()
+}
+
+/// Steps recorded in a version.
+pub fn snapshot_steps(version: &flow_version::Model) -> Result<Vec<flow_step::Model>, String> {
+    serde_json::from_value(version.steps.clone()).map_err(|e| {
+        format!(
+            "Invalid steps in version {} of flow {}: {}",
+            version.version, version.flow_id, e
+        )
+    })
+}
+
+pub struct Service;
+
+impl Service {
+    /// Publishes the draft steps of a flow as its next immutable version. Rejects drafts
+    /// without steps, with an invalid graph, or identical to the latest version.
+    /// Returns `None` when the flow does not exist.
+    pub async fn publish(
+        db: &DatabaseConnection,
+        flow_id: String,
+        payload: PublishFlowPayload,
+    ) -> Result<Option<flow_version::Model>, String> {
+        if FlowRepository::find_by_id(db, flow_id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let steps = FlowStepRepository::get_steps_for_flow(db, flow_id.clone())
+            .await
+            .map_err(|e| format!("Failed to fetch flow steps: {}", e))?;
+        if steps.is_empty() {
+            return Err("Flow has no steps defined".to_string());
+        }
+        FlowGraph::from_steps(&steps)?;
+
+        if let Some(latest) = FlowVersionRepository::find_latest(db, flow_id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            let published = snapshot_steps(&latest)?;
+            if is_empty(&diff_steps("", &published, "", &steps)) {
+                return Err(format!("No changes since version {}", latest.version));
+            }
+        }
+
+        let snapshot = serde_json::to_value(&steps).map_err(|e| e.to_string())?;
+        FlowVersionRepository::create_next(db, flow_id, snapshot, payload.notes)
+            .await
+            .map(Some)
+            .map_err(|e| format!("Failed to publish flow: {}", e))
+    }
+
+    pub async fn list_versions(
+        db: &DatabaseConnection,
+        flow_id: String,
+    ) -> Result<Vec<flow_version::Model>, DbErr> {
+        FlowVersionRepository::find_by_flow(db, flow_id).await
+    }
+
+    pub async fn get_version(
+        db: &DatabaseConnection,
+        flow_id: String,
+        version: i32,
+    ) -> Result<Option<flow_version::Model>, DbErr> {
+        FlowVersionRepository::find_by_number(db, flow_id, version).await
+    }
+
+    /// Version run for `revision`, `None` for the draft.
+    pub async fn resolve(
+        db: &DatabaseConnection,
+        flow_id: &str,
+        revision: Revision,
+    ) -> Result<Option<flow_version::Model>, String> {
+        match revision {
+            Revision::Draft => Ok(None),
+            Revision::Latest => FlowVersionRepository::find_latest(db, flow_id.to_string())
+                .await
+                .map_err(|e| format!("Failed to fetch flow versions: {}", e)),
+            Revision::Version(version) => {
+                FlowVersionRepository::find_by_number(db, flow_id.to_string(), version)
+                    .await
+                    .map_err(|e| format!("Failed to fetch flow versions: {}", e))?
+                    .map(Some)
+                    .ok_or_else(|| format!("Version {} of flow {} not found", version, flow_id))
+            }
+        }
+    }
+
+    /// Steps of a version of a flow, its draft steps without version.
+    pub async fn steps_of(
+        db: &DatabaseConnection,
+        flow_id: String,
+        version_id: Option<String>,
+    ) -> Result<Vec<flow_step::Model>, String> {
+        match version_id {
+            Some(id) => {
+                let version = FlowVersionRepository::find_by_id(db, id.clone())
+                    .await
+                    .map_err(|e| format!("Failed to fetch flow version: {}", e))?
+                    .ok_or_else(|| format!("Flow version {} not found", id))?;
+                snapshot_steps(&version)
+            }
+            None => FlowStepRepository::get_steps_for_flow(db, flow_id)
+                .await
+                .map_err(|e| format!("Failed to fetch flow steps: {}", e)),
+        }
+    }
+
+    /// Compares version `from` with version `to`, or with the draft without `to`.
+    /// Returns `None` when a version does not exist.
+    pub async fn diff(
+        db: &DatabaseConnection,
+        flow_id: String,
+        from: i32,
+        to: Option<i32>,
+    ) -> Result<Option<FlowVersionDiff>, String> {
+        let older = match FlowVersionRepository::find_by_number(db, flow_id.clone(), from)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            Some(version) => version,
+            None => return Ok(None),
+        };
+        let (label, steps) = match to {
+            Some(to) => {
+                match FlowVersionRepository::find_by_number(db, flow_id, to)
+                    .await
+                    .map_err(|e| e.to_string())?
+                {
+                    Some(version) => (to.to_string(), snapshot_steps(&version)?),
+                    None => return Ok(None),
+                }
+            }
+            None => (
+                "draft".to_string(),
+                FlowStepRepository::get_steps_for_flow(db, flow_id)
+                    .await
+                    .map_err(|e| format!("Failed to fetch flow steps: {}", e))?,
+            ),
+        };
+        Ok(Some(diff_steps(
+            &from.to_string(),
+            &snapshot_steps(&older)?,
+            &label,
+            &steps,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn step(id: &str, order: i32, config: Value) -> flow_step::Model {
+        flow_step::Model::fixture(id)
+            .with_order(order)
+            .with_config(config)
+    }
+
+    #[test]
+    fn diffs_steps_by_id() {
+        let from = vec![step("a", 1, json!({})), step("b", 2, json!({ "x": 1 }))];
+        let to = vec![step("b", 1, json!({ "x": 2 })), step("c", 2, json!({}))];
+
+        let diff = diff_steps("1", &from, "draft", &to);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].id, "c");
+        assert_eq!(diff.removed[0].id, "a");
+        assert_eq!(diff.changed.len(), 1);
+        let fields: Vec<&str> = diff.changed[0]
+            .changes
+            .iter()
+            .map(|c| c.field.as_str())
+            .collect();
+        assert_eq!(fields, vec!["step_order", "config"]);
+        assert_eq!(diff.changed[0].changes[1].to, json!({ "x": 2 }));
+
+        assert!(is_empty(&diff_steps("1", &from, "2", &from)));
+    }
+
+    #[test]
+    fn revision_comes_from_version_or_draft() {
+        assert_eq!(Revision::from_request(None, false), Ok(Revision::Latest));
+        assert_eq!(
+            Revision::from_request(Some(3), false),
+            Ok(Revision::Version(3))
+        );
+        assert_eq!(Revision::from_request(None, true), Ok(Revision::Draft));
+        assert!(Revision::from_request(Some(3), true).is_err());
+    }
+}
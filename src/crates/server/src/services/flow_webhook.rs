@@ -0,0 +1,338 @@
+use crate::models::flow_execution;
+use crate::models::flow_webhook::{self, ConfigureWebhookPayload};
+use crate::repositories::{
+    flow::Repository as FlowRepository, flow_execution::Repository as FlowExecutionRepository,
+    flow_webhook::Repository as FlowWebhookRepository,
+    flow_webhook_delivery::Repository as FlowWebhookDeliveryRepository,
+};
+use crate::services::flow_executor::Service as FlowExecutor;
+use crate::services::flow_template::TemplateContext;
+use crate::services::flow_version::Revision;
+use crate::state::AppState;
+use hmac::{Hmac, Mac};
+use sea_orm::{DatabaseConnection, DbErr, Set};
+use serde_json::{json, Map, Value};
+use sha2::Sha256;
+use std::collections::HashMap;
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of the raw body>`.
+pub const SIGNATURE_HEADER: &str = "x-aetherflow-signature";
+/// Header deduplicating the deliveries retried by a sender.
+pub const IDEMPOTENCY_HEADER: &str = "idempotency-key";
+
+/// Time a delivery has to record the execution it starts before a retry takes it over.
+const CLAIM_LEASE_SECS: i64 = 60;
+/// Age after which a delivery is forgotten and its idempotency key accepted again.
+const DELIVERY_RETENTION_SECS: i64 = 7 * 24 * 3600;
+
+/// Request received on a webhook URL.
+pub struct WebhookRequest {
+    pub body: Vec<u8>,
+    /* Lowercase header names */
+    pub headers: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+}
+
+pub enum Delivery {
+    Started(flow_execution::Model),
+    /* The idempotency key was already received, nothing was started */
+    Duplicate(flow_execution::Model),
+}
+
+pub enum WebhookError {
+    NotFound,
+    Unauthorized(String),
+    Invalid(String),
+    /* The first delivery of the idempotency key is still starting its execution, within
+     * its lease */
+    InProgress,
+    Internal(String),
+}
+
+fn mac(secret: &str, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac
+}
+
+/// Signature of a body as expected in the signature header, also used to sign the
+/// outbound callbacks.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        hex::encode(mac(secret, body).finalize().into_bytes())
+    )
+}
+
+/// Checks the signature header of a body in constant time.
+pub fn verify_signature(secret: &str, body: &[u8], signature: Option<&str>) -> Result<(), String> {
+    let signature = signature.ok_or_else(|| format!("Missing {} header", SIGNATURE_HEADER))?;
+    let digest = signature
+        .trim()
+        .strip_prefix("sha256=")
+        .and_then(|digest| hex::decode(digest).ok())
+        .ok_or_else(|| "Malformed signature, expected sha256=<hex digest>".to_string())?;
+    mac(secret, body)
+        .verify_slice(&digest)
+        .map_err(|_| "Invalid signature".to_string())
+}
+
+/// Flow input of a request: the rendered input template parsed as JSON (kept as text
+/// when it is not JSON), the body itself without template. Bodies that are not JSON
+/// are passed on as text.
+pub fn map_input(template: Option<&str>, request: &WebhookRequest) -> Result<Value, String> {
+    let body = if request.body.iter().all(|b| b.is_ascii_whitespace()) {
+        json!({})
+    } else {
+        serde_json::from_slice(&request.body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&request.body).into_owned()))
+    };
+    let template = match template {
+        Some(template) => template,
+        None => return Ok(body),
+    };
+
+    let to_object = |map: &HashMap<String, String>| -> Value {
+        Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+                .collect::<Map<String, Value>>(),
+        )
+    };
+    let context = json!({
+        "body": body,
+        "headers": to_object(&request.headers),
+        "query": to_object(&request.query),
+    });
+    let rendered = TemplateContext::render_data(template, &context)?;
+    Ok(serde_json::from_str(&rendered).unwrap_or(Value::String(rendered)))
+}
+
+/// Random key of 64 hex characters, for tokens and signing secrets.
+pub fn random_key() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+pub struct Service;
+
+impl Service {
+    pub async fn get_webhook(
+        db: &DatabaseConnection,
+        flow_id: String,
+    ) -> Result<Option<flow_webhook::Model>, DbErr> {
+        FlowWebhookRepository::find_by_flow(db, flow_id).await
+    }
+
+    /// Creates the webhook of a flow, with a new token and a generated secret unless one
+    /// is given, or updates its settings. The token, hence the URL, never changes: delete
+    /// the webhook to get a new one. Returns `None` when the flow does not exist.
+    pub async fn configure_webhook(
+        db: &DatabaseConnection,
+        flow_id: String,
+        payload: ConfigureWebhookPayload,
+    ) -> Result<Option<flow_webhook::Model>, String> {
+        if FlowRepository::find_by_id(db, flow_id.clone())
+            .await
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        if payload.secret.as_ref().is_some_and(|s| s.is_empty()) {
+            return Err("The webhook secret cannot be empty".to_string());
+        }
+        if let Some(template) = payload.input_template.as_deref().filter(|t| !t.is_empty()) {
+            TemplateContext::check_syntax(template)?;
+        }
+        let input_template = payload
+            .input_template
+            .map(|template| Some(template).filter(|t| !t.is_empty()));
+
+        let now = chrono::Utc::now();
+        let existing = FlowWebhookRepository::find_by_flow(db, flow_id.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        let webhook = match existing {
+            Some(webhook) => {
+                let mut active: flow_webhook::ActiveModel = webhook.into();
+                if let Some(secret) = payload.secret {
+                    active.secret = Set(secret);
+                }
+                if let Some(input_template) = input_template {
+                    active.input_template = Set(input_template);
+                }
+                if let Some(enabled) = payload.enabled {
+                    active.enabled = Set(enabled);
+                }
+                active.updated_at = Set(Some(now.into()));
+                FlowWebhookRepository::update(db, active).await
+            }
+            None => {
+                let webhook = flow_webhook::ActiveModel {
+                    id: Set(uuid::Uuid::new_v4().to_string()),
+                    flow_id: Set(flow_id),
+                    token: Set(random_key()),
+                    secret: Set(payload.secret.unwrap_or_else(random_key)),
+                    input_template: Set(input_template.flatten()),
+                    enabled: Set(payload.enabled.unwrap_or(true)),
+                    created_at: Set(Some(now.into())),
+                    updated_at: Set(Some(now.into())),
+                };
+                FlowWebhookRepository::create(db, webhook).await
+            }
+        };
+        webhook.map(Some).map_err(|e| e.to_string())
+    }
+
+    pub async fn delete_webhook(db: &DatabaseConnection, flow_id: String) -> Result<u64, DbErr> {
+        FlowWebhookRepository::delete_by_flow(db, flow_id).await
+    }
+
+    /// Starts an execution of the flow behind a webhook token with the mapped request as
+    /// input, once its signature is verified. A delivery repeating an idempotency key
+    /// received in the last 7 days starts nothing and reports the execution of the first
+    /// one.
+    pub async fn receive(
+        state: &AppState,
+        token: String,
+        request: WebhookRequest,
+    ) -> Result<Delivery, WebhookError> {
+        let db = &state.db;
+        let webhook = match FlowWebhookRepository::find_by_token(db, token).await {
+            Ok(Some(webhook)) if webhook.enabled => webhook,
+            Ok(_) => return Err(WebhookError::NotFound),
+            Err(e) => return Err(WebhookError::Internal(e.to_string())),
+        };
+
+        verify_signature(
+            &webhook.secret,
+            &request.body,
+            request.headers.get(SIGNATURE_HEADER).map(|s| s.as_str()),
+        )
+        .map_err(WebhookError::Unauthorized)?;
+        let input = map_input(webhook.input_template.as_deref(), &request)
+            .map_err(|e| WebhookError::Invalid(format!("Input mapping failed: {}", e)))?;
+
+        let idempotency_key = request
+            .headers
+            .get(IDEMPOTENCY_HEADER)
+            .filter(|key| !key.is_empty())
+            .cloned();
+        let delivery = match idempotency_key {
+            Some(key) => {
+                match FlowWebhookDeliveryRepository::claim(
+                    db,
+                    webhook.id.clone(),
+                    key.clone(),
+                    CLAIM_LEASE_SECS,
+                )
+                .await
+                .map_err(|e| WebhookError::Internal(e.to_string()))?
+                {
+                    Some(delivery) => Some(delivery),
+                    None => return Self::duplicate(db, webhook.id, key).await,
+                }
+            }
+            None => None,
+        };
+
+        match FlowExecutor::start_flow(state, webhook.flow_id, input, None, None, Revision::Latest)
+            .await
+        {
+            Ok(execution) => {
+                if let Some(delivery) = delivery {
+                    FlowWebhookDeliveryRepository::set_execution(
+                        db,
+                        delivery.id,
+                        execution.id.clone(),
+                    )
+                    .await
+                    .map_err(|e| WebhookError::Internal(e.to_string()))?;
+                }
+                Ok(Delivery::Started(execution))
+            }
+            Err(e) => {
+                // Lets the sender retry the delivery
+                if let Some(delivery) = delivery {
+                    let _ = FlowWebhookDeliveryRepository::delete(db, delivery.id).await;
+                }
+                Err(WebhookError::Internal(e.message))
+            }
+        }
+    }
+
+    /// Deletes the deliveries past their retention, returning how many were removed.
+    pub async fn purge_deliveries(db: &DatabaseConnection) -> Result<u64, DbErr> {
+        FlowWebhookDeliveryRepository::delete_older_than(db, DELIVERY_RETENTION_SECS).await
+    }
+
+    async fn duplicate(
+        db: &DatabaseConnection,
+        webhook_id: String,
+        key: String,
+    ) -> Result<Delivery, WebhookError> {
+        let execution_id = FlowWebhookDeliveryRepository::find_by_key(db, webhook_id, key)
+            .await
+            .map_err(|e| WebhookError::Internal(e.to_string()))?
+            .and_then(|delivery| delivery.execution_id)
+            .ok_or(WebhookError::InProgress)?;
+        match FlowExecutionRepository::find_by_id(db, execution_id.clone()).await {
+            Ok(Some(execution)) => Ok(Delivery::Duplicate(execution)),
+            Ok(None) => Err(WebhookError::Internal(format!(
+                "Execution {} of the delivery no longer exists",
+                execution_id
+            ))),
+            Err(e) => Err(WebhookError::Internal(e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(body: &str) -> WebhookRequest {
+        WebhookRequest {
+            body: body.as_bytes().to_vec(),
+            headers: HashMap::from([("x-event".to_string(), "order.created".to_string())]),
+            query: HashMap::from([("source".to_string(), "shop".to_string())]),
+        }
+    }
+
+    #[test]
+    fn verifies_body_signatures() {
+        let body = br#"{"order": 42}"#;
+        let signature = sign("s3cret", body);
+        assert!(signature.starts_with("sha256="));
+        assert!(verify_signature("s3cret", body, Some(&signature)).is_ok());
+
+        assert!(verify_signature("other", body, Some(&signature)).is_err());
+        assert!(verify_signature("s3cret", b"{}", Some(&signature)).is_err());
+        assert!(verify_signature("s3cret", body, Some("sha256=zz")).is_err());
+        assert!(verify_signature("s3cret", body, None).is_err());
+    }
+
+    #[test]
+    fn maps_requests_to_flow_input() {
+        let req = request(r#"{"data": {"email": "ada@example.com"}}"#);
+        assert_eq!(
+            map_input(None, &req).unwrap(),
+            json!({ "data": { "email": "ada@example.com" } })
+        );
+
+        let template = r#"{"email": {{ body.data.email | json }}, "event": "{{ headers["x-event"] }}", "source": "{{ query.source }}"}"#;
+        assert_eq!(
+            map_input(Some(template), &req).unwrap(),
+            json!({ "email": "ada@example.com", "event": "order.created", "source": "shop" })
+        );
+
+        assert_eq!(map_input(None, &request("")).unwrap(), json!({}));
+        assert_eq!(map_input(None, &request("a=1")).unwrap(), json!("a=1"));
+        assert!(map_input(Some("{{ body.missing }}"), &req).is_err());
+    }
+}
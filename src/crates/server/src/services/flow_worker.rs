@@ -0,0 +1,182 @@
+use crate::repositories::flow_execution::Repository as FlowExecutionRepository;
+use crate::services::flow_approval::Service as FlowApprovalService;
+use crate::services::flow_executor::Service as FlowExecutor;
+use crate::services::flow_webhook::Service as FlowWebhookService;
+use crate::state::AppState;
+use sea_orm::DatabaseConnection;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+use uuid::Uuid;
+
+/// Interval at which a running execution refreshes its heartbeat.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// Running executions without heartbeat for this long are considered orphaned.
+const STALE_AFTER_SECS: i64 = 60;
+/// Claims after which an orphaned execution is failed instead of requeued.
+const MAX_ATTEMPTS: i32 = 3;
+/// Pause of an idle worker before polling the queue again.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Cancellation tokens of the executions running on this instance.
+#[derive(Clone, Default)]
+pub struct RunningExecutions {
+    tokens: Arc<Mutex<HashMap<String, CancellationToken>>>,
+}
+
+impl RunningExecutions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a running execution until the returned guard is dropped.
+    pub fn register(&self, execution_id: &str) -> Registration {
+        let token = CancellationToken::new();
+        if let Ok(mut tokens) = self.tokens.lock() {
+            tokens.insert(execution_id.to_string(), token.clone());
+        }
+        Registration {
+            executions: self.clone(),
+            execution_id: execution_id.to_string(),
+            token,
+        }
+    }
+
+    /// Cancels an execution running on this instance, false when it runs elsewhere.
+    pub fn cancel(&self, execution_id: &str) -> bool {
+        match self
+            .tokens
+            .lock()
+            .ok()
+            .and_then(|t| t.get(execution_id).cloned())
+        {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Running execution registered in `RunningExecutions`.
+pub struct Registration {
+    executions: RunningExecutions,
+    execution_id: String,
+    pub token: CancellationToken,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Ok(mut tokens) = self.executions.tokens.lock() {
+            tokens.remove(&self.execution_id);
+        }
+    }
+}
+
+/// Keeps the heartbeat of an execution fresh while it runs, stopped when dropped.
+/// Cancellations requested on another instance are noticed here.
+pub struct Heartbeat(JoinHandle<()>);
+
+impl Heartbeat {
+    pub fn start(db: DatabaseConnection, execution_id: String, token: CancellationToken) -> Self {
+        Self(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Ok(true) =
+                    FlowExecutionRepository::heartbeat(&db, execution_id.clone()).await
+                {
+                    token.cancel();
+                }
+            }
+        }))
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+pub struct Service;
+
+impl Service {
+    /// Spawns `workers` executor workers claiming Pending flow executions from the
+    /// Postgres queue, plus a recovery task requeueing executions whose worker died
+    /// (e.g. a restart of the instance), applying the timeout of expired approvals and
+    /// purging the webhook deliveries past their retention. Every gateway instance can run its own workers.
+    pub fn start(state: AppState, workers: usize) {
+        let instance = Uuid::new_v4().to_string();
+        info!(
+            "Flow workers: starting {} workers on instance {}",
+            workers, instance
+        );
+
+        for n in 0..workers {
+            let state = state.clone();
+            let worker_id = format!("{}/{}", instance, n);
+            tokio::spawn(async move { Self::work(state, worker_id).await });
+        }
+
+        let db = state.db.clone();
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(STALE_AFTER_SECS as u64 / 2));
+            loop {
+                interval.tick().await;
+                match FlowExecutionRepository::recover_stale(&db, STALE_AFTER_SECS, MAX_ATTEMPTS)
+                    .await
+                {
+                    Ok((0, 0)) => {}
+                    Ok((requeued, settled)) => info!(
+                        "Flow workers: requeued {} orphaned executions, settled {}",
+                        requeued, settled
+                    ),
+                    Err(e) => tracing::warn!("Failed to recover flow executions: {}", e),
+                }
+                match FlowApprovalService::expire(&db).await {
+                    Ok(0) => {}
+                    Ok(expired) => info!("Flow workers: settled {} expired approvals", expired),
+                    Err(e) => tracing::warn!("Failed to expire approvals: {}", e),
+                }
+                match FlowWebhookService::purge_deliveries(&db).await {
+                    Ok(0) => {}
+                    Ok(purged) => info!("Flow workers: purged {} webhook deliveries", purged),
+                    Err(e) => tracing::warn!("Failed to purge webhook deliveries: {}", e),
+                }
+            }
+        });
+    }
+
+    // Claims and runs executions one at a time, sleeping while the queue is empty.
+    async fn work(state: AppState, worker_id: String) {
+        loop {
+            match FlowExecutionRepository::claim_next(&state.db, &worker_id).await {
+                Ok(Some(execution)) => {
+                    let execution_id = execution.id.clone();
+                    info!(
+                        "Worker {} claimed execution {} (attempt {})",
+                        worker_id, execution_id, execution.attempts
+                    );
+                    match FlowExecutor::run_claimed(&state, execution).await {
+                        Err(e) if e.paused.is_some() => info!("{}", e.message),
+                        Err(e) => {
+                            tracing::warn!("Execution {} failed: {}", execution_id, e.message)
+                        }
+                        Ok(_) => {}
+                    }
+                }
+                Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+                Err(e) => {
+                    tracing::warn!("Worker {} failed to poll the queue: {}", worker_id, e);
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use serde_json::Value;
+
+/*
+ * Small JSONPath subset used by flow expressions: `$`, `.key`, `['key']` and `[index]`
+ * (negative indexes count from the end), e.g. `$.choices[0].message.content`.
+ * The leading `$` is optional.
+ */
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(i64),
+}
+
+/// Returns the value at `path`, `None` when a segment does not exist.
+pub fn select<'a>(value: &'a Value, path: &str) -> Result<Option<&'a Value>, String> {
+    let mut current = value;
+    for segment in parse(path)? {
+        let next = match (&segment, current) {
+            (Segment::Key(key), Value::Object(obj)) => obj.get(key),
+            (Segment::Index(index), Value::Array(items)) => {
+                let position = if *index < 0 {
+                    items.len() as i64 + index
+                } else {
+                    *index
+                };
+                usize::try_from(position).ok().and_then(|i| items.get(i))
+            }
+            _ => None,
+        };
+        match next {
+            Some(next) => current = next,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(current))
+}
+
+fn parse(path: &str) -> Result<Vec<Segment>, String> {
+    let invalid = || format!("Invalid JSONPath {}", path);
+    let path = path.trim();
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    let mut segments = Vec::new();
+
+    // Paths without `$` may start directly with a key
+    if !rest.is_empty() && !rest.starts_with('.') && !rest.starts_with('[') {
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        segments.push(Segment::Key(rest[..end].to_string()));
+        rest = &rest[end..];
+    }
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            segments.push(Segment::Key(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let inner = after[..end].trim();
+            let quoted = inner
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+            let segment = match quoted {
+                Some(key) => Segment::Key(key.to_string()),
+                None => Segment::Index(inner.parse().map_err(|_| invalid())?),
+            };
+            segments.push(segment);
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn selects_nested_keys_and_indexes() {
+        let value = json!({ "choices": [{ "message": { "content": "hi" } }], "a b": 1 });
+        assert_eq!(
+            select(&value, "$.choices[0].message.content").unwrap(),
+            Some(&json!("hi"))
+        );
+        assert_eq!(
+            select(&value, "choices[-1].message.content").unwrap(),
+            Some(&json!("hi"))
+        );
+        assert_eq!(select(&value, "$['a b']").unwrap(), Some(&json!(1)));
+        assert_eq!(select(&value, "$").unwrap(), Some(&value));
+        assert_eq!(select(&value, "$.missing.key").unwrap(), None);
+        assert_eq!(select(&value, "$.choices[3]").unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        assert!(select(&json!({}), "$..a").is_err());
+        assert!(select(&json!({}), "$.a[x]").is_err());
+        assert!(select(&json!({}), "$.a[0").is_err());
+    }
+}
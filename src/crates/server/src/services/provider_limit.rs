@@ -0,0 +1,48 @@
+use crate::models::provider_limit;
+use crate::repositories::provider_limit::Repository as ProviderLimitRepository;
+use crate::services::rate_limiter;
+use sea_orm::*;
+
+pub struct Service;
+
+impl Service {
+    pub async fn get_all_limits(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<provider_limit::Model>, DbErr> {
+        ProviderLimitRepository::find_all(db).await
+    }
+
+    /// Creates or replaces the limits of a provider credential.
+    pub async fn upsert_limit(
+        db: &DatabaseConnection,
+        credential: String,
+        requests_per_minute: Option<i32>,
+        tokens_per_minute: Option<i32>,
+        mode: Option<String>,
+    ) -> Result<provider_limit::Model, String> {
+        let mode = mode.unwrap_or_else(|| rate_limiter::MODE_QUEUE.to_string());
+        rate_limiter::validate_mode(&mode)?;
+        if requests_per_minute.is_some_and(|rpm| rpm <= 0)
+            || tokens_per_minute.is_some_and(|tpm| tpm <= 0)
+        {
+            return Err("Limits must be greater than zero".to_string());
+        }
+
+        let limit = provider_limit::ActiveModel {
+            credential: Set(credential),
+            requests_per_minute: Set(requests_per_minute),
+            tokens_per_minute: Set(tokens_per_minute),
+            mode: Set(mode),
+            created_at: NotSet,
+            updated_at: Set(Some(chrono::Utc::now().into())),
+        };
+
+        ProviderLimitRepository::upsert(db, limit)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn delete_limit(db: &DatabaseConnection, credential: String) -> Result<u64, DbErr> {
+        ProviderLimitRepository::delete(db, credential).await
+    }
+}
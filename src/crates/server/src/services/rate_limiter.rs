@@ -0,0 +1,385 @@
+use crate::models::{agent, provider_limit};
+use crate::services::agent_client::{AgentCallError, ErrorClass};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use utoipa::ToSchema;
+
+/// Waits until capacity is available again.
+pub const MODE_QUEUE: &str = "queue";
+/// Fails immediately with a rate limited error (HTTP 429 at the gateway).
+pub const MODE_REJECT: &str = "reject";
+
+/*
+ * Limits stored in `agents.rate_limit`, e.g.
+ * { "requests_per_second": 2, "burst": 5, "max_concurrent": 4, "mode": "queue" }
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AgentRateLimit {
+    /// Sustained request rate allowed to the agent
+    pub requests_per_second: Option<f64>,
+    /// Requests allowed in a burst, defaults to one second worth of requests
+    pub burst: Option<u32>,
+    /// Calls allowed in flight at the same time
+    pub max_concurrent: Option<u32>,
+    /// "queue" (default) or "reject"
+    #[serde(default = "default_mode")]
+    pub mode: String,
+}
+
+fn default_mode() -> String {
+    MODE_QUEUE.to_string()
+}
+
+/// Checks the queueing mode of a limit.
+pub fn validate_mode(mode: &str) -> Result<(), String> {
+    if mode != MODE_QUEUE && mode != MODE_REJECT {
+        return Err(format!("Unknown rate limit mode {}", mode));
+    }
+    Ok(())
+}
+
+impl AgentRateLimit {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_mode(&self.mode)?;
+        if self.requests_per_second.is_some_and(|rps| rps <= 0.0) {
+            return Err("requests_per_second must be greater than zero".to_string());
+        }
+        if self.burst == Some(0) || self.max_concurrent == Some(0) {
+            return Err("burst and max_concurrent must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+
+    /// Reads the limits of an agent, ignoring malformed values.
+    pub fn from_agent(agent: &agent::Model) -> Option<Self> {
+        let raw = agent.rate_limit.as_ref()?;
+        serde_json::from_value(raw.clone()).ok()
+    }
+}
+
+struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, refill_per_sec: f64, now: Instant) -> Self {
+        Self {
+            capacity,
+            refill_per_sec,
+            tokens: capacity,
+            updated_at: now,
+        }
+    }
+
+    /// Takes `amount` tokens, or returns how long to wait before they are available.
+    /// Amounts above the capacity only require a full bucket, otherwise they would never pass.
+    fn take(&mut self, amount: f64, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated_at = now;
+
+        let amount = amount.min(self.capacity);
+        if self.tokens >= amount {
+            self.tokens -= amount;
+            return None;
+        }
+        Some(Duration::from_secs_f64(
+            (amount - self.tokens) / self.refill_per_sec,
+        ))
+    }
+}
+
+// Concurrency slots of an agent. Permits are forgotten when taken and given back when
+// the call ends, so that a changed limit also counts the calls already in flight.
+struct Slots {
+    semaphore: Arc<Semaphore>,
+    max: u32,
+    /* Permits to drop as in-flight calls end, after the limit was lowered below them */
+    debt: u32,
+}
+
+impl Slots {
+    fn new(max: u32) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max as usize)),
+            max,
+            debt: 0,
+        }
+    }
+
+    fn resize(&mut self, max: u32) {
+        if max > self.max {
+            let extra = max - self.max;
+            let repaid = extra.min(self.debt);
+            self.debt -= repaid;
+            self.semaphore.add_permits((extra - repaid) as usize);
+        } else if max < self.max {
+            let cut = self.max - max;
+            let forgotten = self.semaphore.forget_permits(cut as usize) as u32;
+            self.debt += cut - forgotten;
+        }
+        self.max = max;
+    }
+
+    fn release(&mut self) {
+        if self.debt > 0 {
+            self.debt -= 1;
+        } else {
+            self.semaphore.add_permits(1);
+        }
+    }
+}
+
+type SlotMap = Arc<Mutex<HashMap<String, Slots>>>;
+
+/// Concurrency slot held for the duration of an agent call.
+pub struct Permit {
+    slot: Option<(SlotMap, String)>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some((slots, agent_id)) = self.slot.take() {
+            if let Some(agent_slots) = slots.lock().unwrap().get_mut(&agent_id) {
+                agent_slots.release();
+            }
+        }
+    }
+}
+
+/// Token bucket and concurrency limiter shared by the gateway and the flow executor.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<HashMap<String, TokenBucket>>>,
+    slots: SlotMap,
+    provider_limits: Arc<RwLock<HashMap<String, provider_limit::Model>>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the known provider credential limits (loaded at startup).
+    pub fn set_provider_limits(&self, limits: Vec<provider_limit::Model>) {
+        let mut known = self.provider_limits.write().unwrap();
+        *known = limits
+            .into_iter()
+            .map(|limit| (limit.credential.clone(), limit))
+            .collect();
+    }
+
+    pub fn set_provider_limit(&self, limit: provider_limit::Model) {
+        let mut known = self.provider_limits.write().unwrap();
+        known.insert(limit.credential.clone(), limit);
+    }
+
+    pub fn remove_provider_limit(&self, credential: &str) {
+        let mut known = self.provider_limits.write().unwrap();
+        known.remove(credential);
+    }
+
+    /// Waits for (or is refused) a request slot under the limits of the agent.
+    pub async fn acquire_agent(&self, agent: &agent::Model) -> Result<Permit, AgentCallError> {
+        let limit = match AgentRateLimit::from_agent(agent) {
+            Some(limit) => limit,
+            None => return Ok(Permit { slot: None }),
+        };
+        let reject = limit.mode == MODE_REJECT;
+
+        let slot = match limit.max_concurrent {
+            Some(max) => {
+                // A changed limit resizes the slots of the agent
+                let semaphore = {
+                    let mut slots = self.slots.lock().unwrap();
+                    let agent_slots = slots
+                        .entry(agent.id.clone())
+                        .or_insert_with(|| Slots::new(max));
+                    agent_slots.resize(max);
+                    agent_slots.semaphore.clone()
+                };
+                let permit = if reject {
+                    semaphore.try_acquire().map_err(|_| {
+                        Self::exhausted(format!(
+                            "Agent {} already has {} calls in flight",
+                            agent.slug, max
+                        ))
+                    })?
+                } else {
+                    semaphore
+                        .acquire()
+                        .await
+                        .map_err(|e| AgentCallError::internal(e.to_string()))?
+                };
+                permit.forget();
+                Some((self.slots.clone(), agent.id.clone()))
+            }
+            None => None,
+        };
+
+        if let Some(rps) = limit.requests_per_second {
+            let capacity = limit.burst.map(f64::from).unwrap_or(rps.ceil().max(1.0));
+            self.take(
+                format!("agent:{}", agent.id),
+                capacity,
+                rps,
+                1.0,
+                reject,
+                &format!("Rate limit of agent {} exhausted", agent.slug),
+            )
+            .await?;
+        }
+
+        Ok(Permit { slot })
+    }
+
+    /// Waits for (or is refused) request and token capacity of a provider credential.
+    pub async fn acquire_credential(
+        &self,
+        credential: &str,
+        tokens: i32,
+    ) -> Result<(), AgentCallError> {
+        let limit = match self.provider_limits.read().unwrap().get(credential) {
+            Some(limit) => limit.clone(),
+            None => return Ok(()),
+        };
+        let reject = limit.mode == MODE_REJECT;
+
+        if let Some(rpm) = limit.requests_per_minute.filter(|rpm| *rpm > 0) {
+            self.take(
+                format!("credential:{}:requests", credential),
+                rpm as f64,
+                rpm as f64 / 60.0,
+                1.0,
+                reject,
+                &format!("Requests per minute of credential {} exhausted", credential),
+            )
+            .await?;
+        }
+
+        if let Some(tpm) = limit.tokens_per_minute.filter(|tpm| *tpm > 0) {
+            self.take(
+                format!("credential:{}:tokens", credential),
+                tpm as f64,
+                tpm as f64 / 60.0,
+                tokens as f64,
+                reject,
+                &format!("Tokens per minute of credential {} exhausted", credential),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn take(
+        &self,
+        key: String,
+        capacity: f64,
+        refill_per_sec: f64,
+        amount: f64,
+        reject: bool,
+        message: &str,
+    ) -> Result<(), AgentCallError> {
+        loop {
+            let wait = {
+                let now = Instant::now();
+                let mut buckets = self.buckets.lock().unwrap();
+                let bucket = buckets
+                    .entry(key.clone())
+                    .or_insert_with(|| TokenBucket::new(capacity, refill_per_sec, now));
+                if bucket.capacity != capacity || bucket.refill_per_sec != refill_per_sec {
+                    *bucket = TokenBucket::new(capacity, refill_per_sec, now);
+                }
+                bucket.take(amount, now)
+            };
+
+            match wait {
+                None => return Ok(()),
+                Some(_) if reject => return Err(Self::exhausted(message.to_string())),
+                Some(wait) => {
+                    tracing::debug!("{}, waiting {:?}", message, wait);
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+
+    fn exhausted(message: String) -> AgentCallError {
+        AgentCallError::new(ErrorClass::RateLimited, message, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, 1.0, start);
+        assert!(bucket.take(1.0, start).is_none());
+        assert!(bucket.take(1.0, start).is_none());
+
+        let wait = bucket.take(1.0, start).expect("bucket should be empty");
+        assert!((wait.as_secs_f64() - 1.0).abs() < 1e-6);
+
+        assert!(bucket.take(1.0, start + Duration::from_secs(1)).is_none());
+    }
+
+    #[test]
+    fn oversized_amounts_need_a_full_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(100.0, 10.0, start);
+        assert!(bucket.take(500.0, start).is_none());
+        assert!(bucket.take(500.0, start + Duration::from_secs(5)).is_some());
+        assert!(bucket
+            .take(500.0, start + Duration::from_secs(10))
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_limits_are_rejected() {
+        let limit = AgentRateLimit {
+            requests_per_second: Some(0.0),
+            burst: None,
+            max_concurrent: None,
+            mode: MODE_QUEUE.to_string(),
+        };
+        assert!(limit.validate().is_err());
+
+        let limit = AgentRateLimit {
+            requests_per_second: Some(2.0),
+            burst: None,
+            max_concurrent: Some(1),
+            mode: "drop".to_string(),
+        };
+        assert!(limit.validate().is_err());
+    }
+
+    #[test]
+    fn resized_slots_count_calls_in_flight() {
+        let mut slots = Slots::new(2);
+        slots.semaphore.try_acquire_many(2).unwrap().forget();
+
+        // Both calls are still running under the lowered limit
+        slots.resize(1);
+        assert_eq!(slots.semaphore.available_permits(), 0);
+        slots.release();
+        assert_eq!(slots.semaphore.available_permits(), 0);
+        slots.release();
+        assert_eq!(slots.semaphore.available_permits(), 1);
+
+        slots.semaphore.try_acquire().unwrap().forget();
+        slots.resize(3);
+        assert_eq!(slots.semaphore.available_permits(), 2);
+        slots.release();
+        assert_eq!(slots.semaphore.available_permits(), 3);
+    }
+}
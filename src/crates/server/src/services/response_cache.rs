@@ -0,0 +1,289 @@
+use crate::models::{agent, agent_task, response_cache};
+use crate::repositories::response_cache::Repository as ResponseCacheRepository;
+use sea_orm::{DatabaseConnection, Set};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/*
+ * Opt-in cache policy read from `agent_tasks.settings.cache`, e.g.
+ * { "cache": { "enabled": true, "ttl_secs": 600, "persist": true } }
+ */
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CachePolicy {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /* Lifetime of a cached response */
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64,
+
+    /* Also store entries in Postgres so they survive restarts and are shared between instances */
+    #[serde(default)]
+    pub persist: bool,
+}
+
+fn default_ttl_secs() -> u64 {
+    300
+}
+
+impl CachePolicy {
+    /// Parses the cache section of task settings, `None` when caching is not enabled.
+    pub fn from_settings(settings: Option<&Value>) -> Result<Option<Self>, String> {
+        let raw = match settings.and_then(|s| s.get("cache")) {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        let policy: Self = serde_json::from_value(raw.clone())
+            .map_err(|e| format!("Invalid cache settings: {}", e))?;
+        if policy.enabled && policy.ttl_secs == 0 {
+            return Err("Cache ttl_secs must be greater than zero".to_string());
+        }
+        Ok(policy.enabled.then_some(policy))
+    }
+
+    /// Returns the policy of a task when caching is enabled for it.
+    pub fn from_task(task: &agent_task::Model) -> Option<Self> {
+        Self::from_settings(task.settings.as_ref()).ok().flatten()
+    }
+
+    /// Only deterministic calls are cached: the payload must ask for a temperature of 0,
+    /// providers default to a higher one.
+    pub fn applies_to(&self, payload: &Value) -> bool {
+        let temperature = payload
+            .get("temperature")
+            .or_else(|| payload.get("options").and_then(|o| o.get("temperature")))
+            .and_then(|t| t.as_f64());
+        temperature == Some(0.0)
+    }
+}
+
+/// Owner of the cache entries of a call: a task, or an agent called directly.
+pub enum CacheScope<'a> {
+    Task(&'a agent_task::Model),
+    Agent(&'a agent::Model),
+}
+
+struct CacheEntry {
+    value: Value,
+    expires_at: Instant,
+    inserted_at: Instant,
+}
+
+/// In-memory response cache shared by the gateway and the flow executor,
+/// optionally backed by the `response_cache` table.
+#[derive(Clone)]
+pub struct ResponseCache {
+    entries: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    max_entries: usize,
+}
+
+impl ResponseCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            max_entries,
+        }
+    }
+
+    /// Builds the cache key from the task id and the normalized payload.
+    pub fn key(task_id: &str, payload: &Value) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(task_id.as_bytes());
+        hasher.update(b":");
+        hasher.update(Self::normalize(payload).as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    /// Looks the key up in memory first, then in Postgres when the policy persists entries.
+    pub async fn get(
+        &self,
+        db: &DatabaseConnection,
+        key: &str,
+        policy: &CachePolicy,
+    ) -> Option<Value> {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get(key) {
+                Some(entry) if entry.expires_at > Instant::now() => {
+                    return Some(entry.value.clone())
+                }
+                Some(_) => {
+                    entries.remove(key);
+                }
+                None => {}
+            }
+        }
+
+        if !policy.persist {
+            return None;
+        }
+
+        let stored = ResponseCacheRepository::find_valid(db, key.to_string())
+            .await
+            .ok()
+            .flatten()?;
+
+        // Warm the memory tier with the remaining lifetime of the stored entry
+        let remaining = (stored.expires_at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default();
+        self.insert(key.to_string(), stored.response.clone(), remaining);
+        Some(stored.response)
+    }
+
+    /// Stores a response in memory and, if requested, in Postgres.
+    pub async fn put(
+        &self,
+        db: &DatabaseConnection,
+        key: String,
+        task_id: &str,
+        value: &Value,
+        policy: &CachePolicy,
+    ) {
+        let ttl = Duration::from_secs(policy.ttl_secs);
+        self.insert(key.clone(), value.clone(), ttl);
+
+        if policy.persist {
+            let expires_at = chrono::Utc::now()
+                + chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::zero());
+            let entry = response_cache::ActiveModel {
+                key: Set(key),
+                task_id: Set(task_id.to_string()),
+                response: Set(value.clone()),
+                expires_at: Set(expires_at.into()),
+                created_at: Set(None),
+            };
+            if let Err(e) = ResponseCacheRepository::upsert(db, entry).await {
+                tracing::warn!("Failed to persist cached response: {}", e);
+            }
+            let _ = ResponseCacheRepository::delete_expired(db).await;
+        }
+    }
+
+    fn insert(&self, key: String, value: Value, ttl: Duration) {
+        if self.max_entries == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            // Make room by dropping expired entries, then the oldest one
+            entries.retain(|_, entry| entry.expires_at > now);
+            if entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.inserted_at)
+                    .map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+
+        entries.insert(
+            key,
+            CacheEntry {
+                value,
+                expires_at: now + ttl,
+                inserted_at: now,
+            },
+        );
+    }
+
+    /// Serializes a JSON value with object keys sorted, so that semantically
+    /// identical payloads produce the same key.
+    fn normalize(value: &Value) -> String {
+        match value {
+            Value::Object(obj) => {
+                let mut keys: Vec<&String> = obj.keys().collect();
+                keys.sort();
+                let fields: Vec<String> = keys
+                    .into_iter()
+                    .map(|k| format!("{}:{}", Value::String(k.clone()), Self::normalize(&obj[k])))
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            }
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(Self::normalize).collect();
+                format!("[{}]", items.join(","))
+            }
+            other => other.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn key_ignores_object_key_order() {
+        let a = json!({ "model": "llama3", "prompt": "hi", "options": { "a": 1, "b": 2 } });
+        let b = json!({ "options": { "b": 2, "a": 1 }, "prompt": "hi", "model": "llama3" });
+        assert_eq!(
+            ResponseCache::key("task", &a),
+            ResponseCache::key("task", &b)
+        );
+        assert_ne!(
+            ResponseCache::key("task", &a),
+            ResponseCache::key("other", &a)
+        );
+    }
+
+    #[test]
+    fn only_deterministic_payloads_are_cached() {
+        let policy = CachePolicy {
+            enabled: true,
+            ttl_secs: 60,
+            persist: false,
+        };
+        assert!(!policy.applies_to(&json!({ "prompt": "hi" })));
+        assert!(policy.applies_to(&json!({ "prompt": "hi", "temperature": 0 })));
+        assert!(policy.applies_to(&json!({ "options": { "temperature": 0.0 } })));
+        assert!(!policy.applies_to(&json!({ "prompt": "hi", "temperature": 0.7 })));
+        assert!(!policy.applies_to(&json!({ "options": { "temperature": 1 } })));
+    }
+
+    #[test]
+    fn rejects_malformed_settings() {
+        let settings = json!({ "cache": { "enabled": true, "ttl_secs": 60 } });
+        assert_eq!(
+            CachePolicy::from_settings(Some(&settings))
+                .unwrap()
+                .map(|p| p.ttl_secs),
+            Some(60)
+        );
+        let disabled = json!({ "cache": { "enabled": false } });
+        assert!(CachePolicy::from_settings(Some(&disabled))
+            .unwrap()
+            .is_none());
+        assert!(
+            CachePolicy::from_settings(Some(&json!({ "cache": { "ttl_secs": "1h" } }))).is_err()
+        );
+        assert!(CachePolicy::from_settings(Some(&json!({ "cache": true }))).is_err());
+        assert!(CachePolicy::from_settings(Some(
+            &json!({ "cache": { "enabled": true, "ttl_secs": 0 } })
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn oldest_entry_is_evicted_when_full() {
+        let cache = ResponseCache::new(2);
+        let ttl = Duration::from_secs(60);
+        cache.insert("a".to_string(), json!(1), ttl);
+        cache.insert("b".to_string(), json!(2), ttl);
+        cache.insert("c".to_string(), json!(3), ttl);
+
+        let entries = cache.entries.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(!entries.contains_key("a"));
+    }
+}
@@ -0,0 +1,399 @@
+use crate::models::session::SessionWithMessages;
+use crate::models::{session, session_message};
+use crate::repositories::{
+    session::Repository as SessionRepository,
+    session_message::Repository as SessionMessageRepository,
+};
+use crate::services::{
+    agent_task::Service as AgentTaskService, task_runner::Service as TaskRunner,
+};
+use crate::state::AppState;
+use sea_orm::*;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Drops the oldest messages once the token budget is exceeded.
+pub const STRATEGY_TRUNCATE: &str = "truncate";
+/// Condenses the oldest messages into a single summary message through a task.
+pub const STRATEGY_SUMMARIZE: &str = "summarize";
+
+pub struct Service;
+
+impl Service {
+    pub async fn create_session(
+        db: &DatabaseConnection,
+        name: Option<String>,
+        max_tokens: Option<i32>,
+        strategy: Option<String>,
+        summarizer_task_id: Option<String>,
+    ) -> Result<session::Model, String> {
+        let strategy = strategy.unwrap_or_else(|| STRATEGY_TRUNCATE.to_string());
+        if strategy != STRATEGY_TRUNCATE && strategy != STRATEGY_SUMMARIZE {
+            return Err(format!("Unknown session strategy {}", strategy));
+        }
+        if strategy == STRATEGY_SUMMARIZE && summarizer_task_id.is_none() {
+            return Err("The summarize strategy requires a summarizer_task_id".to_string());
+        }
+        if max_tokens.is_some_and(|max| max <= 0) {
+            return Err("max_tokens must be greater than zero".to_string());
+        }
+
+        let new_session = session::ActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            name: Set(name),
+            max_tokens: Set(max_tokens),
+            strategy: Set(strategy),
+            summarizer_task_id: Set(summarizer_task_id),
+            created_at: Set(None),
+            updated_at: Set(None),
+        };
+
+        SessionRepository::create(db, new_session)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn get_all_sessions(db: &DatabaseConnection) -> Result<Vec<session::Model>, DbErr> {
+        SessionRepository::find_all(db).await
+    }
+
+    pub async fn get_session_with_messages(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<Option<SessionWithMessages>, DbErr> {
+        let session = match SessionRepository::find_by_id(db, id.clone()).await? {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+        let messages = SessionMessageRepository::find_by_session(db, id).await?;
+        let total_tokens = messages.iter().map(|m| m.token_count).sum();
+
+        Ok(Some(SessionWithMessages {
+            session,
+            messages,
+            total_tokens,
+        }))
+    }
+
+    pub async fn delete_session(db: &DatabaseConnection, id: String) -> Result<u64, DbErr> {
+        SessionRepository::delete(db, id).await
+    }
+
+    /// Loads the stored history of a session, failing if the session does not exist.
+    pub async fn get_history(
+        db: &DatabaseConnection,
+        session_id: String,
+    ) -> Result<Vec<session_message::Model>, String> {
+        Self::find_history(db, session_id.clone())
+            .await?
+            .ok_or_else(|| format!("Session {} not found", session_id))
+    }
+
+    /// Loads the stored history of a session, `None` when the session does not exist.
+    pub async fn find_history(
+        db: &DatabaseConnection,
+        session_id: String,
+    ) -> Result<Option<Vec<session_message::Model>>, String> {
+        if SessionRepository::find_by_id(db, session_id.clone())
+            .await
+            .map_err(|e| format!("Database error fetching session: {}", e))?
+            .is_none()
+        {
+            return Ok(None);
+        }
+
+        SessionMessageRepository::find_by_session(db, session_id)
+            .await
+            .map(Some)
+            .map_err(|e| format!("Database error fetching session messages: {}", e))
+    }
+
+    /// Prepends the history to a payload. Chat payloads get the messages in front of
+    /// their `messages` array, prompt payloads get a transcript before the prompt.
+    pub fn apply_history(history: &[session_message::Model], payload: &Value) -> Value {
+        if history.is_empty() {
+            return payload.clone();
+        }
+
+        let mut payload = payload.clone();
+        match &mut payload {
+            Value::Object(obj) => {
+                if let Some(Value::Array(messages)) = obj.get_mut("messages") {
+                    let mut merged: Vec<Value> = history
+                        .iter()
+                        .map(|m| json!({ "role": m.role, "content": m.content }))
+                        .collect();
+                    merged.append(messages);
+                    *messages = merged;
+                } else if let Some(Value::String(prompt)) = obj.get_mut("prompt") {
+                    *prompt = format!("{}{}", Self::transcript(history), prompt);
+                } else {
+                    let messages: Vec<Value> = history
+                        .iter()
+                        .map(|m| json!({ "role": m.role, "content": m.content }))
+                        .collect();
+                    obj.insert("history".to_string(), Value::Array(messages));
+                }
+                payload
+            }
+            Value::String(prompt) => {
+                Value::String(format!("{}{}", Self::transcript(history), prompt))
+            }
+            _ => payload,
+        }
+    }
+
+    /// Appends a user/assistant exchange to the session and compacts the history
+    /// according to the session policy.
+    pub async fn record_exchange(
+        state: &AppState,
+        session_id: String,
+        request: &Value,
+        response: &Value,
+    ) -> Result<(), String> {
+        let db = &state.db;
+        let user_content = match request {
+            Value::Object(obj) => match obj.get("prompt").and_then(|v| v.as_str()) {
+                Some(prompt) => prompt.to_string(),
+                None => request.to_string(),
+            },
+            other => TaskRunner::response_text(other),
+        };
+        let assistant_content = TaskRunner::response_text(response);
+
+        // The session row stays locked until both messages are stored, so concurrent
+        // exchanges of a session take their positions one after the other
+        let txn = db.begin().await.map_err(|e| e.to_string())?;
+        let session = SessionRepository::lock(&txn, session_id.clone())
+            .await
+            .map_err(|e| format!("Database error fetching session: {}", e))?
+            .ok_or_else(|| format!("Session {} not found", session_id))?;
+
+        let history = SessionMessageRepository::find_by_session(&txn, session_id.clone())
+            .await
+            .map_err(|e| format!("Database error fetching session messages: {}", e))?;
+        let next_position = history.last().map(|m| m.position + 1).unwrap_or(0);
+
+        Self::append(&txn, &session_id, next_position, "user", user_content).await?;
+        Self::append(
+            &txn,
+            &session_id,
+            next_position + 1,
+            "assistant",
+            assistant_content,
+        )
+        .await?;
+        txn.commit().await.map_err(|e| e.to_string())?;
+
+        Self::compact(state, &session).await?;
+        SessionRepository::touch(db, session)
+            .await
+            .map_err(|e| format!("Failed to update session: {}", e))
+    }
+
+    /// Rough token estimation (~4 characters per token) used for the history budget.
+    pub fn estimate_tokens(text: &str) -> i32 {
+        (text.chars().count() as i32 + 3) / 4
+    }
+
+    async fn append<C: ConnectionTrait>(
+        db: &C,
+        session_id: &str,
+        position: i32,
+        role: &str,
+        content: String,
+    ) -> Result<session_message::Model, String> {
+        let message = session_message::ActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            session_id: Set(session_id.to_string()),
+            position: Set(position),
+            role: Set(role.to_string()),
+            token_count: Set(Self::estimate_tokens(&content)),
+            content: Set(content),
+            created_at: Set(None),
+        };
+
+        SessionMessageRepository::create(db, message)
+            .await
+            .map_err(|e| format!("Failed to store session message: {}", e))
+    }
+
+    /// Enforces the token budget by removing (or summarizing) the oldest messages.
+    async fn compact(state: &AppState, session: &session::Model) -> Result<(), String> {
+        let db = &state.db;
+        let max_tokens = match session.max_tokens {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+
+        let history = SessionMessageRepository::find_by_session(db, session.id.clone())
+            .await
+            .map_err(|e| format!("Database error fetching session messages: {}", e))?;
+        let evicted = Self::evictions(&history, max_tokens);
+        if evicted.is_empty() {
+            return Ok(());
+        }
+
+        let summary = if session.strategy == STRATEGY_SUMMARIZE {
+            Some(Self::summarize(state, session, &evicted).await?)
+        } else {
+            None
+        };
+
+        let txn = db.begin().await.map_err(|e| e.to_string())?;
+        SessionRepository::lock(&txn, session.id.clone())
+            .await
+            .map_err(|e| format!("Database error fetching session: {}", e))?;
+        let deleted = SessionMessageRepository::delete_many(
+            &txn,
+            evicted.iter().map(|m| m.id.clone()).collect(),
+        )
+        .await
+        .map_err(|e| format!("Failed to compact session: {}", e))?;
+        if deleted as usize != evicted.len() {
+            // A concurrent exchange already compacted these messages, the transaction
+            // is rolled back on drop
+            return Ok(());
+        }
+
+        if let Some(summary) = summary {
+            // The summary takes the slot of the most recent evicted message
+            let position = evicted.last().map(|m| m.position).unwrap_or(0);
+            Self::append(
+                &txn,
+                &session.id,
+                position,
+                "system",
+                format!("Summary of the earlier conversation: {}", summary),
+            )
+            .await?;
+        }
+
+        txn.commit().await.map_err(|e| e.to_string())
+    }
+
+    /// Oldest messages to evict to fit the history in `max_tokens`, the latest exchange
+    /// is always kept.
+    fn evictions(
+        history: &[session_message::Model],
+        max_tokens: i32,
+    ) -> Vec<session_message::Model> {
+        let mut total: i32 = history.iter().map(|m| m.token_count).sum();
+        let mut evicted = Vec::new();
+        for message in history.iter().take(history.len().saturating_sub(2)) {
+            if total <= max_tokens {
+                break;
+            }
+            total -= message.token_count;
+            evicted.push(message.clone());
+        }
+        evicted
+    }
+
+    /// Condenses evicted messages through the configured summarizer task.
+    async fn summarize(
+        state: &AppState,
+        session: &session::Model,
+        messages: &[session_message::Model],
+    ) -> Result<String, String> {
+        let task_id = session
+            .summarizer_task_id
+            .clone()
+            .ok_or_else(|| "Session has no summarizer task".to_string())?;
+        let task = AgentTaskService::get_task_by_id(&state.db, task_id.clone())
+            .await
+            .map_err(|e| format!("Database error fetching summarizer task: {}", e))?
+            .ok_or_else(|| format!("Summarizer task {} not found", task_id))?;
+
+        let transcript = Self::transcript(messages);
+        let payload = json!({ "text": transcript, "prompt": transcript });
+        let (response, _) = TaskRunner::execute(state, &task, &payload)
+            .await
+            .map_err(|e| format!("Failed to summarize session: {}", e.message))?;
+
+        Ok(match response.get("summary").and_then(|v| v.as_str()) {
+            Some(summary) => summary.to_string(),
+            None => TaskRunner::response_text(&response),
+        })
+    }
+
+    fn transcript(messages: &[session_message::Model]) -> String {
+        let mut transcript = String::new();
+        for message in messages {
+            transcript.push_str(&format!("{}: {}\n", message.role, message.content));
+        }
+        transcript.push('\n');
+        transcript
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(position: i32, role: &str, content: &str) -> session_message::Model {
+        session_message::Model {
+            id: format!("m{}", position),
+            session_id: "s".to_string(),
+            position,
+            role: role.to_string(),
+            content: content.to_string(),
+            token_count: Service::estimate_tokens(content),
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn estimates_four_characters_per_token() {
+        assert_eq!(Service::estimate_tokens(""), 0);
+        assert_eq!(Service::estimate_tokens("abc"), 1);
+        assert_eq!(Service::estimate_tokens("abcde"), 2);
+        assert_eq!(Service::estimate_tokens("ééééé"), 2);
+    }
+
+    #[test]
+    fn prepends_the_history_to_payloads() {
+        let history = vec![message(0, "user", "hi"), message(1, "assistant", "hello")];
+
+        let chat = json!({ "messages": [{ "role": "user", "content": "next" }] });
+        let merged = Service::apply_history(&history, &chat);
+        let roles: Vec<&str> = merged["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        assert_eq!(merged["messages"][2]["content"], json!("next"));
+
+        let prompt = Service::apply_history(&history, &json!({ "prompt": "next" }));
+        assert_eq!(
+            prompt["prompt"],
+            json!("user: hi\nassistant: hello\n\nnext")
+        );
+        let text = Service::apply_history(&history, &json!("next"));
+        assert_eq!(text, json!("user: hi\nassistant: hello\n\nnext"));
+
+        let other = Service::apply_history(&history, &json!({ "text": "next" }));
+        assert_eq!(other["history"][1]["content"], json!("hello"));
+        assert_eq!(other["text"], json!("next"));
+
+        let payload = json!({ "prompt": "next" });
+        assert_eq!(Service::apply_history(&[], &payload), payload);
+    }
+
+    #[test]
+    fn evicts_the_oldest_messages_but_the_latest_exchange() {
+        // 2 tokens per message
+        let history: Vec<_> = (0..6).map(|i| message(i, "user", "12345678")).collect();
+
+        assert!(Service::evictions(&history, 12).is_empty());
+        let evicted = Service::evictions(&history, 8);
+        assert_eq!(
+            evicted.iter().map(|m| m.position).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        // The latest exchange is kept even over budget
+        assert_eq!(Service::evictions(&history, 0).len(), 4);
+    }
+}
@@ -0,0 +1,400 @@
+use crate::models::{agent, agent_task};
+use crate::services::{
+    agent::Service as AgentService,
+    agent_client::{AgentCallError, ErrorClass, Service as AgentClient},
+    agent_log::Service as AgentLogService,
+    agent_task::Service as AgentTaskService,
+    agentic,
+    agentic::Service as AgenticService,
+    contract,
+    fallback::{FallbackPolicy, FallbackTarget},
+    response_cache::{CachePolicy, CacheScope, ResponseCache},
+};
+use crate::state::AppState;
+use sea_orm::DatabaseConnection;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+tokio::task_local! {
+    /* Ids of the agent logs written by the calls running in the scope, see `Service::traced` */
+    static WRITTEN_LOGS: Arc<Mutex<Vec<String>>>;
+}
+
+pub struct Service;
+
+impl Service {
+    /// Executes an agent task the same way the gateway does: resolves the owning agent,
+    /// builds the endpoint from the task path and traces the call in the agent logs.
+    /// Agentic tasks are delegated to the tool calling loop. When the task declares a
+    /// fallback chain, the alternative targets are tried in order on eligible failures.
+    /// The payload and the response are checked against the contracts of the task, a
+    /// response breaking the output contract is never cached.
+    pub async fn execute(
+        state: &AppState,
+        task: &agent_task::Model,
+        payload: &serde_json::Value,
+    ) -> Result<(serde_json::Value, i32), AgentCallError> {
+        contract::check_input(task, payload).map_err(|v| AgentCallError::contract(v, 0))?;
+
+        let call = async {
+            let (response, retries) = Self::execute_with_fallback(state, task, payload).await?;
+            contract::check_output(task, &response)
+                .map_err(|v| AgentCallError::contract(v, retries))?;
+            Ok((response, retries))
+        };
+        Self::with_cache(state, task, payload, call).await
+    }
+
+    /// Runs the primary target, then every fallback target while the failure class is
+    /// one the policy fails over on.
+    async fn execute_with_fallback(
+        state: &AppState,
+        task: &agent_task::Model,
+        payload: &serde_json::Value,
+    ) -> Result<(serde_json::Value, i32), AgentCallError> {
+        let policy = match FallbackPolicy::from_task(task) {
+            Ok(Some(policy)) => policy,
+            Ok(None) => return Self::dispatch(state, task, payload, None).await,
+            Err(e) => return Err(AgentCallError::internal(e)),
+        };
+
+        let mut total_retries = 0;
+        let mut last_error = None;
+
+        let attempts = std::iter::once(None).chain(policy.targets.iter().map(Some));
+        for (index, target) in attempts.enumerate() {
+            let (target_task, target_payload) = match target {
+                None => (task.clone(), payload.clone()),
+                Some(target) => {
+                    match Self::resolve_target(&state.db, task, payload, target).await {
+                        Ok(resolved) => resolved,
+                        Err(e) => {
+                            tracing::warn!(
+                                "Skipping fallback target {} of task {}: {}",
+                                index,
+                                task.name,
+                                e.message
+                            );
+                            last_error = Some(e);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            let label = serde_json::json!({
+                "index": index,
+                "task_id": target_task.id,
+                "agent_id": target_task.agent_id,
+                "model": target_payload.get("model"),
+                "provider": target_task.settings.as_ref().and_then(|s| s.get("provider")),
+            });
+
+            // The latency budget includes the wait for the rate limits of the target
+            let attempt = Self::dispatch(state, &target_task, &target_payload, Some(&label));
+            let result = match policy.latency_ms {
+                Some(ms) => tokio::time::timeout(Duration::from_millis(ms), attempt)
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(AgentCallError::new(
+                            ErrorClass::Timeout,
+                            format!("Exceeded the latency budget of {} ms", ms),
+                            0,
+                        ))
+                    }),
+                None => attempt.await,
+            };
+
+            match result {
+                Ok((response, retries)) => {
+                    if index > 0 {
+                        tracing::info!("Task {} served by fallback target {}", task.name, index);
+                    }
+                    return Ok((response, total_retries + retries));
+                }
+                Err(mut e) => {
+                    total_retries += e.retries;
+                    e.retries = total_retries;
+                    if !policy.on.contains(&e.class) {
+                        return Err(e);
+                    }
+                    tracing::warn!(
+                        "Target {} of task {} failed ({:?}): {}",
+                        index,
+                        task.name,
+                        e.class,
+                        e.message
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| AgentCallError::internal("No target left to try")))
+    }
+
+    /// Builds the task and payload of a fallback target, loading the replacement task if any
+    /// and checking that the agent it calls exists.
+    async fn resolve_target(
+        db: &DatabaseConnection,
+        task: &agent_task::Model,
+        payload: &serde_json::Value,
+        target: &FallbackTarget,
+    ) -> Result<(agent_task::Model, serde_json::Value), AgentCallError> {
+        let base = match &target.task_id {
+            Some(task_id) => {
+                let mut replacement = AgentTaskService::get_task_by_id(db, task_id.clone())
+                    .await
+                    .map_err(|e| {
+                        AgentCallError::internal(format!("Database error fetching task: {}", e))
+                    })?
+                    .ok_or_else(|| {
+                        AgentCallError::internal(format!("Fallback task {} not found", task_id))
+                    })?;
+                // Fallbacks are not chained: the replacement only runs its own target
+                if let Some(serde_json::Value::Object(settings)) = replacement.settings.as_mut() {
+                    settings.remove("fallback");
+                }
+                replacement
+            }
+            None => task.clone(),
+        };
+        let (resolved, payload) = target.apply(&base, payload);
+        // An unknown agent skips the target like an unknown task
+        Self::find_agent(db, resolved.agent_id.clone()).await?;
+        Ok((resolved, payload))
+    }
+
+    /// Runs a single target, without cache nor fallback.
+    async fn dispatch(
+        state: &AppState,
+        task: &agent_task::Model,
+        payload: &serde_json::Value,
+        target: Option<&serde_json::Value>,
+    ) -> Result<(serde_json::Value, i32), AgentCallError> {
+        if task.task_type == agentic::TASK_TYPE {
+            return AgenticService::run(state, task, payload, target).await;
+        }
+
+        let agent = Self::find_agent(&state.db, task.agent_id.clone()).await?;
+        let endpoint = Self::resolve_endpoint(&agent, task);
+        Self::call_and_log(
+            state,
+            &agent,
+            Some(&task.id),
+            &endpoint,
+            payload,
+            None,
+            target,
+        )
+        .await
+    }
+
+    /// Serves the call from the response cache when the task opted in and the payload
+    /// is deterministic, otherwise runs it and stores a successful response.
+    async fn with_cache<F>(
+        state: &AppState,
+        task: &agent_task::Model,
+        payload: &serde_json::Value,
+        call: F,
+    ) -> Result<(serde_json::Value, i32), AgentCallError>
+    where
+        F: Future<Output = Result<(serde_json::Value, i32), AgentCallError>>,
+    {
+        let policy = CachePolicy::from_task(task);
+        let scope = CacheScope::Task(task);
+        Self::cached(state, scope, policy, payload, call).await
+    }
+
+    /// Serves an agent call from the response cache when `policy` is set and the payload
+    /// is deterministic, otherwise runs it and stores a successful response. Shared by
+    /// the task runs and the direct agent calls of the gateway.
+    pub async fn cached<F>(
+        state: &AppState,
+        scope: CacheScope<'_>,
+        policy: Option<CachePolicy>,
+        payload: &serde_json::Value,
+        call: F,
+    ) -> Result<(serde_json::Value, i32), AgentCallError>
+    where
+        F: Future<Output = Result<(serde_json::Value, i32), AgentCallError>>,
+    {
+        let policy = match policy.filter(|p| p.applies_to(payload)) {
+            Some(policy) => policy,
+            None => return call.await,
+        };
+
+        let (cache_id, agent_id, task_id) = match scope {
+            CacheScope::Task(task) => (task.id.clone(), &task.agent_id, Some(task.id.clone())),
+            CacheScope::Agent(agent) => (format!("agent:{}", agent.id), &agent.id, None),
+        };
+        // Persisted entries belong to a task, direct agent calls stay in memory
+        let policy = CachePolicy {
+            persist: policy.persist && task_id.is_some(),
+            ..policy
+        };
+        let key = ResponseCache::key(&cache_id, payload);
+        if let Some(cached) = state.cache.get(&state.db, &key, &policy).await {
+            tracing::info!("Cache hit for {} ({})", cache_id, key);
+            Self::spawn_log(
+                &state.db,
+                agent_id.clone(),
+                task_id,
+                payload.clone(),
+                cached.clone(),
+                0,
+                true,
+                None,
+            );
+            return Ok((cached, 0));
+        }
+
+        let result = call.await;
+        if let Ok((response, _)) = &result {
+            state
+                .cache
+                .put(&state.db, key, &cache_id, response, &policy)
+                .await;
+        }
+        result
+    }
+
+    /// Loads the agent owning a task, mapping lookup failures to execution errors.
+    pub async fn find_agent(
+        db: &DatabaseConnection,
+        agent_id: String,
+    ) -> Result<agent::Model, AgentCallError> {
+        match AgentService::get_agent_by_id(db, agent_id.clone()).await {
+            Ok(Some(agent)) => Ok(agent),
+            Ok(None) => Err(AgentCallError::internal(format!(
+                "Agent {} not found for task",
+                agent_id
+            ))),
+            Err(e) => Err(AgentCallError::internal(format!(
+                "Database error fetching agent: {}",
+                e
+            ))),
+        }
+    }
+
+    /// Joins the agent base URL with the optional task path.
+    pub fn resolve_endpoint(agent: &agent::Model, task: &agent_task::Model) -> String {
+        let base_url = agent.endpoint.trim_end_matches('/');
+        match &task.path {
+            Some(p) => {
+                if p.starts_with('/') {
+                    format!("{}{}", base_url, p)
+                } else {
+                    format!("{}/{}", base_url, p)
+                }
+            }
+            None => base_url.to_string(),
+        }
+    }
+
+    /// Sends the payload with rate limiting and retry resilience, and stores the outcome in
+    /// the agent logs without blocking the caller. `credential` names the environment variable
+    /// holding the provider API key, `target` describes the fallback target being tried.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn call_and_log(
+        state: &AppState,
+        agent: &agent::Model,
+        task_id: Option<&str>,
+        endpoint: &str,
+        payload: &serde_json::Value,
+        credential: Option<&str>,
+        target: Option<&serde_json::Value>,
+    ) -> Result<(serde_json::Value, i32), AgentCallError> {
+        let result = AgentClient::execute_task(
+            &state.http_client,
+            &state.limiter,
+            agent,
+            endpoint,
+            payload,
+            credential,
+        )
+        .await;
+
+        let (response_json, retries_used) = match &result {
+            Ok((res, retries)) => (res.clone(), *retries),
+            Err(e) => (
+                serde_json::json!({ "error": e.message, "class": e.class }),
+                e.retries,
+            ),
+        };
+
+        Self::spawn_log(
+            &state.db,
+            agent.id.clone(),
+            task_id.map(|id| id.to_string()),
+            payload.clone(),
+            response_json,
+            retries_used,
+            false,
+            target.cloned(),
+        );
+
+        result
+    }
+
+    /// Runs `call` and returns the ids of the agent logs written meanwhile, in call order.
+    /// Lets flow steps link their records to the logs of the agent calls they made.
+    pub async fn traced<F: Future>(call: F) -> (F::Output, Vec<String>) {
+        let logs = Arc::new(Mutex::new(Vec::new()));
+        let output = WRITTEN_LOGS.scope(logs.clone(), call).await;
+        let ids = logs.lock().map(|ids| ids.clone()).unwrap_or_default();
+        (output, ids)
+    }
+
+    // Persists an agent log entry in the background so it never delays the caller.
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_log(
+        db: &DatabaseConnection,
+        agent_id: String,
+        task_id: Option<String>,
+        prompt: serde_json::Value,
+        response: serde_json::Value,
+        retries: i32,
+        cache_hit: bool,
+        target: Option<serde_json::Value>,
+    ) {
+        let id = Uuid::new_v4().to_string();
+        let _ = WRITTEN_LOGS.try_with(|logs| {
+            if let Ok(mut logs) = logs.lock() {
+                logs.push(id.clone());
+            }
+        });
+
+        let log_db = db.clone();
+        tokio::spawn(async move {
+            let _ = AgentLogService::create(
+                &log_db, id, agent_id, prompt, response, retries, task_id, cache_hit, target,
+            )
+            .await;
+        });
+    }
+
+    /// Extracts a clean string representation of an agent response, looking at the
+    /// usual `response` and `message.content` fields before falling back to raw JSON.
+    pub fn response_text(value: &serde_json::Value) -> String {
+        match value {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Object(obj) => {
+                if let Some(content) = obj.get("response").and_then(|v| v.as_str()) {
+                    content.to_string()
+                } else if let Some(content) = obj
+                    .get("message")
+                    .and_then(|v| v.get("content"))
+                    .and_then(|v| v.as_str())
+                {
+                    content.to_string()
+                } else {
+                    value.to_string()
+                }
+            }
+            v => v.to_string(),
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use crate::services::{
+    flow_worker::RunningExecutions, rate_limiter::RateLimiter, response_cache::ResponseCache,
+};
 use aether_core::Director;
 use sea_orm::DatabaseConnection;
 
@@ -6,4 +9,7 @@ pub struct AppState {
     pub db: DatabaseConnection,
     pub director: Director,
     pub http_client: reqwest::Client,
+    pub cache: ResponseCache,
+    pub limiter: RateLimiter,
+    pub executions: RunningExecutions,
 }